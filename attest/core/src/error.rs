@@ -614,6 +614,10 @@ pub enum VerifyError {
     EpidPseudonym(EpidPseudonymError),
     #[fail(display = "The quote in a verification report does not match the expected quote.")]
     IasQuoteMismatch,
+    #[fail(display = "The verification report timestamp could not be parsed")]
+    Timestamp,
+    #[fail(display = "The verification report issued at {} is too old at {}", _0, _1)]
+    StaleReport(u64, u64),
     #[fail(display = "There was an unknown error")]
     Unknown,
 }
@@ -128,8 +128,8 @@ impl ToBase64 for EpidPseudonym {
 pub struct VerificationReportData {
     /// A unqiue ID of this report
     pub id: String,
-    /// The timestamp this report was generated, as the duration since time::SystemTime::UNIX_EPOCH
-    /// FIXME: It would be nice to parse this into something useful, but it's not really required
+    /// The UTC time this report was generated, as an ISO 8601 string without a zone designator.
+    /// Use `parse_timestamp()` to get it as seconds since the unix epoch.
     pub timestamp: String,
     /// The version number of the API which generated this report.
     pub version: f64, // ugh.
@@ -234,6 +234,99 @@ impl VerificationReportData {
 
         Ok(())
     }
+
+    /// Parse the report timestamp into seconds since the unix epoch, truncating any fractional
+    /// seconds.
+    pub fn parse_timestamp(&self) -> Result<u64, VerifyError> {
+        parse_ias_timestamp(&self.timestamp).ok_or(VerifyError::Timestamp)
+    }
+
+    /// Verify the report was generated no more than `max_age_secs` before `now_unix_secs`.
+    ///
+    /// Reports timestamped after `now_unix_secs` are accepted, so clock skew between IAS and the
+    /// caller does not cause spurious failures.
+    pub fn verify_freshness(
+        &self,
+        now_unix_secs: u64,
+        max_age_secs: u64,
+    ) -> Result<(), VerifyError> {
+        let issued_at = self.parse_timestamp()?;
+        if now_unix_secs.saturating_sub(issued_at) > max_age_secs {
+            return Err(VerifyError::StaleReport(issued_at, now_unix_secs));
+        }
+        Ok(())
+    }
+}
+
+fn is_leap_year(year: u64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: u64, month: u64) -> u64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Parse an IAS `YYYY-MM-DDThh:mm:ss[.ffffff]` UTC timestamp into seconds since the unix epoch.
+fn parse_ias_timestamp(timestamp: &str) -> Option<u64> {
+    let timestamp = timestamp.trim_end_matches('Z');
+    let (datetime, fraction) = match timestamp.find('.') {
+        Some(pos) => (&timestamp[..pos], &timestamp[pos + 1..]),
+        None => (timestamp, ""),
+    };
+    if !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let bytes = datetime.as_bytes();
+    if bytes.len() != 19
+        || bytes[4] != b'-'
+        || bytes[7] != b'-'
+        || bytes[10] != b'T'
+        || bytes[13] != b':'
+        || bytes[16] != b':'
+    {
+        return None;
+    }
+
+    let field = |start: usize, end: usize| -> Option<u64> {
+        let digits = &datetime[start..end];
+        if digits.bytes().all(|b| b.is_ascii_digit()) {
+            digits.parse().ok()
+        } else {
+            None
+        }
+    };
+    let year = field(0, 4)?;
+    let month = field(5, 7)?;
+    let day = field(8, 10)?;
+    let hour = field(11, 13)?;
+    let minute = field(14, 16)?;
+    let second = field(17, 19)?;
+
+    if year < 1970
+        || month == 0
+        || month > 12
+        || day == 0
+        || day > days_in_month(year, month)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return None;
+    }
+
+    let days = (1970..year)
+        .map(|y| if is_leap_year(y) { 366 } else { 365 })
+        .sum::<u64>()
+        + (1..month).map(|m| days_in_month(year, m)).sum::<u64>()
+        + (day - 1);
+
+    Some(days * 86_400 + hour * 3_600 + minute * 60 + second)
 }
 
 impl<'src> TryFrom<&'src VerificationReport> for VerificationReportData {
@@ -704,4 +797,58 @@ mod test {
         let _data = VerificationReportData::try_from(&report)
             .expect("Could not parse IAS verification report");
     }
+
+    #[test]
+    fn test_parse_timestamp() {
+        let report = VerificationReport {
+            sig: VerificationSignature::default(),
+            chain: Vec::default(),
+            http_body: String::from(IAS_WITH_PIB),
+        };
+        let data = VerificationReportData::try_from(&report)
+            .expect("Could not parse IAS verification report");
+
+        // "2019-06-19T22:11:17.616333"
+        assert_eq!(data.parse_timestamp(), Ok(1_560_982_277));
+
+        assert_eq!(parse_ias_timestamp("1970-01-01T00:00:00"), Some(0));
+        assert_eq!(parse_ias_timestamp("2000-02-29T00:00:00Z"), Some(951_782_400));
+        assert_eq!(parse_ias_timestamp("2019-02-29T00:00:00"), None);
+        assert_eq!(parse_ias_timestamp("2019-06-19 22:11:17"), None);
+        assert_eq!(parse_ias_timestamp("2019-06-19T22:11:17.6x"), None);
+        assert_eq!(parse_ias_timestamp("FIXME"), None);
+    }
+
+    #[test]
+    fn test_verify_freshness() {
+        let report = VerificationReport {
+            sig: VerificationSignature::default(),
+            chain: Vec::default(),
+            http_body: String::from(IAS_WITH_PIB),
+        };
+        let data = VerificationReportData::try_from(&report)
+            .expect("Could not parse IAS verification report");
+        let issued_at = 1_560_982_277;
+        let max_age = 3_600;
+
+        // Fresh, including at exactly the maximum age, and slightly in the future.
+        assert_eq!(data.verify_freshness(issued_at, max_age), Ok(()));
+        assert_eq!(data.verify_freshness(issued_at + max_age, max_age), Ok(()));
+        assert_eq!(data.verify_freshness(issued_at - 60, max_age), Ok(()));
+
+        // Stale
+        let now = issued_at + max_age + 1;
+        assert_eq!(
+            data.verify_freshness(now, max_age),
+            Err(VerifyError::StaleReport(issued_at, now))
+        );
+
+        // Unparseable timestamps are never considered fresh.
+        let mut bad_timestamp = data.clone();
+        bad_timestamp.timestamp = String::from("FIXME");
+        assert_eq!(
+            bad_timestamp.verify_freshness(issued_at, max_age),
+            Err(VerifyError::Timestamp)
+        );
+    }
 }
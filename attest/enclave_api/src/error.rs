@@ -74,6 +74,14 @@ pub enum Error {
     /// The connection could not be found by channel binding or node ID.
     #[fail(display = "Connection not found by node ID or session")]
     NotFound,
+
+    /// An authentication request identical to one already accepted was received again.
+    #[fail(display = "Authentication request was replayed")]
+    ReplayedAuthRequest,
+
+    /// No more sessions of the requested kind may be established.
+    #[fail(display = "Session limit reached")]
+    SessionLimitReached,
 }
 
 impl From<AkeError> for Error {
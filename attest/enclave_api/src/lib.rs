@@ -62,6 +62,12 @@ impl Into<Vec<u8>> for PeerAuthRequest {
     }
 }
 
+impl AsRef<[u8]> for PeerAuthRequest {
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_ref()
+    }
+}
+
 /// The raw authentication response message, sent from a responder to an initiator.
 #[derive(Clone, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 pub struct PeerAuthResponse(Vec<u8>);
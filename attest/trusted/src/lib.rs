@@ -68,6 +68,12 @@ pub fn unseal<S: Sealed>(obj: &S) -> Result<S::Source, UnsealingError> {
 pub trait SealAlgo: Sized {
     /// Takes plaintext and optional additional text that is under the mac, produces sealed blob
     fn seal_raw(plaintext: &[u8], additional_mac_txt: &[u8]) -> Result<Self, IntelSealingError>;
+    /// As `seal_raw`, but sealed to the enclave signer rather than the enclave itself, so that
+    /// a later enclave from the same signer can unseal it
+    fn seal_raw_to_signer(
+        plaintext: &[u8],
+        additional_mac_txt: &[u8],
+    ) -> Result<Self, IntelSealingError>;
     /// Takes a sealed blob, reproduces the plaintext and the additional mac text, in that order
     fn unseal_raw(&self) -> SgxResult<(Vec<u8>, Vec<u8>)>;
 }
@@ -81,6 +87,17 @@ impl SealAlgo for IntelSealed {
 
         Ok(Self::try_from(result)?)
     }
+    fn seal_raw_to_signer(
+        plaintext: &[u8],
+        additional_mac_txt: &[u8],
+    ) -> Result<Self, IntelSealingError> {
+        let result_len =
+            sgx_compat::calc_sealed_data_size(plaintext.len(), additional_mac_txt.len())?;
+        let mut result = vec![0u8; result_len as usize];
+        sgx_compat::seal_data_to_signer(plaintext, additional_mac_txt, &mut result[..])?;
+
+        Ok(Self::try_from(result)?)
+    }
     fn unseal_raw(&self) -> SgxResult<(Vec<u8>, Vec<u8>)> {
         let (plaintext_len, mac_txt_len) = sgx_compat::get_sealed_payload_sizes(self.as_ref())?;
         let mut plaintext = vec![0u8; plaintext_len as usize];
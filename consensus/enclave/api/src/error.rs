@@ -3,8 +3,9 @@
 //! Enclave API Errors
 
 use alloc::string::String;
-use attest::SgxError;
+use attest::{NonceError, QuoteError, SgxError, SignatureError, VerifyError};
 use attest_enclave_api::Error as AttestEnclaveError;
+use common::ResponderId;
 use failure::Fail;
 use keys::Ed25519SignatureError;
 use mcserial::{
@@ -14,7 +15,7 @@ use mcserial::{
 use message_cipher::CipherError as MessageCipherError;
 use serde::{Deserialize, Serialize};
 use sgx_compat::sync::PoisonError;
-use transaction::validation::TransactionValidationError;
+use transaction::{ring_signature::KeyImage, tx::TxHash, validation::TransactionValidationError};
 
 /// An enumeration of errors which can occur inside a consensus enclave.
 #[derive(Clone, Debug, Deserialize, Fail, PartialEq, PartialOrd, Serialize)]
@@ -54,6 +55,338 @@ pub enum Error {
     /// Signature error
     #[fail(display = "Signature error")]
     Signature,
+
+    /// The transaction failed the in-enclave well-formedness check.
+    #[fail(display = "Well-formedness check failed: {}", _0)]
+    WellFormedCheck(WellFormedError),
+
+    /// The requested block version is not supported by this enclave.
+    #[fail(display = "Unsupported block version: {}", _0)]
+    UnsupportedBlockVersion(u32),
+
+    /// The transaction's tombstone block has been reached, so it can no longer be included in
+    /// the ledger.
+    #[fail(display = "Tombstone block {} expired at block {}", tombstone, current)]
+    TombstoneExpired { tombstone: u64, current: u64 },
+
+    /// The transaction's tombstone block lies outside the window set by `set_tombstone_window`.
+    #[fail(
+        display = "Tombstone block {} is outside the admitted window [{}, {}]",
+        tombstone, earliest, latest
+    )]
+    TombstoneOutsideWindow {
+        tombstone: u64,
+        earliest: u64,
+        latest: u64,
+    },
+
+    /// A transaction does not fit in the requested peer message chunk size.
+    #[fail(display = "Chunk size of {} bytes is too small to hold a transaction", _0)]
+    ChunkTooSmall(usize),
+
+    /// More than one transaction in a candidate block spends the same key image.
+    #[fail(display = "Duplicate key image in block: {:?}", _0)]
+    DuplicateKeyImageInBlock(KeyImage),
+
+    /// A client proposal is larger than the enclave accepts.
+    #[fail(display = "Proposal of {} bytes exceeds the limit of {} bytes", size, limit)]
+    ProposalTooLarge { size: usize, limit: usize },
+
+    /// `enclave_init` was called again with different arguments.
+    #[fail(display = "Enclave already initialized with different arguments")]
+    AlreadyInitialized,
+
+    /// An operation that uses the block signing key was called before `enclave_init`.
+    #[fail(display = "Enclave not initialized")]
+    NotInitialized,
+
+    /// A sealed blob was sealed for another purpose, e.g. a block signing key was passed to
+    /// `unseal_blob`.
+    #[fail(display = "Sealed blob was sealed for a different purpose")]
+    SealedPurposeMismatch,
+
+    /// A block was formed at a height no higher than the last block the enclave signed.
+    #[fail(display = "Refusing to sign block {}, already signed block {}", attempted, last)]
+    NonMonotonicBlock { attempted: u64, last: u64 },
+
+    /// The peer's enclave measurement is not in the allow-list set by `set_peer_identity_policy`.
+    #[fail(display = "Peer enclave measurement is not allowed")]
+    PeerIdentityNotAllowed,
+
+    /// An IAS report answers a quote nonce which an earlier verified report already consumed, so
+    /// it is a replay of a stale response.
+    #[fail(display = "IAS report nonce was already consumed")]
+    NonceAlreadyConsumed,
+
+    /// The parent block's id does not match its contents, so a block formed on it would not
+    /// append to the chain.
+    #[fail(display = "Parent block id does not match its contents")]
+    BrokenChain,
+
+    /// A locally encrypted transaction failed its integrity check.
+    #[fail(display = "Locally encrypted transaction failed its integrity check")]
+    Integrity,
+
+    /// The redacted transactions given for a block do not hash to the block's contents hash.
+    #[fail(display = "Redacted transactions do not match the block contents")]
+    BlockContentsMismatch,
+
+    /// The hash given to `verify_tx_hash` is not the hash of the transaction.
+    #[fail(display = "Transaction hash does not match the transaction")]
+    TxHashMismatch,
+
+    /// The fees of the transactions in a block do not fit in a `u64`.
+    #[fail(display = "Aggregate fee overflowed")]
+    FeeOverflow,
+
+    /// The fees of the transactions in a block add up to more than `BlockLimits::max_fee`.
+    #[fail(display = "Aggregate fee {} exceeds the cap of {}", total_fee, max_fee)]
+    BlockFeeCapExceeded { total_fee: u64, max_fee: u64 },
+
+    /// A transaction refers to a TxOut beyond the end of the ledger the proofs were made against.
+    #[fail(
+        display = "TxOut index {} is out of range for a ledger of {} TxOuts",
+        index, ledger_size
+    )]
+    IndexOutOfRange { index: u64, ledger_size: u64 },
+
+    /// Untrusted supplied a different number of membership proofs than the transaction has ring
+    /// members.
+    #[fail(display = "Expected {} membership proofs, got {}", expected, got)]
+    ProofCountMismatch { expected: usize, got: usize },
+
+    /// A transaction relayed by a peer is one untrusted reported as already known, so it was not
+    /// re-encrypted.
+    #[fail(display = "Transaction is already known: {:?}", _0)]
+    KnownTx(TxHash),
+
+    /// A responder id passed to `enclave_init` is not of the `host:port` form.
+    #[fail(display = "Invalid responder id: {:?}", _0)]
+    InvalidResponderId(ResponderId),
+
+    /// A well-formed encrypted tx was encrypted with a layout this enclave does not know, e.g.
+    /// by an older enclave before an upgrade.
+    #[fail(display = "Unsupported well-formed tx format version: {}", _0)]
+    UnsupportedTxFormat(u8),
+
+    /// The membership proofs share the root of the ledger at a block this enclave formed below
+    /// the current block index, so they were made against an outdated ledger. Only roots of the
+    /// last `INCLUSION_HISTORY_BLOCKS` blocks formed since the enclave started are checked, so
+    /// not getting this error does not mean the proofs are current.
+    #[fail(display = "Membership proofs are against the ledger at block {}", index)]
+    StaleMembershipProof { index: u64 },
+
+    /// No call timings were recorded, either because the enclave was built without the
+    /// `profiling` feature, or because no timed call was made yet.
+    #[fail(display = "No call timings are available")]
+    ProfilingUnavailable,
+
+    /// The `BlockLimits` leave out every candidate transaction, so there is no block to form.
+    #[fail(
+        display = "No candidate fits within the limits of {} txs and {} bytes",
+        max_txs, max_bytes
+    )]
+    NoTxFitsBlockLimits { max_txs: u32, max_bytes: u32 },
+
+    /// A chunk from `txs_for_peer_chunked` is already in the set of chunks being reassembled.
+    #[fail(display = "Chunk {} was already received", _0)]
+    DuplicateChunk(u32),
+
+    /// A chunk from `txs_for_peer_chunked` does not belong to the set of chunks being
+    /// reassembled, because its index is out of range or its total differs from theirs.
+    #[fail(display = "Chunk {} of {} does not belong to this set", index, total)]
+    ChunkMismatch { index: u32, total: u32 },
+
+    /// A set of chunks from `txs_for_peer_chunked` is incomplete, starting with the chunk at
+    /// this index.
+    #[fail(display = "Chunk {} was not received", _0)]
+    MissingChunk(u32),
+}
+
+/// A coarse, stable classification of an `Error`, which `no_std` callers can branch on without
+/// matching every variant or inspecting display strings.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum ErrorKind {
+    /// Attestation failed, or no attestation evidence is available yet.
+    Attestation,
+    /// A signature or local encryption operation failed.
+    Crypto,
+    /// The input (a transaction, proof, or serialized message) was invalid.
+    Malformed,
+    /// A peer or client session was unknown, or its handshake or encryption failed.
+    Session,
+    /// Communicating with the SGX platform failed.
+    Io,
+    /// Anything else, including internal errors.
+    Other,
+}
+
+impl Error {
+    /// Classify this error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Sgx(_) => ErrorKind::Io,
+            Error::Attest(err) => match err {
+                AttestEnclaveError::Sgx(_) => ErrorKind::Io,
+                AttestEnclaveError::Kex(_)
+                | AttestEnclaveError::Cipher(_)
+                | AttestEnclaveError::NotFound
+                | AttestEnclaveError::ReplayedAuthRequest
+                | AttestEnclaveError::SessionLimitReached => ErrorKind::Session,
+                AttestEnclaveError::Nonce(_)
+                | AttestEnclaveError::Quote(_)
+                | AttestEnclaveError::Verify(_)
+                | AttestEnclaveError::InvalidState
+                | AttestEnclaveError::NoReportAvailable
+                | AttestEnclaveError::TooManyPendingReports => ErrorKind::Attestation,
+                AttestEnclaveError::NotInit
+                | AttestEnclaveError::AlreadyInit
+                | AttestEnclaveError::Poison => ErrorKind::Other,
+            },
+            Error::PeerIdentityNotAllowed | Error::NonceAlreadyConsumed => ErrorKind::Attestation,
+            Error::CacheCipher(_) | Error::Signature | Error::Integrity => ErrorKind::Crypto,
+            Error::Serialization
+            | Error::MalformedTx(_)
+            | Error::InvalidLocalMembershipProof
+            | Error::WellFormedCheck(_)
+            | Error::TombstoneExpired { .. }
+            | Error::TombstoneOutsideWindow { .. }
+            | Error::DuplicateKeyImageInBlock(_)
+            | Error::ProposalTooLarge { .. }
+            | Error::SealedPurposeMismatch
+            | Error::BrokenChain
+            | Error::BlockContentsMismatch
+            | Error::TxHashMismatch
+            | Error::FeeOverflow
+            | Error::BlockFeeCapExceeded { .. }
+            | Error::IndexOutOfRange { .. }
+            | Error::ProofCountMismatch { .. }
+            | Error::StaleMembershipProof { .. }
+            | Error::DuplicateChunk(_)
+            | Error::ChunkMismatch { .. }
+            | Error::MissingChunk(_) => ErrorKind::Malformed,
+            Error::Poison
+            | Error::RedactTxs(_)
+            | Error::UnsupportedBlockVersion(_)
+            | Error::UnsupportedTxFormat(_)
+            | Error::ChunkTooSmall(_)
+            | Error::AlreadyInitialized
+            | Error::NotInitialized
+            | Error::NonMonotonicBlock { .. }
+            | Error::KnownTx(_)
+            | Error::InvalidResponderId(_)
+            | Error::ProfilingUnavailable
+            | Error::NoTxFitsBlockLimits { .. } => ErrorKind::Other,
+        }
+    }
+
+    /// A constant, redacted description of this error's variant, which is safe to log or use as
+    /// a metrics label in untrusted code. None of the data carried by the variant is included.
+    pub fn display_safe(&self) -> &'static str {
+        match self {
+            Error::Sgx(_) => "sgx",
+            Error::Attest(_) => "attest",
+            Error::CacheCipher(_) => "cache_cipher",
+            Error::Serialization => "serialization",
+            Error::Poison => "poison",
+            Error::MalformedTx(_) => "malformed_tx",
+            Error::InvalidLocalMembershipProof => "invalid_local_membership_proof",
+            Error::RedactTxs(_) => "redact_txs",
+            Error::Signature => "signature",
+            Error::WellFormedCheck(_) => "well_formed_check",
+            Error::UnsupportedBlockVersion(_) => "unsupported_block_version",
+            Error::TombstoneExpired { .. } => "tombstone_expired",
+            Error::TombstoneOutsideWindow { .. } => "tombstone_outside_window",
+            Error::ChunkTooSmall(_) => "chunk_too_small",
+            Error::DuplicateKeyImageInBlock(_) => "duplicate_key_image_in_block",
+            Error::ProposalTooLarge { .. } => "proposal_too_large",
+            Error::AlreadyInitialized => "already_initialized",
+            Error::NotInitialized => "not_initialized",
+            Error::SealedPurposeMismatch => "sealed_purpose_mismatch",
+            Error::NonMonotonicBlock { .. } => "non_monotonic_block",
+            Error::PeerIdentityNotAllowed => "peer_identity_not_allowed",
+            Error::NonceAlreadyConsumed => "nonce_already_consumed",
+            Error::BrokenChain => "broken_chain",
+            Error::Integrity => "integrity",
+            Error::BlockContentsMismatch => "block_contents_mismatch",
+            Error::TxHashMismatch => "tx_hash_mismatch",
+            Error::FeeOverflow => "fee_overflow",
+            Error::BlockFeeCapExceeded { .. } => "block_fee_cap_exceeded",
+            Error::IndexOutOfRange { .. } => "index_out_of_range",
+            Error::ProofCountMismatch { .. } => "proof_count_mismatch",
+            Error::KnownTx(_) => "known_tx",
+            Error::InvalidResponderId(_) => "invalid_responder_id",
+            Error::UnsupportedTxFormat(_) => "unsupported_tx_format",
+            Error::StaleMembershipProof { .. } => "stale_membership_proof",
+            Error::ProfilingUnavailable => "profiling_unavailable",
+            Error::NoTxFitsBlockLimits { .. } => "no_tx_fits_block_limits",
+            Error::DuplicateChunk(_) => "duplicate_chunk",
+            Error::ChunkMismatch { .. } => "chunk_mismatch",
+            Error::MissingChunk(_) => "missing_chunk",
+        }
+    }
+}
+
+/// The concrete reasons a transaction can fail `tx_is_well_formed`.
+///
+/// Every field carried here is safe to hand to untrusted code and to the submitting client: fees
+/// and tombstone blocks come from the transaction the client built itself, block indices describe
+/// public ledger state, and key images are published in every block. Nothing derived from the
+/// decrypted transaction's secret contents (amounts, blindings, onetime keys) is exposed.
+#[derive(Clone, Debug, Deserialize, Fail, PartialEq, PartialOrd, Serialize)]
+pub enum WellFormedError {
+    /// The fee paid is lower than the minimum fee enforced by the enclave.
+    #[fail(display = "Insufficient fee: paid {}, minimum is {}", fee, minimum_fee)]
+    InsufficientFee { fee: u64, minimum_fee: u64 },
+
+    /// The transaction's tombstone block is too far in the future.
+    #[fail(
+        display = "Tombstone block {} is too far ahead of block {}",
+        tombstone_block, block_index
+    )]
+    TombstoneBlockTooFar {
+        tombstone_block: u64,
+        block_index: u64,
+    },
+
+    /// The transaction spends the same key image more than once.
+    #[fail(display = "Duplicate key image: {:?}", _0)]
+    DuplicateKeyImage(KeyImage),
+
+    /// The transaction spends a key image untrusted reported as already spent.
+    #[fail(display = "Key image already spent: {:?}", _0)]
+    SpentKeyImage(KeyImage),
+
+    /// The transaction's ring signature is invalid.
+    #[fail(display = "Invalid ring signature")]
+    InvalidRingSignature,
+
+    /// Any other validation failure.
+    #[fail(display = "Transaction validation error: {}", _0)]
+    Validation(TransactionValidationError),
+}
+
+impl From<WellFormedError> for TransactionValidationError {
+    fn from(src: WellFormedError) -> Self {
+        match src {
+            WellFormedError::InsufficientFee { .. } => TransactionValidationError::TxFeeError,
+            WellFormedError::TombstoneBlockTooFar { .. } => {
+                TransactionValidationError::TombstoneBlockTooFar
+            }
+            WellFormedError::DuplicateKeyImage(_) => TransactionValidationError::DuplicateKeyImages,
+            WellFormedError::SpentKeyImage(_) => TransactionValidationError::ContainsSpentKeyImage,
+            WellFormedError::InvalidRingSignature => {
+                TransactionValidationError::InvalidTransactionSignature
+            }
+            WellFormedError::Validation(err) => err,
+        }
+    }
+}
+
+impl From<WellFormedError> for Error {
+    fn from(src: WellFormedError) -> Error {
+        Error::WellFormedCheck(src)
+    }
 }
 
 impl From<MessageCipherError> for Error {
@@ -110,8 +443,169 @@ impl From<AttestEnclaveError> for Error {
     }
 }
 
+impl From<NonceError> for Error {
+    fn from(src: NonceError) -> Error {
+        Error::Attest(src.into())
+    }
+}
+
+impl From<QuoteError> for Error {
+    fn from(src: QuoteError) -> Error {
+        Error::Attest(src.into())
+    }
+}
+
+impl From<SignatureError> for Error {
+    fn from(src: SignatureError) -> Error {
+        Error::Attest(src.into())
+    }
+}
+
+impl From<VerifyError> for Error {
+    fn from(src: VerifyError) -> Error {
+        Error::Attest(src.into())
+    }
+}
+
 impl From<Ed25519SignatureError> for Error {
     fn from(_src: Ed25519SignatureError) -> Error {
         Error::Signature
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::collections::BTreeSet;
+
+    #[test]
+    fn test_error_kind() {
+        assert_eq!(
+            Error::Attest(AttestEnclaveError::NoReportAvailable).kind(),
+            ErrorKind::Attestation
+        );
+        assert_eq!(
+            Error::Attest(AttestEnclaveError::Verify(VerifyError::Timestamp)).kind(),
+            ErrorKind::Attestation
+        );
+        assert_eq!(Error::Attest(AttestEnclaveError::NotFound).kind(), ErrorKind::Session);
+        assert_eq!(Error::Signature.kind(), ErrorKind::Crypto);
+        assert_eq!(Error::Serialization.kind(), ErrorKind::Malformed);
+        assert_eq!(
+            Error::MalformedTx(TransactionValidationError::InvalidRangeProof).kind(),
+            ErrorKind::Malformed
+        );
+        assert_eq!(
+            Error::TombstoneExpired {
+                tombstone: 1,
+                current: 2
+            }
+            .kind(),
+            ErrorKind::Malformed
+        );
+        assert_eq!(Error::Poison.kind(), ErrorKind::Other);
+        assert_eq!(Error::ChunkTooSmall(0).kind(), ErrorKind::Other);
+    }
+
+    #[test]
+    fn test_display_safe() {
+        // `Error::Sgx` is omitted, since an `SgxError` cannot be built without `sgx_types`.
+        let errors = [
+            Error::Attest(AttestEnclaveError::NotFound),
+            Error::CacheCipher(MessageCipherError::MacFailure),
+            Error::Serialization,
+            Error::Poison,
+            Error::MalformedTx(TransactionValidationError::InvalidRangeProof),
+            Error::InvalidLocalMembershipProof,
+            Error::RedactTxs(String::from("secret")),
+            Error::Signature,
+            Error::WellFormedCheck(WellFormedError::InvalidRingSignature),
+            Error::UnsupportedBlockVersion(7),
+            Error::TombstoneExpired {
+                tombstone: 1,
+                current: 2,
+            },
+            Error::TombstoneOutsideWindow {
+                tombstone: 1,
+                earliest: 2,
+                latest: 3,
+            },
+            Error::ChunkTooSmall(0),
+            Error::DuplicateKeyImageInBlock(KeyImage::default()),
+            Error::ProposalTooLarge { size: 2, limit: 1 },
+            Error::AlreadyInitialized,
+            Error::NotInitialized,
+            Error::SealedPurposeMismatch,
+            Error::NonMonotonicBlock {
+                attempted: 1,
+                last: 2,
+            },
+            Error::PeerIdentityNotAllowed,
+            Error::NonceAlreadyConsumed,
+            Error::BrokenChain,
+            Error::Integrity,
+            Error::BlockContentsMismatch,
+            Error::TxHashMismatch,
+            Error::FeeOverflow,
+            Error::BlockFeeCapExceeded {
+                total_fee: 2,
+                max_fee: 1,
+            },
+            Error::IndexOutOfRange {
+                index: 2,
+                ledger_size: 1,
+            },
+            Error::KnownTx(TxHash::default()),
+            Error::InvalidResponderId(ResponderId::default()),
+            Error::UnsupportedTxFormat(2),
+            Error::StaleMembershipProof { index: 1 },
+            Error::ProfilingUnavailable,
+            Error::NoTxFitsBlockLimits {
+                max_txs: 0,
+                max_bytes: 1,
+            },
+            Error::ProofCountMismatch {
+                expected: 11,
+                got: 10,
+            },
+            Error::DuplicateChunk(1),
+            Error::ChunkMismatch { index: 2, total: 1 },
+            Error::MissingChunk(1),
+        ];
+
+        let mut labels = BTreeSet::new();
+        for err in errors.iter() {
+            let label = err.display_safe();
+            assert!(!label.is_empty());
+            // Nothing carried by the variant leaks into the label.
+            assert!(!label.contains("secret"));
+            labels.insert(label);
+        }
+        // Every variant gets its own label.
+        assert_eq!(labels.len(), errors.len());
+    }
+
+    #[test]
+    fn test_from_attest_errors() {
+        assert_eq!(
+            Error::from(NonceError::Mismatch),
+            Error::Attest(AttestEnclaveError::Nonce(NonceError::Mismatch))
+        );
+        assert_eq!(
+            Error::from(QuoteError::InvalidUtf8),
+            Error::Attest(AttestEnclaveError::Quote(QuoteError::InvalidUtf8))
+        );
+        assert_eq!(
+            Error::from(VerifyError::Timestamp),
+            Error::Attest(AttestEnclaveError::Verify(VerifyError::Timestamp))
+        );
+        // A bad IAS signature stays distinguishable from other verification failures.
+        assert_eq!(
+            Error::from(SignatureError::BadSignature),
+            Error::Attest(AttestEnclaveError::Verify(VerifyError::Signature(
+                SignatureError::BadSignature
+            )))
+        );
+        assert_eq!(Error::from(VerifyError::Timestamp).kind(), ErrorKind::Attestation);
+    }
+}
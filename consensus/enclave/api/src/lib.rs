@@ -9,19 +9,36 @@ extern crate alloc;
 mod error;
 mod messages;
 
-pub use crate::{error::Error, messages::EnclaveCall};
+#[cfg(any(test, feature = "test_utils"))]
+pub mod test_utils;
 
-use alloc::vec::Vec;
-use attest::{IasNonce, Quote, QuoteNonce, Report, TargetInfo, VerificationReport};
+pub use crate::{
+    error::{Error, ErrorKind, WellFormedError},
+    messages::EnclaveCall,
+};
+
+use alloc::{
+    collections::{BTreeMap, BinaryHeap},
+    vec::Vec,
+};
+use attest::{
+    IasNonce, Measurement, MrEnclave, Quote, QuoteNonce, Report, ReportBody, TargetInfo,
+    VerificationReport, VerificationReportData,
+};
 use attest_enclave_api::{
-    ClientAuthRequest, ClientAuthResponse, ClientSession, EnclaveMessage, PeerAuthRequest,
-    PeerAuthResponse, PeerSession,
+    ClientAuthRequest, ClientAuthResponse, ClientSession, EnclaveMessage,
+    Error as AttestEnclaveError, PeerAuthRequest, PeerAuthResponse, PeerSession,
 };
+use bitflags::bitflags;
 use common::ResponderId;
-use core::{hash::Hash, result::Result as StdResult};
-use keys::{Ed25519Public, X25519Public};
+use core::{cmp::Ordering, convert::TryFrom, hash::Hash, result::Result as StdResult};
+use digest::Input;
+use keys::{Ed25519Public, Ed25519Signature, Ed25519SignatureError, Verifier, X25519Public};
+use prost::Message;
 use serde::{Deserialize, Serialize};
 use transaction::{
+    blake2b_256::Blake2b256,
+    constants::MAX_TRANSACTIONS_PER_BLOCK,
     ring_signature::KeyImage,
     tx::{Tx, TxHash, TxOutMembershipProof},
     Block, BlockSignature, RedactedTx,
@@ -39,6 +56,83 @@ pub struct LocallyEncryptedTx(pub Vec<u8>);
 #[derive(Clone, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 pub struct WellFormedEncryptedTx(pub Vec<u8>);
 
+/// The layout version of the `WellFormedEncryptedTx`s produced by `tx_is_well_formed`. It is
+/// stored in their first byte, so that txs persisted by an enclave using another layout are
+/// rejected with `Error::UnsupportedTxFormat` rather than failing to decrypt. Version 0 is never
+/// used.
+pub const WELL_FORMED_TX_FORMAT_VERSION: u8 = 1;
+
+/// The size of each length prefix written by `WellFormedEncryptedTx::encode_batch`.
+const BATCH_LEN_PREFIX_LEN: usize = 4;
+
+impl WellFormedEncryptedTx {
+    /// The size of the encrypted tx, in bytes.
+    pub fn encrypted_len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// The layout version this tx was encrypted with, see `WELL_FORMED_TX_FORMAT_VERSION`, or 0
+    /// if it is empty.
+    pub fn format_version(&self) -> u8 {
+        self.0.first().copied().unwrap_or_default()
+    }
+
+    /// Pack a batch of encrypted txs into a single buffer, e.g. for a mempool snapshot. The
+    /// buffer holds the number of txs, then each tx prefixed with its length, all as
+    /// little-endian `u32`s.
+    pub fn encode_batch(txs: &[WellFormedEncryptedTx]) -> Vec<u8> {
+        let len = BATCH_LEN_PREFIX_LEN * (txs.len() + 1)
+            + txs.iter().map(Self::encrypted_len).sum::<usize>();
+        let mut buf = Vec::with_capacity(len);
+        buf.extend_from_slice(&(txs.len() as u32).to_le_bytes());
+        for tx in txs {
+            buf.extend_from_slice(&(tx.0.len() as u32).to_le_bytes());
+            buf.extend_from_slice(&tx.0);
+        }
+        buf
+    }
+
+    /// Unpack a batch of encrypted txs written by `encode_batch`.
+    ///
+    /// Fails with `Error::Serialization` if `buf` is truncated, or has bytes left over after the
+    /// last tx.
+    pub fn decode_batch(buf: &[u8]) -> Result<Vec<WellFormedEncryptedTx>> {
+        let (count, mut rest) = split_len_prefix(buf)?;
+        // Every tx takes up at least its prefix, which bounds the allocation below.
+        if count > rest.len() / BATCH_LEN_PREFIX_LEN {
+            return Err(Error::Serialization);
+        }
+        let mut txs = Vec::with_capacity(count);
+        for _ in 0..count {
+            let (len, after_prefix) = split_len_prefix(rest)?;
+            let bytes = after_prefix.get(..len).ok_or(Error::Serialization)?;
+            txs.push(WellFormedEncryptedTx(bytes.to_vec()));
+            rest = &after_prefix[len..];
+        }
+        if !rest.is_empty() {
+            return Err(Error::Serialization);
+        }
+        Ok(txs)
+    }
+}
+
+/// Split the little-endian `u32` length prefix off the start of `buf`.
+fn split_len_prefix(buf: &[u8]) -> Result<(usize, &[u8])> {
+    if buf.len() < BATCH_LEN_PREFIX_LEN {
+        return Err(Error::Serialization);
+    }
+    let (prefix, rest) = buf.split_at(BATCH_LEN_PREFIX_LEN);
+    let mut len_bytes = [0u8; BATCH_LEN_PREFIX_LEN];
+    len_bytes.copy_from_slice(prefix);
+    Ok((u32::from_le_bytes(len_bytes) as usize, rest))
+}
+
+impl AsRef<[u8]> for WellFormedEncryptedTx {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
 /// Tx data we wish to expose to untrusted from well-formed Txs
 #[derive(Clone, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 pub struct WellFormedTxContext {
@@ -48,6 +142,10 @@ pub struct WellFormedTxContext {
     /// Fee included in the tx.
     fee: u64,
 
+    /// Token id the fee is denominated in. `TxPrefix` does not name a fee token yet, so this is
+    /// always `DEFAULT_TOKEN_ID` for contexts built from a tx.
+    token_id: u64,
+
     /// Tombstone block.
     tombstone_block: u64,
 
@@ -56,6 +154,9 @@ pub struct WellFormedTxContext {
 
     /// Highest membership proofs indices.
     highest_indices: Vec<u64>,
+
+    /// Mempool ordering priority, see `WellFormedTxContext::priority`.
+    priority: u64,
 }
 
 impl WellFormedTxContext {
@@ -67,6 +168,16 @@ impl WellFormedTxContext {
         self.fee
     }
 
+    pub fn token_id(&self) -> u64 {
+        self.token_id
+    }
+
+    /// The fee together with the token it is denominated in, as `(token_id, fee)`. Until the
+    /// transaction format names a fee token, the token is always `DEFAULT_TOKEN_ID`.
+    pub fn fee_token(&self) -> (u64, u64) {
+        (self.token_id, self.fee)
+    }
+
     pub fn tombstone_block(&self) -> u64 {
         self.tombstone_block
     }
@@ -78,6 +189,48 @@ impl WellFormedTxContext {
     pub fn highest_indices(&self) -> &Vec<u64> {
         &self.highest_indices
     }
+
+    /// The highest membership proof indices, sorted and without duplicates.
+    pub fn unique_highest_indices(&self) -> Vec<u64> {
+        unique_indices(&self.highest_indices)
+    }
+
+    /// The key images this and `other` both spend, in the order they appear in this tx. If any
+    /// are shared, at most one of the two txs can make it into the ledger, so untrusted can use
+    /// this to build conflict graphs when packing blocks.
+    pub fn shared_key_images(&self, other: &WellFormedTxContext) -> Vec<KeyImage> {
+        self.key_images
+            .iter()
+            .filter(|key_image| other.key_images.contains(key_image))
+            .copied()
+            .collect()
+    }
+
+    /// The priority untrusted should use when ordering transactions for inclusion in a block,
+    /// higher is better. This is the fee paid per `PRIORITY_SIZE_UNIT` bytes of the tx's
+    /// Prost encoding, rounded down:
+    ///
+    /// `priority = fee * PRIORITY_SIZE_UNIT / encoded_len(tx)`
+    ///
+    /// It is computed when the context is created inside the enclave, so the ordering policy is
+    /// part of the attested code.
+    pub fn priority(&self) -> u64 {
+        self.priority
+    }
+}
+
+/// The number of encoded bytes a transaction's priority is normalized to.
+pub const PRIORITY_SIZE_UNIT: u64 = 1000;
+
+/// The token id of fees paid by the current transaction format, which has no way of naming any
+/// other token.
+pub const DEFAULT_TOKEN_ID: u64 = 0;
+
+/// The priority of `tx`, as reported by `WellFormedTxContext::priority`.
+pub fn tx_priority(tx: &Tx) -> u64 {
+    let encoded_len = core::cmp::max(tx.encoded_len() as u128, 1);
+    let priority = u128::from(tx.prefix.fee) * u128::from(PRIORITY_SIZE_UNIT) / encoded_len;
+    core::cmp::min(priority, u128::from(u64::MAX)) as u64
 }
 
 impl From<&Tx> for WellFormedTxContext {
@@ -85,13 +238,60 @@ impl From<&Tx> for WellFormedTxContext {
         Self {
             tx_hash: tx.tx_hash(),
             fee: tx.prefix.fee,
+            // The prefix has no token id to read, every fee is paid in the default token.
+            token_id: DEFAULT_TOKEN_ID,
             tombstone_block: tx.prefix.tombstone_block,
             key_images: tx.key_images(),
             highest_indices: tx.get_membership_proof_highest_indices(),
+            priority: tx_priority(tx),
         }
     }
 }
 
+/// A `WellFormedTxContext` ordered for a max-heap such as `BinaryHeap`: a higher priority is
+/// greater, and among equal priorities, a lower tx hash is greater. Popping from a heap of these
+/// yields transactions in the order `form_block` includes them.
+///
+/// `WellFormedTxContext`'s own `Ord` compares fields in declaration order, which suits map keys
+/// but not prioritization.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ByPriority(pub WellFormedTxContext);
+
+impl Ord for ByPriority {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0
+            .priority
+            .cmp(&other.0.priority)
+            .then_with(|| other.0.tx_hash.cmp(&self.0.tx_hash))
+            // Only contexts for the same tx remain, and must agree with `Eq`.
+            .then_with(|| self.0.cmp(&other.0))
+    }
+}
+
+impl PartialOrd for ByPriority {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for ByPriority {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Eq for ByPriority {}
+
+/// Yields `contexts` in the order `form_block` includes them, see `ByPriority`. The contexts are
+/// heapified in linear time and each one is only put in order once it is taken, so a caller
+/// filling a block can stop early instead of sorting the whole mempool.
+pub fn into_priority_iter(
+    contexts: Vec<WellFormedTxContext>,
+) -> impl Iterator<Item = WellFormedTxContext> {
+    let mut heap: BinaryHeap<ByPriority> = contexts.into_iter().map(ByPriority).collect();
+    core::iter::from_fn(move || heap.pop().map(|ByPriority(context)| context))
+}
+
 /// An intermediate struct for holding data required to perform the two-step is-well-formed test.
 /// This is returned by `txs_propose` and allows untrusted to gather data required for the
 /// in-enclave well-formedness test that takes place in `tx_is_well_formed`.
@@ -101,15 +301,703 @@ pub struct TxContext {
     pub tx_hash: TxHash,
     pub highest_indices: Vec<u64>,
     pub key_images: Vec<KeyImage>,
+    /// The tx's tombstone block, so untrusted can drop expired txs before checking well-formedness.
+    pub tombstone_block: u64,
+}
+
+impl TxContext {
+    /// Create a new TxContext.
+    pub fn new(
+        locally_encrypted_tx: LocallyEncryptedTx,
+        tx_hash: TxHash,
+        highest_indices: Vec<u64>,
+        key_images: Vec<KeyImage>,
+        tombstone_block: u64,
+    ) -> Self {
+        Self {
+            locally_encrypted_tx,
+            tx_hash,
+            highest_indices,
+            key_images,
+            tombstone_block,
+        }
+    }
+
+    /// The highest membership proof indices, sorted and without duplicates. Several inputs may
+    /// reference proofs with the same highest index, but each only needs to be fetched once.
+    pub fn unique_highest_indices(&self) -> Vec<u64> {
+        unique_indices(&self.highest_indices)
+    }
+
+    /// Returns true if this and `other` spend any of the same key images, i.e. at most one of
+    /// them can ever make it into the ledger. This lets untrusted reject obvious double-spends
+    /// without calling into the enclave.
+    pub fn conflicts_with(&self, other: &TxContext) -> bool {
+        self.key_images
+            .iter()
+            .any(|key_image| other.key_images.contains(key_image))
+    }
+
+    /// The key images, sorted by their bytes. Their order in `key_images` depends on how the tx
+    /// was constructed, so nodes comparing key images across txs, e.g. to deduplicate gossip,
+    /// should use this canonical order instead.
+    pub fn sorted_key_images(&self) -> Vec<KeyImage> {
+        let mut key_images = self.key_images.clone();
+        key_images.sort();
+        key_images
+    }
+
+    /// An upper bound on the length of `mcserial::serialize(self)`, computed without serializing,
+    /// so untrusted can size buffers up front. CBOR encodes each byte of the encrypted tx, the
+    /// hash and the key images in one or two bytes depending on its value; the bound assumes two,
+    /// so it is never more than twice the actual length.
+    pub fn serialized_size(&self) -> usize {
+        let field_names: usize = TX_CONTEXT_FIELD_NAMES
+            .iter()
+            .map(|name| cbor_head_len(name.len()) + name.len())
+            .sum();
+        let highest_indices: usize = self
+            .highest_indices
+            .iter()
+            .map(|index| cbor_uint_len(*index))
+            .sum();
+
+        cbor_head_len(TX_CONTEXT_FIELD_NAMES.len())
+            + field_names
+            + cbor_byte_array_bound(self.locally_encrypted_tx.0.len())
+            + cbor_byte_array_bound(self.tx_hash.0.len())
+            + cbor_head_len(self.highest_indices.len())
+            + highest_indices
+            + cbor_head_len(self.key_images.len())
+            + self.key_images.len() * cbor_byte_array_bound(KEY_IMAGE_LEN)
+            + cbor_uint_len(self.tombstone_block)
+    }
+}
+
+/// The names of the fields of `TxContext`, which serde serializes as the keys of a CBOR map.
+const TX_CONTEXT_FIELD_NAMES: [&str; 5] = [
+    "locally_encrypted_tx",
+    "tx_hash",
+    "highest_indices",
+    "key_images",
+    "tombstone_block",
+];
+
+/// The length of a key image, a compressed Ristretto point.
+const KEY_IMAGE_LEN: usize = 32;
+
+/// The encoded length of a CBOR unsigned integer, which is also the length of the head of a CBOR
+/// array, map or string with that many entries.
+fn cbor_uint_len(value: u64) -> usize {
+    match value {
+        0..=23 => 1,
+        24..=0xff => 2,
+        0x100..=0xffff => 3,
+        0x1_0000..=0xffff_ffff => 5,
+        _ => 9,
+    }
+}
+
+/// The encoded length of the head of a CBOR array, map or string with `len` entries.
+fn cbor_head_len(len: usize) -> usize {
+    cbor_uint_len(len as u64)
+}
+
+/// An upper bound on the encoded length of `len` bytes, which serde serializes as a CBOR array
+/// holding one unsigned integer per byte.
+fn cbor_byte_array_bound(len: usize) -> usize {
+    cbor_head_len(len) + 2 * len
+}
+
+/// Returns a sorted, deduplicated copy of `indices`.
+fn unique_indices(indices: &[u64]) -> Vec<u64> {
+    let mut unique = indices.to_vec();
+    unique.sort_unstable();
+    unique.dedup();
+    unique
+}
+
+/// A signed acknowledgement that an enclave received and re-encrypted a client's transaction,
+/// returned by `client_tx_propose_with_receipt`. The signature is made with the enclave's block
+/// signing key, so it can be checked against `get_signer`.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ProposeReceipt {
+    /// Hash of the received tx.
+    pub tx_hash: TxHash,
+
+    /// The block index the tx was received at.
+    pub block_index: u64,
+
+    /// Signature over `ProposeReceipt::message(tx_hash, block_index)`.
+    pub signature: Ed25519Signature,
+}
+
+/// Domain separator for the message signed by a `ProposeReceipt`.
+pub const PROPOSE_RECEIPT_DOMAIN_TAG: &[u8] = b"mc-consensus-propose-receipt";
+
+impl ProposeReceipt {
+    /// The message signed by the enclave when acknowledging `tx_hash` at `block_index`.
+    pub fn message(tx_hash: &TxHash, block_index: u64) -> Vec<u8> {
+        [
+            PROPOSE_RECEIPT_DOMAIN_TAG,
+            &tx_hash.0[..],
+            &block_index.to_le_bytes()[..],
+        ]
+        .concat()
+    }
+
+    /// Verify that this receipt was signed by the given block signer.
+    pub fn verify(&self, signer: &Ed25519Public) -> StdResult<(), Ed25519SignatureError> {
+        signer.verify(&Self::message(&self.tx_hash, self.block_index), &self.signature)
+    }
 }
 
 pub type SealedBlockSigningKey = Vec<u8>;
 
+/// The default maximum size, in bytes, of a client message accepted by `client_tx_propose`, until
+/// another is set with `set_max_proposal_bytes`. This comfortably fits a transaction with the
+/// maximum number of inputs and outputs.
+pub const DEFAULT_MAX_PROPOSAL_BYTES: usize = 1024 * 1024;
+
+/// The maximum age of an IAS report accepted by `verify_ias_report_at`. This is fixed when the
+/// enclave is built, since letting untrusted code choose it would defeat the check.
+pub const IAS_REPORT_MAX_AGE_SECS: u64 = 24 * 60 * 60;
+
+/// The number of most recently formed blocks `included_in_block` and `recent_key_images`
+/// remember the transactions of.
+pub const INCLUSION_HISTORY_BLOCKS: usize = 64;
+
+/// The number of block signing keys replaced by `rotate_signing_key` that `is_trusted_signer`
+/// still accepts.
+pub const RETIRED_SIGNER_HISTORY: usize = 4;
+
+/// The number of nonces of IAS reports verified by `verify_ias_report` that an enclave
+/// remembers, and rejects any further report for.
+pub const CONSUMED_IAS_NONCE_HISTORY: usize = 16;
+
+/// The cached IAS report together with when it was issued, returned by `get_ias_report_info`.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct IasReportInfo {
+    /// The cached verification report.
+    pub report: VerificationReport,
+
+    /// When IAS issued the report, in seconds since the unix epoch.
+    pub issued_at: u64,
+
+    /// When the enclave stops considering the report fresh, i.e. `issued_at` plus
+    /// `IAS_REPORT_MAX_AGE_SECS`, in seconds since the unix epoch.
+    pub expires_at: u64,
+}
+
+impl IasReportInfo {
+    /// The number of seconds the report remains valid for at `now_unix_secs`, zero once it has
+    /// expired.
+    pub fn remaining_validity(&self, now_unix_secs: u64) -> u64 {
+        self.expires_at.saturating_sub(now_unix_secs)
+    }
+}
+
+impl TryFrom<VerificationReport> for IasReportInfo {
+    type Error = Error;
+
+    fn try_from(report: VerificationReport) -> Result<Self> {
+        let issued_at = ias_report_issued_at(&report)?;
+        Ok(Self {
+            report,
+            issued_at,
+            expires_at: issued_at.saturating_add(IAS_REPORT_MAX_AGE_SECS),
+        })
+    }
+}
+
+/// Everything a client needs to decide whether to trust an enclave, fetched in one call with
+/// `attestation_bundle`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct AttestationBundle {
+    /// The body of a report for the enclave, as returned by `report_body`. It carries the
+    /// enclave's MRENCLAVE, and its report data starts with `identity`.
+    pub report_body: ReportBody,
+
+    /// The key exchange public key clients run the AKE against, as returned by `get_identity`.
+    pub identity: X25519Public,
+
+    /// The cached IAS report, as returned by `get_ias_report`, or `None` if no report has been
+    /// verified yet. A bundle without a report can not establish trust on its own.
+    pub ias_report: Option<VerificationReport>,
+}
+
+impl AttestationBundle {
+    /// The MRENCLAVE of the enclave the bundle describes.
+    pub fn mr_enclave(&self) -> Measurement {
+        Measurement::from(self.report_body.mr_enclave())
+    }
+
+    /// Returns true if `identity` is the key exchange public key attested in the report body's
+    /// report data, i.e. the bundle's parts belong together.
+    pub fn is_identity_bound(&self) -> bool {
+        let report_data = self.report_body.report_data();
+        let report_data_bytes: &[u8] = report_data.as_ref();
+        report_data_bytes.starts_with(self.identity.as_ref())
+    }
+}
+
+/// When IAS issued `report`, in seconds since the unix epoch, as returned by `ias_report_time`.
+/// Only the report body is parsed, the report's signature is not checked.
+pub fn ias_report_issued_at(report: &VerificationReport) -> Result<u64> {
+    Ok(VerificationReportData::try_from(report)
+        .and_then(|data| data.parse_timestamp())
+        .map_err(AttestEnclaveError::from)?)
+}
+
+/// The number of blocks left at `now_unix_secs` before a report issued at `issued_at` is due for
+/// a refresh every `blocks_per_refresh` blocks, when a block is formed every `secs_per_block`
+/// seconds, zero once the refresh is due, as returned by `ias_report_blocks_remaining`.
+///
+/// Only whole blocks count towards the report's age. A `secs_per_block` of zero makes every
+/// report due for a refresh.
+pub fn ias_report_blocks_remaining_at(
+    issued_at: u64,
+    now_unix_secs: u64,
+    secs_per_block: u64,
+    blocks_per_refresh: u64,
+) -> u64 {
+    let age_blocks = now_unix_secs
+        .saturating_sub(issued_at)
+        .checked_div(secs_per_block)
+        .unwrap_or(u64::MAX);
+    blocks_per_refresh.saturating_sub(age_blocks)
+}
+
+/// The number of AKE sessions currently held by an enclave, returned by `session_stats`.
+///
+/// Only counts are reported, which reveal nothing about the session keys themselves.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct SessionStats {
+    /// Established client sessions.
+    pub client_sessions: usize,
+
+    /// Established peer sessions, both inbound and outbound.
+    pub peer_sessions: usize,
+}
+
+/// A snapshot of the enclave's session, attestation and signing state, returned by
+/// `enclave_status`. Every field is read at the same point in time.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct EnclaveStatus {
+    /// The number of established sessions, as returned by `session_stats`.
+    pub sessions: SessionStats,
+
+    /// The cached IAS report, as returned by `get_ias_report_info`, or `None` if no report has
+    /// been verified yet.
+    pub ias_report_info: Option<IasReportInfo>,
+
+    /// The index of the last block signed, as returned by `current_signing_height`.
+    pub signing_height: u64,
+}
+
+/// An enclave operation whose time spent inside the enclave is measured by profiling builds.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum TimedOperation {
+    /// `tx_is_well_formed`, or one of its variants.
+    TxIsWellFormed,
+
+    /// `form_block`, or one of its variants.
+    FormBlock,
+}
+
+/// How long the most recent timed operation spent inside the enclave, returned by
+/// `last_call_timings`.
+///
+/// The counts are read from the CPU's timestamp counter at the start and end of the operation,
+/// so they exclude the cost of entering and leaving the enclave.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct CallTimings {
+    /// The operation that was timed.
+    pub operation: TimedOperation,
+
+    /// The timestamp counter when the operation started.
+    pub start_cycles: u64,
+
+    /// The timestamp counter when the operation finished, successfully or not.
+    pub end_cycles: u64,
+}
+
+impl CallTimings {
+    /// The number of cycles the operation took.
+    pub fn cycles(&self) -> u64 {
+        self.end_cycles.saturating_sub(self.start_cycles)
+    }
+}
+
+/// A client or peer session, as reported by `close_sessions`.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum SessionRef {
+    /// A client session.
+    Client(ClientSession),
+    /// A peer session, inbound or outbound.
+    Peer(PeerSession),
+}
+
+/// The transactions of one message from `txs_for_peer_chunked`, returned by
+/// `peer_tx_propose_chunk` along with the message's position among the chunks of its batch.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PeerTxChunk {
+    /// The index of this chunk, counting from zero.
+    pub chunk_index: u32,
+
+    /// The total number of chunks the batch was split into.
+    pub chunk_total: u32,
+
+    /// The result for each transaction in the chunk, as returned by `peer_tx_propose`.
+    pub tx_contexts: Vec<Result<TxContext>>,
+}
+
+/// Reassembles the chunks of one `txs_for_peer_chunked` batch, received in any order.
+#[derive(Clone, Debug, Default)]
+pub struct PeerTxChunks {
+    /// The number of chunks in the batch, taken from the first chunk inserted.
+    chunk_total: Option<u32>,
+
+    /// The transactions of each chunk inserted so far, by chunk index.
+    chunks: BTreeMap<u32, Vec<Result<TxContext>>>,
+}
+
+impl PeerTxChunks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a received chunk to the batch. Fails with `Error::ChunkMismatch` if the chunk's index
+    /// is out of range, or its total differs from the chunks inserted before it, and with
+    /// `Error::DuplicateChunk` if a chunk with the same index was inserted before. A rejected
+    /// chunk leaves the batch unchanged.
+    pub fn insert(&mut self, chunk: PeerTxChunk) -> Result<()> {
+        let chunk_total = self.chunk_total.unwrap_or(chunk.chunk_total);
+        if chunk.chunk_total != chunk_total || chunk.chunk_index >= chunk_total {
+            return Err(Error::ChunkMismatch {
+                index: chunk.chunk_index,
+                total: chunk.chunk_total,
+            });
+        }
+        if self.chunks.contains_key(&chunk.chunk_index) {
+            return Err(Error::DuplicateChunk(chunk.chunk_index));
+        }
+        self.chunk_total = Some(chunk_total);
+        self.chunks.insert(chunk.chunk_index, chunk.tx_contexts);
+        Ok(())
+    }
+
+    /// The indices of the chunks not inserted yet, in ascending order. Nothing is known to be
+    /// missing before the first chunk is inserted.
+    pub fn missing(&self) -> Vec<u32> {
+        (0..self.chunk_total.unwrap_or(0))
+            .filter(|chunk_index| !self.chunks.contains_key(chunk_index))
+            .collect()
+    }
+
+    /// Whether every chunk of the batch was inserted.
+    pub fn is_complete(&self) -> bool {
+        self.chunk_total.is_some() && self.missing().is_empty()
+    }
+
+    /// The results for every transaction in the batch, in the order they were sent. Fails with
+    /// `Error::MissingChunk` naming the first chunk not inserted unless the batch is complete.
+    pub fn into_tx_contexts(self) -> Result<Vec<Result<TxContext>>> {
+        if let Some(chunk_index) = self.missing().first() {
+            return Err(Error::MissingChunk(*chunk_index));
+        }
+        if self.chunk_total.is_none() {
+            return Err(Error::MissingChunk(0));
+        }
+        Ok(self.chunks.into_iter().flat_map(|(_, txs)| txs).collect())
+    }
+}
+
+/// Caps on the contents of blocks formed by the enclave, set with `set_block_limits`.
+///
+/// Every node should use the same limits, so that they form identical blocks from the same
+/// candidate set.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct BlockLimits {
+    /// The most transactions a block may hold, not counting the aggregate fee output.
+    pub max_txs: u32,
+
+    /// The most bytes the Prost-encoded transactions in a block may add up to, not counting the
+    /// aggregate fee output.
+    pub max_bytes: u32,
+
+    /// The largest aggregate fee a block may mint. This is a safety bound on fee accounting: a
+    /// block whose transactions pay more in total is not formed at all.
+    pub max_fee: u64,
+}
+
+impl Default for BlockLimits {
+    fn default() -> Self {
+        Self {
+            max_txs: MAX_TRANSACTIONS_PER_BLOCK as u32,
+            max_bytes: u32::max_value(),
+            max_fee: u64::max_value(),
+        }
+    }
+}
+
+/// The tombstone blocks `tx_is_well_formed` admits, as offsets from the current block, set with
+/// `set_tombstone_window`.
+///
+/// A transaction is admitted if its tombstone block lies in
+/// `[block_index + min_offset, block_index + max_offset]`. Raising `min_offset` turns away
+/// transactions that would likely expire before being included, and lowering `max_offset` turns
+/// away long-lived spam.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct TombstoneWindow {
+    /// The fewest blocks after the current one a tombstone block may lie.
+    pub min_offset: u64,
+
+    /// The most blocks after the current one a tombstone block may lie.
+    pub max_offset: u64,
+}
+
+/// Whether a candidate transaction passed to `form_block_with_report` was included in the block.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum InclusionStatus {
+    /// The transaction is in the block.
+    Included,
+
+    /// The transaction was left out of the block, and may be proposed again in a later one.
+    Excluded(ExclusionReason),
+}
+
+/// Why a candidate transaction was left out of a block.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum ExclusionReason {
+    /// The block already holds `BlockLimits::max_txs` transactions, and this transaction was
+    /// ordered after all of them.
+    BlockFull,
+
+    /// Including this transaction, or one ordered before it, would have taken the block over
+    /// `BlockLimits::max_bytes`.
+    BlockTooLarge,
+}
+
+bitflags! {
+    /// Optional `ConsensusEnclave` capabilities, which untrusted code can check before relying on
+    /// newer methods.
+    #[derive(Deserialize, Serialize)]
+    pub struct EnclaveCapabilities: u64 {
+        /// `txs_are_well_formed` is available.
+        const BATCH_WELL_FORMED = 1;
+        /// `get_minimum_fee` is available.
+        const MINIMUM_FEE = 1 << 1;
+        /// `tx_is_well_formed` reports failures as `Error::WellFormedCheck`.
+        const STRUCTURED_WELL_FORMED_ERRORS = 1 << 2;
+        /// `peer_tx_propose` returns a result per relayed tx.
+        const PEER_TX_PROPOSE_PARTIAL_FAILURE = 1 << 3;
+        /// `form_block_versioned` is available.
+        const VERSIONED_BLOCK_FORMATION = 1 << 4;
+        /// `WellFormedTxContext::priority` is computed by the enclave.
+        const TX_PRIORITY = 1 << 5;
+        /// `export_signing_key` and `reseal_signing_key` are available.
+        const SIGNING_KEY_MIGRATION = 1 << 6;
+        /// `get_signer_fingerprint` is available.
+        const SIGNER_FINGERPRINT = 1 << 7;
+        /// `client_tx_propose_with_receipt` is available.
+        const PROPOSE_RECEIPT = 1 << 8;
+        /// `txs_for_peer_chunked` is available.
+        const CHUNKED_TXS_FOR_PEER = 1 << 9;
+        /// `peer_close_all` is available.
+        const PEER_CLOSE_ALL = 1 << 10;
+        /// `session_stats` is available.
+        const SESSION_STATS = 1 << 11;
+        /// `verify_ias_report_at` is available.
+        const IAS_REPORT_FRESHNESS = 1 << 12;
+        /// `get_ias_report_info` is available.
+        const IAS_REPORT_INFO = 1 << 13;
+        /// `verify_own_block_signature` is available.
+        const VERIFY_OWN_BLOCK_SIGNATURE = 1 << 14;
+        /// `cancel_ereport` is available.
+        const CANCEL_EREPORT = 1 << 15;
+        /// `client_sessions` is available.
+        const CLIENT_SESSIONS = 1 << 16;
+        /// `form_block_with_report` is available.
+        const FORM_BLOCK_WITH_REPORT = 1 << 17;
+        /// `set_block_limits` is available.
+        const BLOCK_LIMITS = 1 << 18;
+        /// `client_accept_with_budget` is available.
+        const CLIENT_ACCEPT_BUDGET = 1 << 19;
+        /// `report_body` is available.
+        const REPORT_BODY = 1 << 20;
+        /// `tx_ring_signature_is_valid` is available.
+        const RING_SIGNATURE_PREVALIDATION = 1 << 21;
+        /// `seal_blob` and `unseal_blob` are available.
+        const SEALED_BLOBS = 1 << 22;
+        /// `current_signing_height` is available.
+        const SIGNING_HEIGHT = 1 << 23;
+        /// `close_sessions` is available.
+        const CLOSE_SESSIONS = 1 << 24;
+        /// `get_minimum_fees` is available.
+        const MINIMUM_FEES = 1 << 25;
+        /// `set_peer_identity_policy` is available.
+        const PEER_IDENTITY_POLICY = 1 << 26;
+        /// `verify_tx_hash` is available.
+        const TX_HASH_VERIFICATION = 1 << 27;
+        /// `estimate_form_block_cost` is available.
+        const FORM_BLOCK_COST_ESTIMATE = 1 << 28;
+        /// `peer_init_pinned` is available.
+        const PEER_INIT_PINNED = 1 << 29;
+        /// `block_version_range` is available.
+        const BLOCK_VERSION_RANGE = 1 << 30;
+        /// `enclave_status` is available.
+        const ENCLAVE_STATUS = 1 << 31;
+        /// `rotate_signing_key` is available.
+        const SIGNING_KEY_ROTATION = 1 << 32;
+        /// `client_discard_messages` is available.
+        const BATCH_CLIENT_DISCARD = 1 << 33;
+        /// `validate_ias_report` is available.
+        const IAS_REPORT_VALIDATION = 1 << 34;
+        /// `included_in_block` is available.
+        const INCLUSION_LOOKUP = 1 << 35;
+        /// `fee_policy_digest` is available.
+        const FEE_POLICY_DIGEST = 1 << 36;
+        /// `redact_single` is available.
+        const REDACT_SINGLE = 1 << 37;
+        /// `ias_report_blocks_remaining` is available.
+        const IAS_REPORT_BLOCKS_REMAINING = 1 << 38;
+        /// `self_peer_id` and `self_client_id` are available.
+        const SELF_RESPONDER_IDS = 1 << 39;
+        /// `block_total_fees` is available.
+        const BLOCK_TOTAL_FEES = 1 << 40;
+        /// `integrity_check` is available.
+        const INTEGRITY_CHECK = 1 << 41;
+        /// `set_tombstone_window` is available.
+        const TOMBSTONE_WINDOW = 1 << 42;
+        /// `tx_is_well_formed_unspent` is available.
+        const SPENT_KEY_IMAGE_CHECK = 1 << 43;
+        /// `recent_key_images` is available.
+        const RECENT_KEY_IMAGES = 1 << 44;
+        /// `ias_report_time` is available.
+        const IAS_REPORT_TIME = 1 << 45;
+        /// `form_block_excluding` is available.
+        const FORM_BLOCK_EXCLUDE = 1 << 46;
+        /// `peer_tx_propose_dedup` is available.
+        const PEER_TX_DEDUP = 1 << 47;
+        /// `is_trusted_signer` is available.
+        const TRUSTED_SIGNER_CHECK = 1 << 48;
+        /// `verify_own_block_signatures` is available.
+        const BATCH_OWN_BLOCK_SIGNATURES = 1 << 49;
+        /// `tx_fee_preview` is available.
+        const TX_FEE_PREVIEW = 1 << 50;
+        /// `form_empty_block` is available.
+        const FORM_EMPTY_BLOCK = 1 << 51;
+        /// `last_call_timings` is available. Only profiling builds support this.
+        const CALL_TIMINGS = 1 << 52;
+        /// `client_connect_info` is available.
+        const CLIENT_CONNECT_INFO = 1 << 53;
+        /// `context_of` is available.
+        const CONTEXT_OF = 1 << 54;
+        /// `attestation_bundle` is available.
+        const ATTESTATION_BUNDLE = 1 << 55;
+        /// `set_max_proposal_bytes` is available.
+        const MAX_PROPOSAL_BYTES = 1 << 56;
+        /// `peer_tx_propose_chunk` is available.
+        const PEER_TX_CHUNK_POSITION = 1 << 57;
+    }
+}
+
+/// The `ConsensusEnclave` API version implemented by an enclave build, and the optional
+/// capabilities it supports.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct EnclaveVersion {
+    /// Incremented on incompatible API changes.
+    pub major: u16,
+
+    /// Incremented when methods or capabilities are added.
+    pub minor: u16,
+
+    /// Incremented on fixes which do not change the API.
+    pub patch: u16,
+
+    /// Optional capabilities supported by the enclave.
+    pub capabilities: EnclaveCapabilities,
+}
+
+impl EnclaveVersion {
+    /// Returns true if the enclave supports all of the given capabilities.
+    pub fn supports(&self, capabilities: EnclaveCapabilities) -> bool {
+        self.capabilities.contains(capabilities)
+    }
+}
+
+/// Domain separator for the message a signing key migration blob is sealed under.
+pub const SIGNING_KEY_MIGRATION_DOMAIN_TAG: &[u8] = b"mc-consensus-signing-key-migration";
+
+/// The additional MAC text of a migration blob made by `export_signing_key`, binding it to the
+/// enclave it is meant for and to the sealed key it carries.
+pub fn signing_key_migration_message(
+    target_mr_enclave: &MrEnclave,
+    old_sealed: &SealedBlockSigningKey,
+) -> Vec<u8> {
+    [
+        SIGNING_KEY_MIGRATION_DOMAIN_TAG,
+        target_mr_enclave.as_ref(),
+        &old_sealed[..],
+    ]
+    .concat()
+}
+
+/// Domain separator for block signing key fingerprints.
+pub const SIGNER_FINGERPRINT_DOMAIN_TAG: &[u8] = b"mc-consensus-signer-fingerprint";
+
+/// A domain-separated Blake2b-256 hash of a block signing public key, as returned by
+/// `get_signer_fingerprint`.
+pub fn signer_fingerprint(signer: &Ed25519Public) -> [u8; 32] {
+    let mut hasher = Blake2b256::new();
+    hasher.input(SIGNER_FINGERPRINT_DOMAIN_TAG);
+    hasher.input(signer);
+
+    let mut fingerprint = [0u8; 32];
+    fingerprint.copy_from_slice(hasher.result().as_slice());
+    fingerprint
+}
+
+/// Domain separator for fee policy digests.
+pub const FEE_POLICY_DOMAIN_TAG: &[u8] = b"mc-consensus-fee-policy";
+
+/// A domain-separated Blake2b-256 hash of a fee schedule of `(token_id, minimum_fee)` pairs, as
+/// returned by `fee_policy_digest`. The pairs are hashed in token order, so the order they are
+/// listed in does not matter.
+pub fn digest_fee_policy(minimum_fees: &[(u64, u64)]) -> [u8; 32] {
+    let mut schedule = minimum_fees.to_vec();
+    schedule.sort_unstable();
+
+    let mut hasher = Blake2b256::new();
+    hasher.input(FEE_POLICY_DOMAIN_TAG);
+    hasher.input((schedule.len() as u64).to_le_bytes());
+    for (token_id, minimum_fee) in schedule {
+        hasher.input(token_id.to_le_bytes());
+        hasher.input(minimum_fee.to_le_bytes());
+    }
+
+    let mut digest = [0u8; 32];
+    digest.copy_from_slice(hasher.result().as_slice());
+    digest
+}
+
 /// The API for interacting with a consensus node's enclave.
 pub trait ConsensusEnclave {
     // UTILITY METHODS
 
     /// Perform one-time initialization upon enclave startup.
+    ///
+    /// Both responder ids must be of the `host:port` form, or the call fails with
+    /// `Error::InvalidResponderId` and leaves the enclave uninitialized.
+    ///
+    /// Repeating the first call with identical arguments succeeds without changing any state, and
+    /// returns the same sealed key. A later call with any other arguments fails with
+    /// `Error::AlreadyInitialized`.
+    ///
+    /// Until this has succeeded, every method that uses the block signing key (`get_signer`,
+    /// `get_signer_fingerprint`, `form_block` and its variants, `txs_for_peer`, and the receipts
+    /// of `client_tx_propose_with_receipt`) fails with `Error::NotInitialized`.
     fn enclave_init(
         &self,
         self_peer_id: &ResponderId,
@@ -117,15 +1005,115 @@ pub trait ConsensusEnclave {
         sealed_key: &Option<SealedBlockSigningKey>,
     ) -> Result<SealedBlockSigningKey>;
 
+    /// Export a block signing key sealed by this enclave to the enclave described by `target`,
+    /// typically the next version of the node software, which can then install it with
+    /// `reseal_signing_key`.
+    ///
+    /// Keys are sealed to MRENCLAVE identity, so no other enclave can read `old_sealed`. The
+    /// returned migration blob holds the key sealed to the enclave signer instead, under
+    /// `signing_key_migration_message(&target.report_body.mr_enclave(), old_sealed)`. Only an
+    /// enclave from the same signer, with the same or a greater security version, can unseal it,
+    /// and only the one with the target's MRENCLAVE accepts it.
+    ///
+    /// The key is only exported if `target.ias_report` is signed by IAS and attests the quote
+    /// `target.report_body` came from, so no report body can be substituted for the target's.
+    fn export_signing_key(
+        &self,
+        old_sealed: &SealedBlockSigningKey,
+        target: &AttestationBundle,
+    ) -> Result<Vec<u8>>;
+
+    /// Migrate a block signing key sealed by a previous version of the enclave, so that node
+    /// software can be upgraded without rotating the block signing identity.
+    ///
+    /// `migration_blob` is the result of calling `export_signing_key(old_sealed, target)` on the
+    /// previous enclave, with the `attestation_bundle` of this one as `target`. The key it holds
+    /// replaces the block signing key as `rotate_signing_key` does, and is returned re-sealed
+    /// under the current enclave identity. A blob exported for any other enclave or sealed key
+    /// is rejected with `Error::SealedPurposeMismatch`.
+    fn reseal_signing_key(
+        &self,
+        old_sealed: &SealedBlockSigningKey,
+        migration_blob: &[u8],
+    ) -> Result<SealedBlockSigningKey>;
+
+    /// Replace the block signing key with a freshly generated one, returning the new key sealed
+    /// under the current enclave identity along with its public key.
+    ///
+    /// Sessions are left untouched, so peers and clients stay connected. Blocks formed from here
+    /// on are signed with the new key only; the caller is responsible for persisting the new
+    /// sealed key and announcing the new public key.
+    ///
+    /// Fails with `Error::NotInitialized` before `enclave_init`, which would replace the key.
+    fn rotate_signing_key(&self) -> Result<(SealedBlockSigningKey, Ed25519Public)>;
+
+    /// Seal a small piece of operator state, such as a counter, so that it can be persisted
+    /// across restarts. Blobs are sealed under the same key policy as the block signing key, but
+    /// are tagged so the two can not be swapped for one another.
+    fn seal_blob(&self, plaintext: &[u8]) -> Result<Vec<u8>>;
+
+    /// Unseal a blob produced by `seal_blob`. Anything else, including a sealed block signing
+    /// key, is rejected with `Error::SealedPurposeMismatch`.
+    fn unseal_blob(&self, sealed: &[u8]) -> Result<Vec<u8>>;
+
+    /// Retrieve the API version and optional capabilities of the enclave, so that untrusted
+    /// code can gate calls to newer methods during rolling upgrades.
+    fn enclave_version(&self) -> Result<EnclaveVersion>;
+
     /// Retrieve the public identity of the enclave.
     fn get_identity(&self) -> Result<X25519Public>;
 
     /// Retreive the block signing public key from the enclave.
     fn get_signer(&self) -> Result<Ed25519Public>;
 
+    /// Retrieve a fingerprint of the block signing public key, see `signer_fingerprint`. This is
+    /// a cheap way for monitoring tooling to check that nodes share the intended signing key.
+    fn get_signer_fingerprint(&self) -> Result<[u8; 32]>;
+
+    /// Retrieve the `ResponderId` the enclave identifies itself by to peers, as passed to
+    /// `enclave_init`. Fails with `Error::NotInitialized` before then.
+    fn self_peer_id(&self) -> Result<ResponderId>;
+
+    /// Retrieve the `ResponderId` the enclave identifies itself by to clients, as passed to
+    /// `enclave_init`. Fails with `Error::NotInitialized` before then.
+    fn self_client_id(&self) -> Result<ResponderId>;
+
+    /// Retrieve what a client needs to start an AKE with this enclave: the `ResponderId` it
+    /// identifies itself by to clients, as returned by `self_client_id`, together with its key
+    /// exchange public key, as returned by `get_identity`. Fails with `Error::NotInitialized`
+    /// before `enclave_init`.
+    fn client_connect_info(&self) -> Result<(ResponderId, X25519Public)>;
+
+    /// Retrieve the number of client and peer sessions currently held by the enclave. Pending
+    /// (not yet established) handshakes are not counted.
+    fn session_stats(&self) -> Result<SessionStats>;
+
+    /// Retrieve the values of `session_stats`, `get_ias_report_info` and `current_signing_height`
+    /// in one call, read at the same point in time, so monitoring never sees them out of step.
+    fn enclave_status(&self) -> Result<EnclaveStatus>;
+
+    /// Retrieve how long the most recent `tx_is_well_formed` or `form_block` call spent inside
+    /// the enclave, for performance tuning.
+    ///
+    /// Only enclaves built with the `profiling` feature record timings, and they are not meant
+    /// for production: reading the timestamp counter is not permitted in SGX1 hardware enclaves.
+    /// Fails with `Error::ProfilingUnavailable` in other builds, or before any timed call.
+    fn last_call_timings(&self) -> Result<CallTimings>;
+
+    // The attestation methods below implement the EPID flow, where the quote is verified by IAS
+    // and the enclave checks IAS's signed report. DCAP quotes are instead checked against PCK
+    // certificates, TCB info and QE identity collateral, which the `attest` crates cannot yet
+    // verify inside the enclave, so there is no DCAP variant of these methods.
+
     /// Retrieve a new report for this enclave, targetted for the given
     /// quoting enclave. Untrusted code should call this on startup as
     /// part of the initialization process.
+    ///
+    /// Pending reports do not expire after a fixed time, since the enclave
+    /// has no trusted clock. A report stays usable by `verify_quote()` until
+    /// a quote for it is verified, or until enough newer calls to
+    /// `new_ereport()` evict it from the enclave's bounded cache of pending
+    /// reports (the 64 most recent are kept).
     fn new_ereport(&self, qe_info: TargetInfo) -> Result<(Report, QuoteNonce)>;
 
     /// Checks the quote and it's generating enclave for validity.
@@ -139,6 +1127,13 @@ pub trait ConsensusEnclave {
     /// the results, which will be used to verify
     fn verify_quote(&self, quote: Quote, qe_report: Report) -> Result<IasNonce>;
 
+    /// Abandon any in-flight attestation, discarding every report created by `new_ereport()` and
+    /// every quote accepted by `verify_quote()` which has not yet been matched by
+    /// `verify_ias_report()`. Untrusted code should call this when a step of the attestation
+    /// process fails, before starting again with `new_ereport()`. A previously verified IAS
+    /// report remains cached.
+    fn cancel_ereport(&self) -> Result<()>;
+
     /// Cache the verification report for this enclave.
     ///
     /// Untrusted code should transmit the quote previously checked by
@@ -151,54 +1146,234 @@ pub trait ConsensusEnclave {
     /// After that check has been performed, the enclave will use the
     /// verification report for all requests until another verfication report
     /// has been successfully loaded in it's place.
+    ///
+    /// A report answering the nonce of one of the last `CONSUMED_IAS_NONCE_HISTORY` reports
+    /// verified is a replay, and is rejected with `Error::NonceAlreadyConsumed`. A report whose
+    /// nonce can not be read is rejected too, since it could not be tracked.
     fn verify_ias_report(&self, ias_report: VerificationReport) -> Result<()>;
 
+    /// Same as `verify_ias_report`, but also rejects reports issued more than
+    /// `IAS_REPORT_MAX_AGE_SECS` before `now_unix_secs`.
+    ///
+    /// The enclave has no trusted clock, so this protects against untrusted code accidentally
+    /// loading an old report, not against a host lying about the current time.
+    fn verify_ias_report_at(
+        &self,
+        ias_report: VerificationReport,
+        now_unix_secs: u64,
+    ) -> Result<()>;
+
+    /// Check that an IAS report was signed by a trusted IAS certificate and that its contents
+    /// are acceptable, without caching it. The cached report, and any quote awaiting its IAS
+    /// report, are left untouched, so this can be used to vet a peer's report.
+    fn validate_ias_report(&self, ias_report: &VerificationReport) -> Result<()>;
+
     /// Retrieve a copy of the cached verification report.
     fn get_ias_report(&self) -> Result<VerificationReport>;
 
+    /// Retrieve a copy of the cached verification report, along with when it was issued and when
+    /// the enclave will stop considering it fresh (see `verify_ias_report_at`).
+    fn get_ias_report_info(&self) -> Result<IasReportInfo>;
+
+    /// Retrieve when IAS issued `report`, in seconds since the unix epoch, as the enclave reads
+    /// it. Untrusted can compare this against a time source it trusts more than the host clock,
+    /// such as recent block timestamps, before relying on `verify_ias_report_at`. Only the report
+    /// body is parsed, the report's signature is not checked.
+    fn ias_report_time(&self, report: &VerificationReport) -> Result<u64>;
+
+    /// Retrieve the number of blocks left at `now_unix_secs` before the cached report is due for
+    /// a refresh, given that it is refreshed every `blocks_per_refresh` blocks and a block is
+    /// formed every `secs_per_block` seconds, or zero once the refresh is due. The report's age
+    /// is counted from when IAS issued it, see `ias_report_time` and
+    /// `ias_report_blocks_remaining_at`.
+    ///
+    /// Fails with `NoReportAvailable` if no report is cached.
+    fn ias_report_blocks_remaining(
+        &self,
+        now_unix_secs: u64,
+        secs_per_block: u64,
+        blocks_per_refresh: u64,
+    ) -> Result<u64>;
+
+    /// Retrieve the body of a report for this enclave, as the enclave presents it in attestation.
+    /// Its MRENCLAVE and MRSIGNER identify the enclave, and its report data holds the enclave's
+    /// public key exchange key and block signing key, so clients can pin them out-of-band without
+    /// a full attestation round trip. Only public measurements and public keys are exposed.
+    fn report_body(&self) -> Result<ReportBody>;
+
+    /// Retrieve the values of `report_body`, `get_identity` and `get_ias_report` in one call, so
+    /// that client SDKs can bootstrap their trust in the enclave with a single fetch. Having no
+    /// cached IAS report is not an error here, see `AttestationBundle::ias_report`.
+    fn attestation_bundle(&self) -> Result<AttestationBundle>;
+
     // CLIENT-FACING METHODS
+    //
+    // Clients trust that anything they receive over an attested session was produced inside the
+    // enclave, so no method here encrypts plaintext chosen by untrusted code for a client session.
 
     /// Accept an inbound authentication request
     fn client_accept(&self, req: ClientAuthRequest) -> Result<(ClientAuthResponse, ClientSession)>;
 
+    /// Same as `client_accept`, but fails with `SessionLimitReached` instead of establishing a
+    /// session once `max_sessions` client sessions are already established. The limit is checked
+    /// again when the session is stored, so concurrent calls cannot exceed it.
+    fn client_accept_with_budget(
+        &self,
+        req: ClientAuthRequest,
+        max_sessions: usize,
+    ) -> Result<(ClientAuthResponse, ClientSession)>;
+
     /// Destroy a peer association
     fn client_close(&self, channel_id: ClientSession) -> Result<()>;
 
+    /// Retrieve the IDs of all established client sessions, ordered from most to least recently
+    /// used, so untrusted code can choose which to close with `client_close()`. Only the opaque
+    /// session IDs are returned, never any key material.
+    fn client_sessions(&self) -> Result<Vec<ClientSession>>;
+
     /// Decrypts a message from a client and then immediately discard it. This is useful when we
     /// want to skip processing an incoming message, but still properly maintain our AKE state in
     /// sync with the client.
     fn client_discard_message(&self, msg: EnclaveMessage<ClientSession>) -> Result<()>;
 
+    /// Discards a batch of client messages in one call, as `client_discard_message` would one by
+    /// one. The results are returned in the order of `msgs`, so a failure to discard any one
+    /// message does not stop the rest from being discarded.
+    fn client_discard_messages(
+        &self,
+        msgs: Vec<EnclaveMessage<ClientSession>>,
+    ) -> Result<Vec<Result<()>>>;
+
     // NODE-FACING METHODS
 
     /// Start a new outbound connection.
     fn peer_init(&self, peer_id: &ResponderId) -> Result<PeerAuthRequest>;
 
-    /// Accept an inbound authentication request
+    /// Same as `peer_init`, but pins the measurement the peer must present to `expected` rather
+    /// than this enclave's own MRENCLAVE, so `peer_connect` fails unless the peer's response is
+    /// attested with it. Fails right away with `PeerIdentityNotAllowed` if `expected` is not
+    /// allowed by `set_peer_identity_policy()`.
+    fn peer_init_pinned(
+        &self,
+        peer_id: &ResponderId,
+        expected: Measurement,
+    ) -> Result<PeerAuthRequest>;
+
+    /// Accept an inbound authentication request.
+    ///
+    /// Each request is accepted at most once: a request identical to one of the recently accepted
+    /// ones is rejected with `ReplayedAuthRequest`. A request which failed may be retried.
     fn peer_accept(&self, req: PeerAuthRequest) -> Result<(PeerAuthResponse, PeerSession)>;
 
     /// Complete the connection
     fn peer_connect(&self, peer_id: &ResponderId, res: PeerAuthResponse) -> Result<PeerSession>;
 
+    /// Restrict which enclave measurements `peer_accept()` and `peer_connect()` will federate
+    /// with, replacing any previous allow-list. Until this is called, any peer which passes
+    /// attestation is accepted; an empty list rejects every peer with `PeerIdentityNotAllowed`.
+    ///
+    /// The attested handshake pins inbound peers to this enclave's own MRENCLAVE, and outbound
+    /// peers to the measurement given to `peer_init_pinned()`, or to this enclave's own MRENCLAVE
+    /// for `peer_init()`. A peer is accepted if that pinned measurement is in the allow-list.
+    fn set_peer_identity_policy(&self, allowed: Vec<Measurement>) -> Result<()>;
+
     /// Destroy a peer association
     fn peer_close(&self, channel_id: &PeerSession) -> Result<()>;
 
+    /// Destroy every peer association, inbound and outbound, in a single call.
+    ///
+    /// A failure while closing one set of sessions does not prevent the others from being closed;
+    /// the first error encountered is returned once every session has been attempted.
+    fn peer_close_all(&self) -> Result<()>;
+
+    /// Destroy the given client and peer associations in a single call. Every session is torn
+    /// down at once, so no other call sees only part of the set closed.
+    ///
+    /// Returns the outcome for each session, clients first, in the order given. Sessions which
+    /// were not open are reported as `AttestEnclaveError::NotFound`, without failing the others.
+    fn close_sessions(
+        &self,
+        clients: &[ClientSession],
+        peers: &[PeerSession],
+    ) -> Result<Vec<(SessionRef, Result<()>)>>;
+
     // TRANSACTION-HANDLING API
 
     /// Performs the first steps in accepting transactions from a remote client:
     /// 1) Re-encrypt all txs for the local enclave
     /// 2) Extract context data to be handed back to untrusted so that it could collect the
     ///    information required by `tx_is_well_formed`.
+    ///
+    /// Messages larger than the enclave's limit (`DEFAULT_MAX_PROPOSAL_BYTES` unless another is
+    /// set with `set_max_proposal_bytes`) are rejected with `Error::ProposalTooLarge` before
+    /// being decrypted.
     fn client_tx_propose(&self, msg: EnclaveMessage<ClientSession>) -> Result<TxContext>;
 
+    /// Same as `client_tx_propose`, but also returns a receipt signed by the enclave's block
+    /// signing key covering the tx hash and the given current block index. Clients can keep the
+    /// receipt as proof that the node accepted their submission.
+    fn client_tx_propose_with_receipt(
+        &self,
+        msg: EnclaveMessage<ClientSession>,
+        block_index: u64,
+    ) -> Result<(TxContext, ProposeReceipt)>;
+
     /// Performs the first steps in accepting transactions from a remote peer:
     /// 1) Re-encrypt all txs for the local enclave
     /// 2) Extract context data to be handed back to untrusted so that it could collect the
     ///    information required by `tx_is_well_formed`.
+    ///
+    /// The outer result fails only if the message itself cannot be decrypted or parsed. Each
+    /// transaction inside it is handled independently, so a single corrupt tx relayed by a peer
+    /// fails its own entry without discarding the valid ones. The message is decrypted exactly
+    /// once either way, keeping the AKE channel state in sync with the peer.
     /// TODO: rename to txs_propose since this operates on multiple txs?
-    fn peer_tx_propose(&self, msg: EnclaveMessage<PeerSession>) -> Result<Vec<TxContext>>;
+    fn peer_tx_propose(&self, msg: EnclaveMessage<PeerSession>) -> Result<Vec<Result<TxContext>>>;
+
+    /// Same as `peer_tx_propose`, but txs whose hashes are in `known_hashes` are not re-encrypted.
+    /// Their entries fail with `Error::KnownTx` instead, so a node relaying gossip it has already
+    /// seen does not pay for re-encrypting it. Untrusted passes the hashes of the txs it holds.
+    fn peer_tx_propose_dedup(
+        &self,
+        msg: EnclaveMessage<PeerSession>,
+        known_hashes: &[TxHash],
+    ) -> Result<Vec<Result<TxContext>>>;
+
+    /// Same as `peer_tx_propose_dedup`, but also returns the position of the message among the
+    /// chunks `txs_for_peer_chunked` split its batch into, so that the receiver can tell when it
+    /// holds the whole batch, see `PeerTxChunks`. A message from `txs_for_peer` is chunk 0 of 1.
+    fn peer_tx_propose_chunk(
+        &self,
+        msg: EnclaveMessage<PeerSession>,
+        known_hashes: &[TxHash],
+    ) -> Result<PeerTxChunk>;
 
     /// Checks a LocallyEncryptedTx for well-formedness using the given membership proofs and current block index.
+    ///
+    /// The enclave has no view of the ledger of its own, so there is no "expected" Merkle root it
+    /// validates against: each proof is checked against the root element it carries, and all
+    /// proofs must share that same root. Untrusted can therefore pre-check proofs locally by
+    /// comparing their last element against its own ledger's current root.
+    ///
+    /// The enclave does remember the roots of the last `INCLUSION_HISTORY_BLOCKS` blocks it
+    /// formed. Proofs sharing the root of one of those blocks below `block_index` were made
+    /// against an outdated ledger, and are rejected with `Error::StaleMembershipProof`. Untrusted
+    /// must therefore read `block_index` from the same ledger state as the proofs: proofs read
+    /// just before a block is written are stale once `block_index` counts that block.
+    ///
+    /// This staleness check is best-effort, not a guarantee that the proofs are current: the
+    /// roots are only kept in memory and are lost on restart, and proofs against a root older
+    /// than the remembered blocks, or one the enclave never formed a block on, can not be told
+    /// apart from current ones.
+    ///
+    /// Ring members are never resolved by the enclave: each input carries its ring's TxOuts and
+    /// their membership proofs inside the tx. The only ledger data untrusted supplies is `proofs`,
+    /// one root proof per ring member, which the enclave must check in any case. Supplying any
+    /// other number of proofs fails with `Error::ProofCountMismatch`.
+    ///
+    /// The proofs also tell the enclave how many TxOuts that ledger holds. A transaction whose
+    /// highest membership proof indices lie beyond it is rejected with `Error::IndexOutOfRange`
+    /// before any further validation is done.
     fn tx_is_well_formed(
         &self,
         locally_encrypted_tx: LocallyEncryptedTx,
@@ -206,8 +1381,85 @@ pub trait ConsensusEnclave {
         proofs: Vec<TxOutMembershipProof>,
     ) -> Result<(WellFormedEncryptedTx, WellFormedTxContext)>;
 
+    /// Checks a batch of LocallyEncryptedTxs for well-formedness in a single enclave call. Each
+    /// entry holds the tx, the current block index and the tx's membership proofs, exactly as
+    /// they would be passed to `tx_is_well_formed`.
+    ///
+    /// The returned vector lines up index-for-index with `txs`: the i-th result is the outcome of
+    /// checking the i-th input. A malformed tx only fails its own entry, the rest of the batch is
+    /// still processed.
+    fn txs_are_well_formed(
+        &self,
+        txs: Vec<(LocallyEncryptedTx, u64, Vec<TxOutMembershipProof>)>,
+    ) -> Result<Vec<Result<(WellFormedEncryptedTx, WellFormedTxContext)>>>;
+
+    /// Same as `tx_is_well_formed`, but also rejects a transaction spending any of
+    /// `spent_key_images`, the key images untrusted knows to be spent on the ledger, with
+    /// `WellFormedError::SpentKeyImage`. This turns away double spends before they reach the
+    /// mempool, rather than only when a block is formed. The check is made before the
+    /// transaction is validated, so it also spares the cost of validating it.
+    fn tx_is_well_formed_unspent(
+        &self,
+        locally_encrypted_tx: LocallyEncryptedTx,
+        block_index: u64,
+        proofs: Vec<TxOutMembershipProof>,
+        spent_key_images: &[KeyImage],
+    ) -> Result<(WellFormedEncryptedTx, WellFormedTxContext)>;
+
+    /// Checks only the ring signature of a LocallyEncryptedTx, against the rings and outputs the
+    /// transaction itself carries. This needs no membership proofs, so untrusted can use it to
+    /// cheaply reject forged transactions before fetching proofs from the ledger.
+    ///
+    /// Passing this check does not make a transaction well-formed: the rings are not known to be
+    /// in the ledger until `tx_is_well_formed` checks their membership proofs.
+    fn tx_ring_signature_is_valid(&self, locally_encrypted_tx: &LocallyEncryptedTx) -> Result<()>;
+
+    /// Checks only that a LocallyEncryptedTx is intact, i.e. that its authentication tag
+    /// verifies, failing with `Error::Integrity` otherwise. Nothing is deserialized or validated,
+    /// so untrusted can use this to cheaply detect corruption of persisted transactions before
+    /// calling `tx_is_well_formed`.
+    fn integrity_check(&self, locally_encrypted_tx: &LocallyEncryptedTx) -> Result<()>;
+
+    /// Checks that `expected` is the hash of the transaction a LocallyEncryptedTx holds, failing
+    /// with `Error::TxHashMismatch` otherwise. This lets auditors cross-check the `tx_hash`
+    /// reported in a `TxContext` against the enclave, without revealing the transaction.
+    fn verify_tx_hash(
+        &self,
+        locally_encrypted_tx: &LocallyEncryptedTx,
+        expected: &TxHash,
+    ) -> Result<()>;
+
+    /// Retrieve the fee a LocallyEncryptedTx pays, as `(token_id, fee)`, without checking that
+    /// it is well-formed. This lets untrusted order proposals for admission before gathering the
+    /// membership proofs `tx_is_well_formed` needs. The fee matches
+    /// `WellFormedTxContext::fee_token` for a tx that later passes `tx_is_well_formed`.
+    ///
+    /// The whole tx is decrypted, since the local cipher authenticates it as a unit.
+    fn tx_fee_preview(&self, locally_encrypted_tx: &LocallyEncryptedTx) -> Result<(u64, u64)>;
+
+    /// Retrieve the minimum fee a transaction must pay in order to be considered well-formed at
+    /// the given block index. This is the same fee policy enforced by `tx_is_well_formed`.
+    fn get_minimum_fee(&self, block_index: u64) -> Result<u64>;
+
+    /// Retrieve the fee schedule enforced by `tx_is_well_formed`, as `(token_id, minimum_fee)`
+    /// pairs. The transaction format can not name a fee token yet, so every fee is paid in
+    /// `DEFAULT_TOKEN_ID` and the schedule holds that token alone, at the fee returned by
+    /// `get_minimum_fee`.
+    fn get_minimum_fees(&self) -> Result<Vec<(u64, u64)>>;
+
+    /// Retrieve a digest of the full fee schedule enforced by `tx_is_well_formed`, see
+    /// `digest_fee_policy`. Operators can compare digests across nodes to confirm they all
+    /// enforce the same fee policy, before a mismatch leads to divergent blocks.
+    fn fee_policy_digest(&self) -> Result<[u8; 32]>;
+
     /// Re-encrypt sealed transactions for the given peer session, using the given authenticated
     /// data for the peer.
+    ///
+    /// The transactions are deliberately not compressed before encryption. A batch mixes
+    /// transactions from many clients, so its compressed length would reveal how much an
+    /// attacker's own transaction shares with the others, e.g. ring members, to anyone watching
+    /// the peer link. Transactions are mostly curve points and signatures, so there is little to
+    /// gain from compressing them in any case.
     fn txs_for_peer(
         &self,
         encrypted_txs: &[WellFormedEncryptedTx],
@@ -215,19 +1467,656 @@ pub trait ConsensusEnclave {
         peer: &PeerSession,
     ) -> Result<EnclaveMessage<PeerSession>>;
 
+    /// Same as `txs_for_peer`, but splits the transactions across as many messages as needed so
+    /// that none of them is larger than `max_chunk_bytes`, counting each message's `aad`,
+    /// `channel_id` and encrypted `data` together. Each message can be decrypted and handed to
+    /// `peer_tx_propose` on its own, and its encrypted payload carries the chunk index and the
+    /// total number of chunks, which the receiver reads with `peer_tx_propose_chunk`. Messages
+    /// must be delivered in order, since each one advances the peer channel's nonce.
+    ///
+    /// Fails with `Error::ChunkTooSmall` if a single transaction does not fit in `max_chunk_bytes`
+    /// alongside the `aad` and the channel id.
+    fn txs_for_peer_chunked(
+        &self,
+        encrypted_txs: &[WellFormedEncryptedTx],
+        aad: &[u8],
+        peer: &PeerSession,
+        max_chunk_bytes: usize,
+    ) -> Result<Vec<EnclaveMessage<PeerSession>>>;
+
     /// Redact txs in order to form a new block.
     /// Returns a block, the set of redacted transactions included in it, and a signature over the
-    /// block's digest.
+    /// block's digest. Fails with `Error::NonMonotonicBlock` unless the new block is higher than
+    /// any block signed before, see `current_signing_height`, and with `Error::BrokenChain` if
+    /// `parent_block`'s id does not match its contents.
+    ///
+    /// Transactions are included ordered by `WellFormedTxContext::priority`, highest first, with
+    /// ties broken by ascending tx hash, so the resulting block does not depend on the order of
+    /// `txs`. Once the enclave's `BlockLimits` are reached, the remaining transactions are left
+    /// out; `form_block_with_report` reports which ones. If the limits leave out every one of
+    /// them, the call fails with `Error::NoTxFitsBlockLimits`.
     fn form_block(
         &self,
         parent_block: &Block,
         txs: &[(WellFormedEncryptedTx, Vec<TxOutMembershipProof>)],
     ) -> Result<(Block, Vec<RedactedTx>, BlockSignature)>;
+
+    /// Same as `form_block`, but produces a block at the given format version. This allows
+    /// operators to form blocks at a negotiated version while rolling out a block format upgrade.
+    /// Versions this enclave does not know how to produce are rejected with
+    /// `Error::UnsupportedBlockVersion`.
+    fn form_block_versioned(
+        &self,
+        parent_block: &Block,
+        txs: &[(WellFormedEncryptedTx, Vec<TxOutMembershipProof>)],
+        block_version: u32,
+    ) -> Result<(Block, Vec<RedactedTx>, BlockSignature)>;
+
+    /// Retrieve the lowest and highest block format versions, inclusive, this enclave can
+    /// produce with `form_block_versioned`, so orchestration can pick a version every node in the
+    /// quorum supports.
+    fn block_version_range(&self) -> Result<(u32, u32)>;
+
+    /// Same as `form_block`, but also reports whether each candidate transaction was included.
+    ///
+    /// The enclave takes transactions in the order described for `form_block` until the next one
+    /// would exceed its `BlockLimits`. That transaction and all remaining candidates are left out
+    /// and reported as `InclusionStatus::Excluded`, so the caller can propose them again rather
+    /// than dropping them. Every candidate's hash appears in the report exactly once: included
+    /// transactions first, in block order, followed by the excluded ones.
+    fn form_block_with_report(
+        &self,
+        parent_block: &Block,
+        txs: &[(WellFormedEncryptedTx, Vec<TxOutMembershipProof>)],
+    ) -> Result<(Block, Vec<RedactedTx>, BlockSignature, Vec<(TxHash, InclusionStatus)>)>;
+
+    /// Same as `form_block`, but leaves out the candidates whose hashes are in `exclude`, so that
+    /// untrusted can drop transactions it decided against after proposing them without rebuilding
+    /// the candidate set. Hashes which match no candidate are ignored.
+    fn form_block_excluding(
+        &self,
+        parent_block: &Block,
+        txs: &[(WellFormedEncryptedTx, Vec<TxOutMembershipProof>)],
+        exclude: &[TxHash],
+    ) -> Result<(Block, Vec<RedactedTx>, BlockSignature)>;
+
+    /// Form and sign a block containing no transactions on top of `parent_block`, so that
+    /// operators can advance the chain while no transactions are being externalized.
+    ///
+    /// `root_proof` is any membership proof against the ledger as of `parent_block`, and
+    /// supplies the block's `root_element`, as the proofs of the transactions do in `form_block`.
+    /// Since the ledger does not accept blocks without transactions, the block holds a single
+    /// zero-value aggregate fee output, derived from the parent's id. The block is therefore
+    /// fully determined by its parent and the root, so every node forms the same one.
+    ///
+    /// It is signed under the same rules as `form_block`, so it takes the place of any other
+    /// block at its height.
+    fn form_empty_block(
+        &self,
+        parent_block: &Block,
+        root_proof: &TxOutMembershipProof,
+    ) -> Result<(Block, Vec<RedactedTx>, BlockSignature)>;
+
+    /// Estimate the enclave memory, in bytes, `form_block` needs to form a block from `txs`, so
+    /// that untrusted can split a large candidate set into calls the enclave can afford. The
+    /// estimate is computed from the sizes of the candidates, without decrypting them, and grows
+    /// linearly with their number.
+    fn estimate_form_block_cost(
+        &self,
+        txs: &[(WellFormedEncryptedTx, Vec<TxOutMembershipProof>)],
+    ) -> Result<usize>;
+
+    /// Redact a single well-formed transaction, exactly as `form_block` redacts it when including
+    /// it in a block. A client can be handed this one `RedactedTx` and match it against the block
+    /// it landed in, without downloading the rest of the block's transactions.
+    fn redact_single(&self, encrypted_tx: &WellFormedEncryptedTx) -> Result<RedactedTx>;
+
+    /// Recover the `WellFormedTxContext` `tx_is_well_formed` returned alongside `encrypted_tx`,
+    /// so that untrusted can re-queue transactions `form_block` left out without keeping their
+    /// contexts around. The transaction is only decrypted, not checked for well-formedness again.
+    fn context_of(&self, encrypted_tx: &WellFormedEncryptedTx) -> Result<WellFormedTxContext>;
+
+    /// Recover the aggregate fee `form_block` minted into `block`, given the block's redacted
+    /// transactions, so that auditors can reconcile fee revenue against the ledger. The fee is
+    /// recovered from the fee output, using the publicly known fee view key. Transactions which
+    /// do not match the block's contents hash are rejected with `Error::BlockContentsMismatch`.
+    fn block_total_fees(&self, block: &Block, redacted: &[RedactedTx]) -> Result<u64>;
+
+    /// Set the limits enforced by `form_block` and its variants on every block formed from now on.
+    /// Until this is called, `BlockLimits::default()` applies.
+    fn set_block_limits(&self, limits: BlockLimits) -> Result<()>;
+
+    /// Set the size, in bytes, of the largest client message `client_tx_propose` accepts from now
+    /// on. Until this is called, `DEFAULT_MAX_PROPOSAL_BYTES` applies.
+    fn set_max_proposal_bytes(&self, max_proposal_bytes: usize) -> Result<()>;
+
+    /// Set the window of tombstone blocks `tx_is_well_formed` and `txs_are_well_formed` admit
+    /// from now on, replacing any previous window. Transactions outside it are rejected with
+    /// `Error::TombstoneOutsideWindow`. Until this is called, only expired transactions and
+    /// those beyond the tombstone limit enforced by transaction validation are rejected.
+    fn set_tombstone_window(&self, window: TombstoneWindow) -> Result<()>;
+
+    /// Retrieve the index of the last block signed by `form_block` or one of its variants, or 0
+    /// if none was signed yet. Forming a block at this height or below fails with
+    /// `Error::NonMonotonicBlock`, so the enclave never signs two blocks at the same height.
+    ///
+    /// The height is only kept in memory: it starts over at 0 whenever the enclave is restarted.
+    fn current_signing_height(&self) -> Result<u64>;
+
+    /// Retrieve the index of the block `form_block` or one of its variants included the
+    /// transaction with the given hash in, if it is one of the last `INCLUSION_HISTORY_BLOCKS`
+    /// blocks formed. Returns `None` for older blocks and for transactions never included.
+    ///
+    /// Like the signing height, this is only kept in memory and is lost on restart.
+    fn included_in_block(&self, tx_hash: &TxHash) -> Result<Option<u64>>;
+
+    /// Retrieve the key images spent by the blocks `form_block` or one of its variants formed at
+    /// or after `since_block`, among the last `INCLUSION_HISTORY_BLOCKS` blocks formed, oldest
+    /// block first. Operators can compare this across nodes when reconciling double-spend
+    /// protection. Key images are published in every block, so nothing private is revealed.
+    ///
+    /// The key images come from the same block history as `included_in_block`. After a restart
+    /// only blocks formed since are covered, so nodes restarted at different times can disagree
+    /// here without either having missed a double spend.
+    fn recent_key_images(&self, since_block: u64) -> Result<Vec<KeyImage>>;
+
+    /// Checks that `signature` is a valid signature over `block` made by this enclave's block
+    /// signing key, e.g. to self-verify the output of `form_block` before broadcasting it.
+    /// Fails with `Error::Signature` otherwise.
+    fn verify_own_block_signature(&self, block: &Block, signature: &BlockSignature) -> Result<()>;
+
+    /// Same as `verify_own_block_signature`, for a run of blocks in a single call, e.g. when a
+    /// node reattaching to the network checks the blocks it signed recently. Returns, in order,
+    /// whether each signature would be accepted by `verify_own_block_signature`.
+    fn verify_own_block_signatures(&self, blocks: &[(Block, BlockSignature)]) -> Result<Vec<bool>>;
+
+    /// Whether `key` is this enclave's block signing key, or one of the last
+    /// `RETIRED_SIGNER_HISTORY` keys it replaced with `rotate_signing_key`. Auditors can use this
+    /// to check the signer of a block formed before a rotation. Retired keys are only kept in
+    /// memory, so after a restart only the current key is trusted.
+    fn is_trusted_signer(&self, key: &Ed25519Public) -> Result<bool>;
 }
 
 /// Helper trait which reduces boiler-plate in untrusted side
 /// The trusted object which implements consensus_enclave usually cannot implement
 /// Clone, Send, Sync, etc., but the untrusted side can and usually having a "handle to an enclave"
 /// is what is most useful for a webserver.
-/// This marker trait can be implemented for the untrusted-side representation of the enclave.
-pub trait ConsensusEnclaveProxy: ConsensusEnclave + Clone + Send + Sync + 'static {}
+/// This trait can be implemented for the untrusted-side representation of the enclave.
+pub trait ConsensusEnclaveProxy: ConsensusEnclave + Clone + Send + Sync + 'static {
+    /// Run `f` while holding a lock shared by every clone of this proxy, so that the calls `f`
+    /// makes do not interleave with those of another `with_exclusive` section. Each enclave
+    /// method locks internally and is atomic on its own, but a caller running e.g. `form_block`
+    /// on one thread and `rotate_signing_key` on another can not otherwise control which key
+    /// signs the block. Calls made outside `with_exclusive` are not held back.
+    ///
+    /// Fails with `Error::Poison` if an earlier section panicked while holding the lock.
+    fn with_exclusive<R>(&self, f: impl FnOnce() -> R) -> Result<R>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{collections::BTreeSet, vec};
+    use rand_core::{RngCore, SeedableRng};
+    use rand_hc::Hc128Rng;
+    use transaction::tx::TxPrefix;
+
+    const IAS_WITH_PIB: &str = include_str!("../../../../attest/core/data/test/ias_with_pib.json");
+
+    fn tx_context_with_key_images(key_images: &[u64]) -> TxContext {
+        TxContext {
+            key_images: key_images.iter().map(|i| KeyImage::from(*i)).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_conflicts_with_disjoint_key_images() {
+        let a = tx_context_with_key_images(&[1, 2, 3]);
+        let b = tx_context_with_key_images(&[4, 5]);
+        assert!(!a.conflicts_with(&b));
+        assert!(!b.conflicts_with(&a));
+
+        // A context without key images conflicts with nothing.
+        let empty = tx_context_with_key_images(&[]);
+        assert!(!a.conflicts_with(&empty));
+        assert!(!empty.conflicts_with(&empty));
+    }
+
+    #[test]
+    fn test_conflicts_with_overlapping_key_images() {
+        let a = tx_context_with_key_images(&[1, 2, 3]);
+        let b = tx_context_with_key_images(&[3, 4]);
+        assert!(a.conflicts_with(&b));
+        assert!(b.conflicts_with(&a));
+    }
+
+    #[test]
+    fn test_conflicts_with_identical_key_images() {
+        let a = tx_context_with_key_images(&[1, 2]);
+        let b = TxContext {
+            tx_hash: TxHash([7u8; 32]),
+            ..a.clone()
+        };
+        assert!(a.conflicts_with(&a));
+        assert!(a.conflicts_with(&b));
+        assert!(b.conflicts_with(&a));
+    }
+
+    #[test]
+    fn test_sorted_key_images_is_canonical() {
+        let a = tx_context_with_key_images(&[3, 1, 4, 2]);
+        let b = tx_context_with_key_images(&[2, 4, 1, 3]);
+        let sorted = a.sorted_key_images();
+        assert!(sorted.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(b.sorted_key_images(), sorted);
+
+        // Sorting again, or sorting an already sorted context, changes nothing.
+        assert_eq!(a.sorted_key_images(), sorted);
+        let c = TxContext {
+            key_images: sorted.clone(),
+            ..Default::default()
+        };
+        assert_eq!(c.sorted_key_images(), sorted);
+
+        // The original key images are left untouched.
+        assert_eq!(a, tx_context_with_key_images(&[3, 1, 4, 2]));
+    }
+
+    fn well_formed_tx_context_with_key_images(key_images: &[u64]) -> WellFormedTxContext {
+        WellFormedTxContext {
+            key_images: key_images.iter().map(|i| KeyImage::from(*i)).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_shared_key_images_disjoint() {
+        let a = well_formed_tx_context_with_key_images(&[1, 2, 3]);
+        let b = well_formed_tx_context_with_key_images(&[4, 5]);
+        assert_eq!(a.shared_key_images(&b), vec![]);
+        assert_eq!(b.shared_key_images(&a), vec![]);
+
+        // A context without key images shares nothing.
+        let empty = well_formed_tx_context_with_key_images(&[]);
+        assert_eq!(a.shared_key_images(&empty), vec![]);
+        assert_eq!(empty.shared_key_images(&a), vec![]);
+    }
+
+    #[test]
+    fn test_shared_key_images_overlapping() {
+        let a = well_formed_tx_context_with_key_images(&[1, 2, 3, 4]);
+        let b = well_formed_tx_context_with_key_images(&[4, 5, 2]);
+        assert_eq!(
+            a.shared_key_images(&b),
+            vec![KeyImage::from(2), KeyImage::from(4)]
+        );
+        // Each side lists the shared key images in its own order.
+        assert_eq!(
+            b.shared_key_images(&a),
+            vec![KeyImage::from(4), KeyImage::from(2)]
+        );
+        assert_eq!(a.shared_key_images(&a), a.key_images().clone());
+    }
+
+    #[test]
+    fn test_tx_context_serialized_size() {
+        let mut rng = Hc128Rng::from_seed([68u8; 32]);
+        let mut encrypted_tx = vec![0u8; 1000];
+        rng.fill_bytes(&mut encrypted_tx);
+        let contexts = [
+            TxContext::default(),
+            TxContext::new(
+                LocallyEncryptedTx(encrypted_tx),
+                TxHash([200u8; 32]),
+                vec![0, 23, 24, 1 << 20, u64::MAX],
+                (0u64..11).map(KeyImage::from).collect(),
+                u64::from(u32::MAX) + 1,
+            ),
+            TxContext {
+                locally_encrypted_tx: LocallyEncryptedTx(vec![255u8; 300]),
+                ..tx_context_with_key_images(&[1, 2, 3])
+            },
+        ];
+
+        for context in contexts.iter() {
+            let actual = mcserial::serialize(context).unwrap().len();
+            let bound = context.serialized_size();
+            assert!(actual <= bound, "{} > {}", actual, bound);
+            assert!(bound <= 2 * actual, "{} > 2 * {}", bound, actual);
+        }
+    }
+
+    #[test]
+    fn test_ias_report_info_timestamp_matches_report_body() {
+        let report = VerificationReport {
+            http_body: IAS_WITH_PIB.into(),
+            ..Default::default()
+        };
+        let data = VerificationReportData::try_from(&report).unwrap();
+        assert_eq!(data.timestamp, "2019-06-19T22:11:17.616333");
+
+        let info = IasReportInfo::try_from(report.clone()).unwrap();
+        assert_eq!(info.report, report);
+        assert_eq!(info.issued_at, 1_560_982_277);
+        assert_eq!(info.issued_at, data.parse_timestamp().unwrap());
+        assert_eq!(info.expires_at, info.issued_at + IAS_REPORT_MAX_AGE_SECS);
+
+        assert_eq!(info.remaining_validity(info.issued_at), IAS_REPORT_MAX_AGE_SECS);
+        assert_eq!(info.remaining_validity(info.expires_at), 0);
+        assert_eq!(info.remaining_validity(info.expires_at + 1), 0);
+    }
+
+    #[test]
+    fn test_ias_report_blocks_remaining_at() {
+        // Far from the refresh boundary.
+        assert_eq!(ias_report_blocks_remaining_at(1000, 1000, 5, 50), 50);
+        assert_eq!(ias_report_blocks_remaining_at(1000, 1050, 5, 50), 40);
+
+        // Partial blocks do not count towards the report's age.
+        assert_eq!(ias_report_blocks_remaining_at(1000, 1054, 5, 50), 40);
+
+        // Near and at the boundary.
+        assert_eq!(ias_report_blocks_remaining_at(1000, 1245, 5, 50), 1);
+        assert_eq!(ias_report_blocks_remaining_at(1000, 1250, 5, 50), 0);
+        assert_eq!(ias_report_blocks_remaining_at(1000, 1255, 5, 50), 0);
+        assert_eq!(ias_report_blocks_remaining_at(1000, u64::MAX, 5, 50), 0);
+
+        // A clock behind the issue time counts as a fresh report.
+        assert_eq!(ias_report_blocks_remaining_at(1000, 900, 5, 50), 50);
+        assert_eq!(ias_report_blocks_remaining_at(1000, 1000, 5, 0), 0);
+        assert_eq!(ias_report_blocks_remaining_at(1000, 1000, 0, 50), 0);
+    }
+
+    #[test]
+    fn test_ias_report_issued_at() {
+        let report = VerificationReport {
+            http_body: IAS_WITH_PIB.into(),
+            ..Default::default()
+        };
+        assert_eq!(ias_report_issued_at(&report), Ok(1_560_982_277));
+        assert!(ias_report_issued_at(&VerificationReport::default()).is_err());
+    }
+
+    #[test]
+    fn test_ias_report_info_rejects_unparseable_report() {
+        assert!(IasReportInfo::try_from(VerificationReport::default()).is_err());
+    }
+
+    #[test]
+    fn test_well_formed_encrypted_tx_len() {
+        let encrypted_tx = WellFormedEncryptedTx(vec![7u8; 123]);
+        assert_eq!(encrypted_tx.encrypted_len(), encrypted_tx.0.len());
+        assert_eq!(encrypted_tx.encrypted_len(), 123);
+        assert_eq!(encrypted_tx.as_ref(), &encrypted_tx.0[..]);
+
+        assert_eq!(WellFormedEncryptedTx::default().encrypted_len(), 0);
+    }
+
+    #[test]
+    fn test_well_formed_encrypted_tx_format_version() {
+        let encrypted_tx = WellFormedEncryptedTx(vec![WELL_FORMED_TX_FORMAT_VERSION, 9, 9]);
+        assert_eq!(encrypted_tx.format_version(), WELL_FORMED_TX_FORMAT_VERSION);
+        assert_eq!(WellFormedEncryptedTx(vec![7u8; 3]).format_version(), 7);
+        assert_eq!(WellFormedEncryptedTx::default().format_version(), 0);
+    }
+
+    #[test]
+    fn test_well_formed_encrypted_tx_batch_round_trip() {
+        let empty = WellFormedEncryptedTx::encode_batch(&[]);
+        assert_eq!(empty.len(), BATCH_LEN_PREFIX_LEN);
+        assert_eq!(WellFormedEncryptedTx::decode_batch(&empty), Ok(Vec::new()));
+
+        // Txs of varying sizes, including empty ones.
+        let txs: Vec<WellFormedEncryptedTx> = (0..1000)
+            .map(|i| WellFormedEncryptedTx(vec![i as u8; i % 300]))
+            .collect();
+        let buf = WellFormedEncryptedTx::encode_batch(&txs);
+        let payload_len: usize = txs.iter().map(|tx| tx.encrypted_len()).sum();
+        assert_eq!(buf.len(), payload_len + BATCH_LEN_PREFIX_LEN * (txs.len() + 1));
+        assert_eq!(WellFormedEncryptedTx::decode_batch(&buf), Ok(txs));
+    }
+
+    #[test]
+    fn test_well_formed_encrypted_tx_batch_rejects_corrupt_buffer() {
+        let txs = vec![
+            WellFormedEncryptedTx(vec![1u8; 10]),
+            WellFormedEncryptedTx(vec![2u8; 20]),
+        ];
+        let buf = WellFormedEncryptedTx::encode_batch(&txs);
+
+        // Truncated anywhere, including between txs or inside a length prefix.
+        for len in 0..buf.len() {
+            assert_eq!(
+                WellFormedEncryptedTx::decode_batch(&buf[..len]),
+                Err(Error::Serialization)
+            );
+        }
+
+        // Trailing bytes.
+        let mut trailing = buf.clone();
+        trailing.push(0);
+        assert_eq!(
+            WellFormedEncryptedTx::decode_batch(&trailing),
+            Err(Error::Serialization)
+        );
+
+        // A count or length larger than the buffer.
+        let mut bad_count = buf.clone();
+        bad_count[..BATCH_LEN_PREFIX_LEN].copy_from_slice(&u32::max_value().to_le_bytes());
+        assert_eq!(
+            WellFormedEncryptedTx::decode_batch(&bad_count),
+            Err(Error::Serialization)
+        );
+        let mut bad_len = buf;
+        bad_len[BATCH_LEN_PREFIX_LEN] = 0xff;
+        assert_eq!(
+            WellFormedEncryptedTx::decode_batch(&bad_len),
+            Err(Error::Serialization)
+        );
+    }
+
+    #[test]
+    fn test_digest_fee_policy() {
+        let schedule = [(DEFAULT_TOKEN_ID, 10), (1, 7)];
+        let digest = digest_fee_policy(&schedule);
+        assert_eq!(digest, digest_fee_policy(&[(1, 7), (DEFAULT_TOKEN_ID, 10)]));
+
+        // Changing, adding or removing any minimum fee changes the digest.
+        let variants = [
+            vec![(DEFAULT_TOKEN_ID, 11), (1, 7)],
+            vec![(DEFAULT_TOKEN_ID, 10), (1, 8)],
+            vec![(DEFAULT_TOKEN_ID, 10), (2, 7)],
+            vec![(DEFAULT_TOKEN_ID, 10), (1, 7), (2, 7)],
+            vec![(DEFAULT_TOKEN_ID, 10)],
+            vec![],
+        ];
+        let mut digests = BTreeSet::new();
+        digests.insert(digest);
+        for variant in variants.iter() {
+            assert!(digests.insert(digest_fee_policy(variant)));
+        }
+    }
+
+    #[test]
+    fn test_well_formed_tx_context_fee_token() {
+        // The tx format can not name a fee token, so contexts built from a tx always pay fees in
+        // the default token.
+        let tx = Tx {
+            prefix: TxPrefix {
+                fee: 17,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let context = WellFormedTxContext::from(&tx);
+        assert_eq!(context.token_id(), DEFAULT_TOKEN_ID);
+        assert_eq!(context.fee_token(), (DEFAULT_TOKEN_ID, 17));
+    }
+
+    #[test]
+    fn test_by_priority_pops_in_priority_order() {
+        let context = |priority: u64, hash_byte: u8| WellFormedTxContext {
+            tx_hash: TxHash([hash_byte; 32]),
+            priority,
+            ..Default::default()
+        };
+
+        let mut heap = alloc::collections::BinaryHeap::new();
+        for (priority, hash_byte) in &[(10, 1), (30, 2), (20, 3), (30, 1), (5, 9)] {
+            heap.push(ByPriority(context(*priority, *hash_byte)));
+        }
+
+        let popped: Vec<(u64, u8)> = core::iter::from_fn(|| heap.pop())
+            .map(|ByPriority(context)| (context.priority(), context.tx_hash().0[0]))
+            .collect();
+        // Highest priority first, with equal priorities popped in ascending tx hash order.
+        assert_eq!(popped, vec![(30, 1), (30, 2), (20, 3), (10, 1), (5, 9)]);
+
+        // A higher fee does not outrank a higher priority, e.g. one from a smaller tx.
+        let large = WellFormedTxContext {
+            fee: 100,
+            ..context(10, 1)
+        };
+        let small = WellFormedTxContext {
+            fee: 50,
+            ..context(20, 2)
+        };
+        assert!(ByPriority(small) > ByPriority(large));
+
+        // The original ordering, used for map keys, is unchanged.
+        assert!(context(30, 1) < context(10, 2));
+    }
+
+    #[test]
+    fn test_into_priority_iter_matches_full_sort() {
+        let contexts: Vec<WellFormedTxContext> = (0..50u8)
+            .map(|i| WellFormedTxContext {
+                tx_hash: TxHash([i.wrapping_mul(37); 32]),
+                priority: u64::from(i % 7) * 10,
+                ..Default::default()
+            })
+            .collect();
+
+        let mut sorted: Vec<ByPriority> = contexts.iter().cloned().map(ByPriority).collect();
+        sorted.sort_by(|a, b| b.cmp(a));
+        let expected: Vec<WellFormedTxContext> = sorted
+            .into_iter()
+            .take(10)
+            .map(|ByPriority(context)| context)
+            .collect();
+
+        let top: Vec<WellFormedTxContext> = into_priority_iter(contexts.clone()).take(10).collect();
+        assert_eq!(top, expected);
+
+        // Draining the iterator yields every context exactly once.
+        assert_eq!(into_priority_iter(contexts).count(), 50);
+    }
+
+    fn peer_tx_chunk(chunk_index: u32, chunk_total: u32) -> PeerTxChunk {
+        let tx_context = TxContext {
+            tx_hash: TxHash([chunk_index as u8; 32]),
+            ..Default::default()
+        };
+        PeerTxChunk {
+            chunk_index,
+            chunk_total,
+            tx_contexts: vec![Ok(tx_context), Err(Error::KnownTx(TxHash::default()))],
+        }
+    }
+
+    #[test]
+    // Chunks received out of order are reassembled in the order they were sent.
+    fn test_peer_tx_chunks_out_of_order() {
+        let mut chunks = PeerTxChunks::new();
+        assert!(!chunks.is_complete());
+        for chunk_index in &[2, 0, 1] {
+            chunks.insert(peer_tx_chunk(*chunk_index, 3)).unwrap();
+        }
+        assert!(chunks.is_complete());
+        assert!(chunks.missing().is_empty());
+
+        let expected: Vec<Result<TxContext>> = (0..3)
+            .flat_map(|chunk_index| peer_tx_chunk(chunk_index, 3).tx_contexts)
+            .collect();
+        assert_eq!(chunks.into_tx_contexts(), Ok(expected));
+    }
+
+    #[test]
+    // A batch missing a chunk is reported as incomplete instead of being handed back with a gap.
+    fn test_peer_tx_chunks_missing_chunk() {
+        let mut chunks = PeerTxChunks::new();
+        assert_eq!(chunks.clone().into_tx_contexts(), Err(Error::MissingChunk(0)));
+
+        chunks.insert(peer_tx_chunk(3, 4)).unwrap();
+        chunks.insert(peer_tx_chunk(0, 4)).unwrap();
+        chunks.insert(peer_tx_chunk(2, 4)).unwrap();
+        assert!(!chunks.is_complete());
+        assert_eq!(chunks.missing(), vec![1]);
+        assert_eq!(chunks.into_tx_contexts(), Err(Error::MissingChunk(1)));
+    }
+
+    #[test]
+    // Duplicate chunks, and chunks of another batch, are rejected without changing the batch.
+    fn test_peer_tx_chunks_rejects_foreign_chunks() {
+        let mut chunks = PeerTxChunks::new();
+        chunks.insert(peer_tx_chunk(1, 2)).unwrap();
+
+        assert_eq!(
+            chunks.insert(peer_tx_chunk(1, 2)),
+            Err(Error::DuplicateChunk(1))
+        );
+        assert_eq!(
+            chunks.insert(peer_tx_chunk(0, 3)),
+            Err(Error::ChunkMismatch { index: 0, total: 3 })
+        );
+        assert_eq!(
+            chunks.insert(peer_tx_chunk(2, 2)),
+            Err(Error::ChunkMismatch { index: 2, total: 2 })
+        );
+        assert_eq!(chunks.missing(), vec![0]);
+
+        // A chunk can not claim to be part of an empty batch.
+        assert_eq!(
+            PeerTxChunks::new().insert(peer_tx_chunk(0, 0)),
+            Err(Error::ChunkMismatch { index: 0, total: 0 })
+        );
+    }
+
+    #[test]
+    fn test_tx_context_builder() {
+        let mut rng = Hc128Rng::from_seed([27u8; 32]);
+        let builder = test_utils::TxContextBuilder::new()
+            .num_inputs(3)
+            .ring_size(4);
+
+        let a = builder.build(&mut rng);
+        assert_eq!(a.key_images.len(), 3);
+        assert_eq!(a.highest_indices.len(), 12);
+
+        // Independently built contexts do not conflict with each other, only with themselves.
+        let b = builder.build(&mut rng);
+        assert_ne!(a.tx_hash, b.tx_hash);
+        assert!(!a.conflicts_with(&b));
+        assert!(a.conflicts_with(&a));
+    }
+
+    #[test]
+    fn test_unique_highest_indices() {
+        let tx_context = TxContext {
+            highest_indices: vec![7, 3, 7, 1, 3, 3, 9],
+            ..Default::default()
+        };
+        assert_eq!(tx_context.unique_highest_indices(), vec![1, 3, 7, 9]);
+        // The original indices are left untouched.
+        assert_eq!(tx_context.highest_indices, vec![7, 3, 7, 1, 3, 3, 9]);
+
+        let well_formed_tx_context = WellFormedTxContext {
+            highest_indices: vec![5, 5, 2],
+            ..Default::default()
+        };
+        assert_eq!(well_formed_tx_context.unique_highest_indices(), vec![2, 5]);
+
+        assert!(TxContext::default().unique_highest_indices().is_empty());
+    }
+}
@@ -2,15 +2,23 @@
 
 //! The message types used by the consensus_enclave_api.
 
-use crate::{LocallyEncryptedTx, ResponderId, SealedBlockSigningKey, WellFormedEncryptedTx};
+use crate::{
+    AttestationBundle, BlockLimits, Error, LocallyEncryptedTx, ResponderId, Result,
+    SealedBlockSigningKey, TombstoneWindow, WellFormedEncryptedTx,
+};
 use alloc::vec::Vec;
-use attest::{Quote, Report, TargetInfo, VerificationReport};
+use attest::{Measurement, Quote, Report, TargetInfo, VerificationReport};
 use attest_enclave_api::{
     ClientAuthRequest, ClientSession, EnclaveMessage, PeerAuthRequest, PeerAuthResponse,
     PeerSession,
 };
+use keys::Ed25519Public;
 use serde::{Deserialize, Serialize};
-use transaction::{tx::TxOutMembershipProof, Block};
+use transaction::{
+    ring_signature::KeyImage,
+    tx::{TxHash, TxOutMembershipProof},
+    Block, BlockSignature,
+};
 
 /// An enumeration of API calls and their arguments for use across serialization boundaries.
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
@@ -18,11 +26,46 @@ pub enum EnclaveCall {
     /// The [ConsensusEnclave::enclave_init()] method.
     EnclaveInit(ResponderId, ResponderId, Option<SealedBlockSigningKey>),
 
+    /// The [ConsensusEnclave::export_signing_key()] method.
+    ///
+    /// Seals a block signing key for the next enclave from the same signer.
+    ExportSigningKey(SealedBlockSigningKey, AttestationBundle),
+
+    /// The [ConsensusEnclave::reseal_signing_key()] method.
+    ///
+    /// Re-seals a block signing key exported by a previous enclave under the current one.
+    ResealSigningKey(SealedBlockSigningKey, Vec<u8>),
+
+    /// The [ConsensusEnclave::rotate_signing_key()] method.
+    ///
+    /// Replaces the block signing key with a freshly generated one.
+    RotateSigningKey,
+
+    /// The [ConsensusEnclave::seal_blob()] method.
+    ///
+    /// Seals a piece of operator state.
+    SealBlob(Vec<u8>),
+
+    /// The [ConsensusEnclave::unseal_blob()] method.
+    ///
+    /// Unseals a piece of operator state sealed by `seal_blob`.
+    UnsealBlob(Vec<u8>),
+
+    /// The [ConsensusEnclave::enclave_version()] method.
+    ///
+    /// Retrieves the API version and capabilities of an enclave.
+    EnclaveVersion,
+
     /// The [PeerableEnclave::peer_init()] method.
     ///
     /// Starts an outbound connection.
     PeerInit(ResponderId),
 
+    /// The [ConsensusEnclave::peer_init_pinned()] method.
+    ///
+    /// Starts an outbound connection to a peer which must present the given measurement.
+    PeerInitPinned(ResponderId, Measurement),
+
     /// The [PeerableEnclave::peer_accept()] method.
     ///
     /// Process a new inbound peer connection.
@@ -33,21 +76,46 @@ pub enum EnclaveCall {
     /// Completes an outbound connection using the peer's response.
     PeerConnect(ResponderId, PeerAuthResponse),
 
+    /// The [ConsensusEnclave::set_peer_identity_policy()] method.
+    ///
+    /// Replaces the set of enclave measurements peers are allowed to present.
+    SetPeerIdentityPolicy(Vec<Measurement>),
+
     /// The [PeerableEnclave::peer_close()] method.
     ///
     /// Tears down any in-enclave state about a peer association.
     PeerClose(PeerSession),
 
+    /// The [ConsensusEnclave::peer_close_all()] method.
+    ///
+    /// Tears down in-enclave state about every peer association.
+    PeerCloseAll,
+
+    /// The [ConsensusEnclave::close_sessions()] method.
+    ///
+    /// Tears down in-enclave state about the given client and peer associations.
+    CloseSessions(Vec<ClientSession>, Vec<PeerSession>),
+
     /// The [ConsensusEnclave::client_accept()] method.
     ///
     /// Process a new inbound client connection.
     ClientAccept(ClientAuthRequest),
 
+    /// The [ConsensusEnclave::client_accept_with_budget()] method.
+    ///
+    /// Process a new inbound client connection, if the session budget allows it.
+    ClientAcceptWithBudget(ClientAuthRequest, usize),
+
     /// The [ConsensusEnclave::client_close()] method.
     ///
     /// Tears down any in-enclave state about a client association.
     ClientClose(ClientSession),
 
+    /// The [ConsensusEnclave::client_sessions()] method.
+    ///
+    /// Retrieves the IDs of all established client sessions.
+    ClientSessions,
+
     /// The [ConsensusEnclave::get_identity()] method.
     ///
     /// Retrieves the public identity (X25519 public key) of an enclave.
@@ -58,6 +126,41 @@ pub enum EnclaveCall {
     /// Retrieves the block signer (Ed25519 public key) of an enclave.
     GetSigner,
 
+    /// The [ConsensusEnclave::get_signer_fingerprint()] method.
+    ///
+    /// Retrieves a fingerprint of the block signer of an enclave.
+    GetSignerFingerprint,
+
+    /// The [ConsensusEnclave::self_peer_id()] method.
+    ///
+    /// Retrieves the responder ID an enclave was initialized with for peers.
+    SelfPeerId,
+
+    /// The [ConsensusEnclave::self_client_id()] method.
+    ///
+    /// Retrieves the responder ID an enclave was initialized with for clients.
+    SelfClientId,
+
+    /// The [ConsensusEnclave::client_connect_info()] method.
+    ///
+    /// Retrieves the responder ID and key exchange public key clients connect to an enclave with.
+    ClientConnectInfo,
+
+    /// The [ConsensusEnclave::session_stats()] method.
+    ///
+    /// Retrieves the number of client and peer sessions held by an enclave.
+    SessionStats,
+
+    /// The [ConsensusEnclave::enclave_status()] method.
+    ///
+    /// Retrieves a consistent snapshot of an enclave's sessions, IAS report and signing height.
+    EnclaveStatus,
+
+    /// The [ConsensusEnclave::last_call_timings()] method.
+    ///
+    /// Retrieves how long the most recent timed operation spent inside the enclave.
+    LastCallTimings,
+
     /// The [ConsensusEnclave::new_ereport()] method.
     ///
     /// Creates a new report for the enclave with the provided target info.
@@ -70,42 +173,154 @@ pub enum EnclaveCall {
     /// * Caches the quote.
     VerifyQuote(Quote, Report),
 
+    /// The [ConsensusEnclave::cancel_ereport()] method.
+    ///
+    /// Discards any pending reports and quotes.
+    CancelEreport,
+
     /// The [ConsensusEnclave::verify_ias_report()] method.
     ///
     /// * Verifies the signed report from IAS matches the previously received quote,
     /// * Caches the signed report. This cached report may be overwritten by later calls.
     VerifyReport(VerificationReport),
 
+    /// The [ConsensusEnclave::verify_ias_report_at()] method.
+    ///
+    /// Same as [EnclaveCall::VerifyReport], but also rejects reports which are too old at the
+    /// given unix time.
+    VerifyReportAt(VerificationReport, u64),
+
+    /// The [ConsensusEnclave::validate_ias_report()] method.
+    ///
+    /// Checks a signed report from IAS without caching it.
+    ValidateReport(VerificationReport),
+
     /// The [ConsensusEnclave::get_ias_report()] method.
     ///
     /// Retrieves a previously cached report, if any.
     GetReport,
 
+    /// The [ConsensusEnclave::get_ias_report_info()] method.
+    ///
+    /// Retrieves a previously cached report, if any, along with its issue and expiry times.
+    GetReportInfo,
+
+    /// The [ConsensusEnclave::ias_report_blocks_remaining()] method.
+    ///
+    /// Retrieves the number of blocks left before the cached report is due for a refresh.
+    IasReportBlocksRemaining(u64, u64, u64),
+
+    /// The [ConsensusEnclave::ias_report_time()] method.
+    ///
+    /// Retrieves when IAS issued a report.
+    IasReportTime(VerificationReport),
+
+    /// The [ConsensusEnclave::report_body()] method.
+    ///
+    /// Retrieves the body of a report for the enclave, including its measurements and public keys.
+    GetReportBody,
+
+    /// The [ConsensusEnclave::attestation_bundle()] method.
+    ///
+    /// Retrieves the report body, key exchange public key and IAS report of an enclave together.
+    AttestationBundle,
+
     /// The [ConsensusEnclave::client_tx_propose()] method.
     ///
     /// Start a new transaction proposal given the encrypted message from a client.
     ClientTxPropose(EnclaveMessage<ClientSession>),
 
+    /// The [ConsensusEnclave::client_tx_propose_with_receipt()] method.
+    ///
+    /// Same as `ClientTxPropose`, but also returns a signed receipt for the tx.
+    ClientTxProposeWithReceipt(EnclaveMessage<ClientSession>, u64),
+
     /// The [ConsensusEnclave::client_discard_message()] method.
     ///
     /// Decrypts an incoming message and discard the data.
     ClientDiscardMessage(EnclaveMessage<ClientSession>),
 
+    /// The [ConsensusEnclave::client_discard_messages()] method.
+    ///
+    /// Decrypts a batch of incoming messages and discards the data.
+    ClientDiscardMessages(Vec<EnclaveMessage<ClientSession>>),
+
     /// The [ConsensusEnclave::client_tx_propose()] method.
     ///
     /// Start a new transaction proposal given the encrypted message from a peer.
     PeerTxPropose(EnclaveMessage<PeerSession>),
 
+    /// The [ConsensusEnclave::peer_tx_propose_dedup()] method.
+    ///
+    /// Same as `PeerTxPropose`, but skips re-encrypting txs with the given hashes.
+    PeerTxProposeDedup(EnclaveMessage<PeerSession>, Vec<TxHash>),
+
+    /// The [ConsensusEnclave::peer_tx_propose_chunk()] method.
+    ///
+    /// Same as `PeerTxProposeDedup`, but also returns the message's chunk position.
+    PeerTxProposeChunk(EnclaveMessage<PeerSession>, Vec<TxHash>),
+
     /// The [ConsensusEnclave::tx_is_well_formed()] method.
     ///
     /// Provide the missing proofs required to check if a given sealed transaction is well-formed.
     TxIsWellFormed(LocallyEncryptedTx, u64, Vec<TxOutMembershipProof>),
 
+    /// The [ConsensusEnclave::txs_are_well_formed()] method.
+    ///
+    /// Check a batch of sealed transactions for well-formedness in a single call.
+    TxsAreWellFormed(Vec<(LocallyEncryptedTx, u64, Vec<TxOutMembershipProof>)>),
+
+    /// The [ConsensusEnclave::tx_is_well_formed_unspent()] method.
+    ///
+    /// Check a sealed transaction for well-formedness, rejecting it if it spends a key image
+    /// known to be spent.
+    TxIsWellFormedUnspent(LocallyEncryptedTx, u64, Vec<TxOutMembershipProof>, Vec<KeyImage>),
+
+    /// The [ConsensusEnclave::tx_ring_signature_is_valid()] method.
+    ///
+    /// Check the ring signature of a sealed transaction, without any membership proofs.
+    TxRingSignatureIsValid(LocallyEncryptedTx),
+
+    /// The [ConsensusEnclave::integrity_check()] method.
+    ///
+    /// Check that a sealed transaction has not been corrupted, without validating it.
+    IntegrityCheck(LocallyEncryptedTx),
+
+    /// The [ConsensusEnclave::verify_tx_hash()] method.
+    ///
+    /// Check that a hash is the hash of a sealed transaction.
+    VerifyTxHash(LocallyEncryptedTx, TxHash),
+
+    /// The [ConsensusEnclave::tx_fee_preview()] method.
+    ///
+    /// Retrieves the fee of a sealed transaction without checking it is well-formed.
+    TxFeePreview(LocallyEncryptedTx),
+
+    /// The [ConsensusEnclave::get_minimum_fee()] method.
+    ///
+    /// Retrieves the minimum fee enforced by the enclave at the given block index.
+    GetMinimumFee(u64),
+
+    /// The [ConsensusEnclave::get_minimum_fees()] method.
+    ///
+    /// Retrieves the minimum fee enforced by the enclave for each fee token.
+    GetMinimumFees,
+
+    /// The [ConsensusEnclave::fee_policy_digest()] method.
+    ///
+    /// Retrieves a digest of the fee schedule enforced by the enclave.
+    FeePolicyDigest,
+
     /// The [ConsensusEnclave::txs_for_peer()] method.
     ///
     /// Re-encrypt the given transactions for transmission to a peer.
     TxsForPeer(Vec<WellFormedEncryptedTx>, Vec<u8>, PeerSession),
 
+    /// The [ConsensusEnclave::txs_for_peer_chunked()] method.
+    ///
+    /// Re-encrypt the given transactions for a peer, split across size-limited messages.
+    TxsForPeerChunked(Vec<WellFormedEncryptedTx>, Vec<u8>, PeerSession, usize),
+
     /// The [ConsensusEnclave::form_block()] method.
     ///
     /// Converts a list of well-formed, encrypted txs + proofs into a block, a set of redacted transactions
@@ -114,4 +329,195 @@ pub enum EnclaveCall {
         Block,
         Vec<(WellFormedEncryptedTx, Vec<TxOutMembershipProof>)>,
     ),
+
+    /// The [ConsensusEnclave::form_block_versioned()] method.
+    ///
+    /// Same as `FormBlock`, but produces a block at the requested block version.
+    FormBlockVersioned(
+        Block,
+        Vec<(WellFormedEncryptedTx, Vec<TxOutMembershipProof>)>,
+        u32,
+    ),
+
+    /// The [ConsensusEnclave::block_version_range()] method.
+    ///
+    /// Retrieves the range of block versions the enclave can produce.
+    BlockVersionRange,
+
+    /// The [ConsensusEnclave::form_block_with_report()] method.
+    ///
+    /// Same as `FormBlock`, but also reports which transactions were included in the block.
+    FormBlockWithReport(
+        Block,
+        Vec<(WellFormedEncryptedTx, Vec<TxOutMembershipProof>)>,
+    ),
+
+    /// The [ConsensusEnclave::form_block_excluding()] method.
+    ///
+    /// Same as `FormBlock`, but leaves out the transactions with the given hashes.
+    FormBlockExcluding(
+        Block,
+        Vec<(WellFormedEncryptedTx, Vec<TxOutMembershipProof>)>,
+        Vec<TxHash>,
+    ),
+
+    /// The [ConsensusEnclave::form_empty_block()] method.
+    ///
+    /// Forms and signs a block with no transactions on top of the given parent, at the root of
+    /// the given proof.
+    FormEmptyBlock(Block, TxOutMembershipProof),
+
+    /// The [ConsensusEnclave::estimate_form_block_cost()] method.
+    ///
+    /// Estimates the memory needed to form a block from the given candidates.
+    EstimateFormBlockCost(Vec<(WellFormedEncryptedTx, Vec<TxOutMembershipProof>)>),
+
+    /// The [ConsensusEnclave::redact_single()] method.
+    ///
+    /// Redacts one well-formed transaction as block formation would.
+    RedactSingle(WellFormedEncryptedTx),
+
+    /// The [ConsensusEnclave::context_of()] method.
+    ///
+    /// Recovers the context of one well-formed transaction.
+    ContextOf(WellFormedEncryptedTx),
+
+    /// The [ConsensusEnclave::block_total_fees()] method.
+    ///
+    /// Recovers the aggregate fee minted into a formed block.
+    BlockTotalFees(Block, Vec<RedactedTx>),
+
+    /// The [ConsensusEnclave::set_block_limits()] method.
+    ///
+    /// Sets the limits on the contents of blocks formed from now on.
+    SetBlockLimits(BlockLimits),
+
+    /// The [ConsensusEnclave::set_max_proposal_bytes()] method.
+    ///
+    /// Sets the size of the largest client message accepted from now on.
+    SetMaxProposalBytes(usize),
+
+    /// The [ConsensusEnclave::set_tombstone_window()] method.
+    ///
+    /// Sets the tombstone blocks admitted by the well-formed check from now on.
+    SetTombstoneWindow(TombstoneWindow),
+
+    /// The [ConsensusEnclave::current_signing_height()] method.
+    ///
+    /// Retrieves the index of the last block signed by the enclave.
+    CurrentSigningHeight,
+
+    /// The [ConsensusEnclave::included_in_block()] method.
+    ///
+    /// Looks up which recently formed block included a transaction.
+    IncludedInBlock(TxHash),
+
+    /// The [ConsensusEnclave::recent_key_images()] method.
+    ///
+    /// Retrieves the key images spent by recently formed blocks.
+    RecentKeyImages(u64),
+
+    /// The [ConsensusEnclave::verify_own_block_signature()] method.
+    ///
+    /// Checks a block signature against the enclave's block signing key.
+    VerifyOwnBlockSignature(Block, BlockSignature),
+
+    /// The [ConsensusEnclave::verify_own_block_signatures()] method.
+    ///
+    /// Checks a batch of block signatures against the enclave's block signing key.
+    VerifyOwnBlockSignatures(Vec<(Block, BlockSignature)>),
+
+    /// The [ConsensusEnclave::is_trusted_signer()] method.
+    ///
+    /// Checks a key against the current and recently retired block signing keys.
+    IsTrustedSigner(Ed25519Public),
+}
+
+/// The length of the little-endian `u32` which prefixes each framed `EnclaveCall`.
+const FRAME_LEN_PREFIX_LEN: usize = 4;
+
+impl EnclaveCall {
+    /// Serialize this call, prefixed with its length as a little-endian `u32`, so that several
+    /// calls can be packed one after another into a single buffer.
+    pub fn to_framed_bytes(&self) -> Vec<u8> {
+        let payload =
+            mcserial::serialize(self).expect("serializing to an unbounded buffer should not fail");
+        let mut framed = Vec::with_capacity(FRAME_LEN_PREFIX_LEN + payload.len());
+        framed.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        framed.extend_from_slice(&payload);
+        framed
+    }
+
+    /// Read the framed call at the start of `buf`, as written by `to_framed_bytes`. Returns the
+    /// call and the number of bytes it took up, i.e. the offset of the next frame.
+    ///
+    /// Fails with `Error::Serialization` if `buf` is too short to hold the frame, or its payload
+    /// is not a valid `EnclaveCall`.
+    pub fn from_framed_bytes(buf: &[u8]) -> Result<(Self, usize)> {
+        if buf.len() < FRAME_LEN_PREFIX_LEN {
+            return Err(Error::Serialization);
+        }
+        let (prefix, rest) = buf.split_at(FRAME_LEN_PREFIX_LEN);
+        let mut len_bytes = [0u8; FRAME_LEN_PREFIX_LEN];
+        len_bytes.copy_from_slice(prefix);
+        let len = u32::from_le_bytes(len_bytes) as usize;
+
+        let payload = rest.get(..len).ok_or(Error::Serialization)?;
+        let call = mcserial::deserialize(payload)?;
+        Ok((call, FRAME_LEN_PREFIX_LEN + len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    // Several framed calls packed into one buffer read back in order.
+    fn test_framed_bytes_round_trip() {
+        let calls = vec![
+            EnclaveCall::EnclaveVersion,
+            EnclaveCall::SetBlockLimits(BlockLimits::default()),
+            EnclaveCall::SealBlob(vec![1, 2, 3]),
+            EnclaveCall::GetMinimumFee(7),
+        ];
+
+        let mut buf = Vec::new();
+        for call in &calls {
+            buf.extend_from_slice(&call.to_framed_bytes());
+        }
+
+        let mut offset = 0;
+        let mut decoded = Vec::new();
+        while offset < buf.len() {
+            let (call, len) = EnclaveCall::from_framed_bytes(&buf[offset..]).unwrap();
+            decoded.push(call);
+            offset += len;
+        }
+        assert_eq!(offset, buf.len());
+        assert_eq!(decoded, calls);
+    }
+
+    #[test]
+    // A buffer cut short anywhere fails cleanly instead of panicking.
+    fn test_from_framed_bytes_truncated() {
+        let framed = EnclaveCall::SealBlob(vec![9; 32]).to_framed_bytes();
+        for len in 0..framed.len() {
+            assert_eq!(
+                EnclaveCall::from_framed_bytes(&framed[..len]),
+                Err(Error::Serialization)
+            );
+        }
+
+        // A corrupt payload of the right length is rejected too.
+        let mut corrupt = framed.clone();
+        for byte in corrupt[FRAME_LEN_PREFIX_LEN..].iter_mut() {
+            *byte = 0xff;
+        }
+        assert_eq!(
+            EnclaveCall::from_framed_bytes(&corrupt),
+            Err(Error::Serialization)
+        );
+    }
 }
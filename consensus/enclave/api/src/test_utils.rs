@@ -0,0 +1,100 @@
+// Copyright (c) 2018-2020 MobileCoin Inc.
+
+//! Helpers for fabricating enclave API types in untrusted tests.
+
+use crate::{LocallyEncryptedTx, TxContext};
+use alloc::{vec, vec::Vec};
+use digest::Input;
+use rand_core::{CryptoRng, RngCore};
+use transaction::{
+    blake2b_256::Blake2b256, constants::MIN_RING_SIZE, ring_signature::KeyImage, tx::TxHash,
+};
+
+/// Builds random `TxContext`s whose fields agree with each other: one key image per input, one
+/// highest index per ring member of each input, and a tx hash derived from the encrypted tx.
+#[derive(Clone, Debug)]
+pub struct TxContextBuilder {
+    num_inputs: usize,
+    ring_size: usize,
+    max_index: u64,
+    encrypted_len: usize,
+    tombstone_block: u64,
+}
+
+impl Default for TxContextBuilder {
+    fn default() -> Self {
+        Self {
+            num_inputs: 1,
+            ring_size: MIN_RING_SIZE,
+            max_index: 1000,
+            encrypted_len: 256,
+            tombstone_block: u64::max_value(),
+        }
+    }
+}
+
+impl TxContextBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the number of inputs, i.e. key images.
+    pub fn num_inputs(mut self, num_inputs: usize) -> Self {
+        self.num_inputs = num_inputs;
+        self
+    }
+
+    /// Set the number of ring members, i.e. highest indices, per input.
+    pub fn ring_size(mut self, ring_size: usize) -> Self {
+        self.ring_size = ring_size;
+        self
+    }
+
+    /// Set the largest highest index which may be generated.
+    pub fn max_index(mut self, max_index: u64) -> Self {
+        self.max_index = max_index;
+        self
+    }
+
+    /// Set the length of the random locally encrypted tx.
+    pub fn encrypted_len(mut self, encrypted_len: usize) -> Self {
+        self.encrypted_len = encrypted_len;
+        self
+    }
+
+    /// Set the tombstone block of the generated contexts.
+    pub fn tombstone_block(mut self, tombstone_block: u64) -> Self {
+        self.tombstone_block = tombstone_block;
+        self
+    }
+
+    /// Build a new random `TxContext`.
+    pub fn build<R: RngCore + CryptoRng>(&self, rng: &mut R) -> TxContext {
+        let mut encrypted_tx = vec![0u8; self.encrypted_len];
+        rng.fill_bytes(&mut encrypted_tx);
+
+        let tx_hash = {
+            let mut hasher = Blake2b256::new();
+            hasher.input(&encrypted_tx);
+            let mut hash = [0u8; 32];
+            hash.copy_from_slice(hasher.result().as_slice());
+            TxHash(hash)
+        };
+
+        let key_images: Vec<KeyImage> = (0..self.num_inputs)
+            .map(|_| KeyImage::from(rng.next_u64()))
+            .collect();
+
+        let highest_indices: Vec<u64> = (0..self.num_inputs * self.ring_size)
+            .map(|_| rng.next_u64() % (self.max_index + 1))
+            .collect();
+
+        TxContext::new(
+            LocallyEncryptedTx(encrypted_tx),
+            tx_hash,
+            highest_indices,
+            key_images,
+            self.tombstone_block,
+        )
+    }
+}
@@ -15,23 +15,43 @@ extern crate alloc;
 mod identity;
 
 use ake_enclave::AkeEnclaveState;
-use alloc::{collections::BTreeSet, format, vec::Vec};
-use attest::{IasNonce, IntelSealed, Quote, QuoteNonce, Report, TargetInfo, VerificationReport};
+use alloc::{
+    collections::{BTreeSet, VecDeque},
+    format, vec,
+    vec::Vec,
+};
+use attest::{
+    IasNonce, IntelSealed, Measurement, NonceError, Quote, QuoteError, QuoteNonce, Report,
+    ReportBody, TargetInfo, VerificationReport, VerificationReportData, VerifyError,
+};
 use attest_enclave_api::{
     ClientAuthRequest, ClientAuthResponse, ClientSession, EnclaveMessage,
     Error as AttestEnclaveError, PeerAuthRequest, PeerAuthResponse, PeerSession,
 };
-use attest_trusted::SealAlgo;
+use attest_trusted::{IntelSealingError, SealAlgo};
 use common::ResponderId;
 use consensus_enclave_api::{
-    ConsensusEnclave, Error, LocallyEncryptedTx, Result, SealedBlockSigningKey, TxContext,
-    WellFormedEncryptedTx, WellFormedTxContext,
+    digest_fee_policy, ias_report_blocks_remaining_at, ias_report_issued_at, signer_fingerprint,
+    signing_key_migration_message, tx_priority, AttestationBundle, BlockLimits, CallTimings,
+    ConsensusEnclave, EnclaveCapabilities, EnclaveStatus, EnclaveVersion, Error, ExclusionReason,
+    IasReportInfo, InclusionStatus, LocallyEncryptedTx, PeerTxChunk, ProposeReceipt, Result,
+    SealedBlockSigningKey, SessionRef, SessionStats, TimedOperation, TombstoneWindow, TxContext,
+    WellFormedEncryptedTx, WellFormedError, WellFormedTxContext, CONSUMED_IAS_NONCE_HISTORY,
+    DEFAULT_MAX_PROPOSAL_BYTES, DEFAULT_TOKEN_ID, IAS_REPORT_MAX_AGE_SECS, INCLUSION_HISTORY_BLOCKS,
+    RETIRED_SIGNER_HISTORY, WELL_FORMED_TX_FORMAT_VERSION,
+};
+use core::{
+    cmp::Reverse,
+    convert::{TryFrom, TryInto},
+    mem::size_of,
 };
-use core::convert::{TryFrom, TryInto};
 use digest::Input;
 use digestible::Digestible;
 use identity::Ed25519Identity;
-use keys::{Ed25519Pair, Ed25519Public, RistrettoPrivate, RistrettoPublic, X25519Public};
+use keys::{
+    Ed25519Pair, Ed25519Public, FromRandom, RistrettoPrivate, RistrettoPublic, Signer,
+    X25519Public,
+};
 use mcrand::McRng;
 use message_cipher::{AesMessageCipher, MessageCipher};
 use prost::Message;
@@ -41,16 +61,20 @@ use transaction::{
     account_keys::PublicAddress,
     amount::Amount,
     blake2b_256::Blake2b256,
-    constants::{FEE_SPEND_PUBLIC_KEY, FEE_VIEW_PUBLIC_KEY},
+    constants::{BASE_FEE, FEE_SPEND_PUBLIC_KEY, FEE_VIEW_PRIVATE_KEY, FEE_VIEW_PUBLIC_KEY},
     onetime_keys::{compute_shared_secret, compute_tx_pubkey, create_onetime_public_key},
     ring_signature::{Blinding, KeyImage, Scalar},
-    tx::{Tx, TxOut, TxOutMembershipProof},
-    Block, BlockSignature, RedactedTx, BLOCK_VERSION,
+    tx::{Tx, TxHash, TxOut, TxOutMembershipElement, TxOutMembershipProof},
+    validation::TransactionValidationError,
+    hash_block_contents, Block, BlockSignature, RedactedTx, BLOCK_VERSION,
 };
 
 /// The prefix used when constructing the fees output blinding.
 const BLINDING_HASH_PREFIX: &[u8] = b"BLINDING_1";
 
+/// Domain separator for the fee output private key of blocks formed by `form_empty_block`.
+const EMPTY_BLOCK_FEE_KEY_PREFIX: &[u8] = b"EMPTY_BLOCK_FEE_KEY_1";
+
 /// A well-formed transaction.
 #[derive(Clone, Eq, PartialEq, Message)]
 pub struct WellFormedTx {
@@ -82,13 +106,29 @@ impl Into<RedactedTx> for WellFormedTx {
 /// We need to define this since that's the only way to get Prost to serialize a
 /// list of transactions. Prost is used for the sake of uniformity - all other data inside
 /// `consensus_enclave_impl` is also serialized using it.
+/// Transactions are kept as individually-encoded bytes so that `peer_tx_propose` can decode each
+/// one separately. On the wire this is identical to a repeated `Tx` message field.
 #[derive(Message)]
 pub struct TxList {
-    /// Transactions.
-    #[prost(message, repeated, tag = "1")]
-    pub txs: Vec<Tx>,
+    /// Prost-encoded transactions.
+    #[prost(bytes, repeated, tag = "1")]
+    pub txs: Vec<Vec<u8>>,
+
+    /// The index of this list among the chunks produced by `txs_for_peer_chunked`.
+    #[prost(fixed32, tag = "2")]
+    pub chunk_index: u32,
+
+    /// The total number of chunks the transactions were split into.
+    #[prost(fixed32, tag = "3")]
+    pub chunk_total: u32,
 }
 
+/// The length of the authentication tag added to every encrypted peer message.
+const PEER_MESSAGE_TAG_LEN: usize = 16;
+
+/// The most bytes `TxList::chunk_index` and `TxList::chunk_total` can take up when encoded.
+const TX_LIST_CHUNK_HEADER_LEN: usize = 10;
+
 /// Internal state of the enclave, including AKE and attestation related as well as any business logic state
 pub struct SgxConsensusEnclave {
     /// All AKE and attestation related state including responder ids, established channels for peers and clients,
@@ -100,6 +140,92 @@ pub struct SgxConsensusEnclave {
 
     /// Cipher used to encrypt well-formed-encrypted transactions.
     well_formed_encrypted_tx_cipher: Mutex<AesMessageCipher>,
+
+    /// The largest client message, in bytes, accepted by `client_tx_propose`.
+    max_proposal_bytes: Mutex<usize>,
+
+    /// The limits enforced by `form_block` on the blocks it forms.
+    block_limits: Mutex<BlockLimits>,
+
+    /// The tombstone blocks `tx_is_well_formed` admits, or `None` if no window was set.
+    tombstone_window: Mutex<Option<TombstoneWindow>>,
+
+    /// The arguments of the first successful `enclave_init` call, and its result.
+    init_args: Mutex<Option<InitArgs>>,
+
+    /// The index of the last block signed by `form_block`, or 0 if none was signed yet.
+    signing_height: Mutex<u64>,
+
+    /// The last `INCLUSION_HISTORY_BLOCKS` blocks formed, oldest first.
+    recent_inclusions: Mutex<VecDeque<RecentBlock>>,
+
+    /// The enclave measurements peers may be pinned to, or `None` if any attested peer is
+    /// accepted.
+    peer_identity_policy: Mutex<Option<Vec<Measurement>>>,
+
+    /// The public keys replaced by the last `RETIRED_SIGNER_HISTORY` calls to
+    /// `rotate_signing_key`, oldest first.
+    retired_signers: Mutex<VecDeque<Ed25519Public>>,
+
+    /// The nonces of the last `CONSUMED_IAS_NONCE_HISTORY` IAS reports verified, oldest first.
+    consumed_ias_nonces: Mutex<VecDeque<IasNonce>>,
+
+    /// The timings of the most recent timed operation, or `None` if there was none yet.
+    #[cfg(feature = "profiling")]
+    last_call_timings: Mutex<Option<CallTimings>>,
+}
+
+/// Records the timings of an operation in its enclave's `last_call_timings` when dropped, so that
+/// early returns are timed too.
+#[cfg(feature = "profiling")]
+struct CallTimer<'a> {
+    last_call_timings: &'a Mutex<Option<CallTimings>>,
+    operation: TimedOperation,
+    start_cycles: u64,
+}
+
+#[cfg(feature = "profiling")]
+impl Drop for CallTimer<'_> {
+    fn drop(&mut self) {
+        let timings = CallTimings {
+            operation: self.operation,
+            start_cycles: self.start_cycles,
+            end_cycles: read_cycle_counter(),
+        };
+        // A poisoned lock only loses the timings, which are not worth panicking over.
+        if let Ok(mut last_call_timings) = self.last_call_timings.lock() {
+            *last_call_timings = Some(timings);
+        }
+    }
+}
+
+/// Stands in for the profiling `CallTimer`, and records nothing.
+#[cfg(not(feature = "profiling"))]
+struct CallTimer;
+
+/// Read the CPU's timestamp counter.
+#[cfg(feature = "profiling")]
+fn read_cycle_counter() -> u64 {
+    // Safety: RDTSC has no side effects. It faults in SGX1 hardware enclaves, which is why only
+    // profiling builds use it.
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+/// What `included_in_block`, `recent_key_images` and `tx_is_well_formed` remember about a formed
+/// block.
+struct RecentBlock {
+    index: u64,
+    tx_hashes: Vec<TxHash>,
+    key_images: Vec<KeyImage>,
+    root_element: TxOutMembershipElement,
+}
+
+/// The arguments `enclave_init` was called with, and the sealed key it returned.
+struct InitArgs {
+    peer_self_id: ResponderId,
+    client_self_id: ResponderId,
+    sealed_key: Option<SealedBlockSigningKey>,
+    result: SealedBlockSigningKey,
 }
 
 impl core::default::Default for SgxConsensusEnclave {
@@ -110,28 +236,256 @@ impl core::default::Default for SgxConsensusEnclave {
             well_formed_encrypted_tx_cipher: Mutex::new(AesMessageCipher::new(
                 &mut McRng::default(),
             )),
+            max_proposal_bytes: Mutex::new(DEFAULT_MAX_PROPOSAL_BYTES),
+            block_limits: Mutex::new(BlockLimits::default()),
+            tombstone_window: Mutex::new(None),
+            init_args: Mutex::new(None),
+            signing_height: Mutex::new(0),
+            recent_inclusions: Mutex::new(VecDeque::new()),
+            peer_identity_policy: Mutex::new(None),
+            retired_signers: Mutex::new(VecDeque::new()),
+            consumed_ias_nonces: Mutex::new(VecDeque::new()),
+            #[cfg(feature = "profiling")]
+            last_call_timings: Mutex::new(None),
         }
     }
 }
 
 impl SgxConsensusEnclave {
+    /// Make the quote nonces returned by `new_ereport` from here on deterministic, derived from
+    /// `seed`, so tests can reproduce attestation flows. This is compiled out of production
+    /// builds, and is deliberately not part of `ConsensusEnclave`, so it can not be reached
+    /// through an ECALL.
+    #[cfg(any(test, feature = "test_utils"))]
+    pub fn seed_test_rng(&self, seed: [u8; 32]) -> Result<()> {
+        Ok(self.ake.seed_test_rng(seed)?)
+    }
+
+    /// Fails with `PeerIdentityNotAllowed` unless `peer_measurement`, the measurement a peer is
+    /// pinned to, is allowed by the peer identity policy.
+    fn check_peer_identity(&self, peer_measurement: &Measurement) -> Result<()> {
+        if let Some(allowed) = self.peer_identity_policy.lock()?.as_ref() {
+            if !allowed.contains(peer_measurement) {
+                return Err(Error::PeerIdentityNotAllowed);
+            }
+        }
+        Ok(())
+    }
+
+    /// The measurement inbound peers must present: our own MRENCLAVE.
+    fn own_measurement(&self) -> Result<Measurement> {
+        Ok(Measurement::from(self.ake.report_body()?.mr_enclave()))
+    }
+
+    /// Start timing `operation`, which is recorded in `last_call_timings` when the returned timer
+    /// is dropped.
+    #[cfg(feature = "profiling")]
+    fn call_timer(&self, operation: TimedOperation) -> CallTimer<'_> {
+        CallTimer {
+            last_call_timings: &self.last_call_timings,
+            operation,
+            start_cycles: read_cycle_counter(),
+        }
+    }
+
+    #[cfg(not(feature = "profiling"))]
+    fn call_timer(&self, _operation: TimedOperation) -> CallTimer {
+        CallTimer
+    }
+
+    /// Run `verify` on `ias_report` and consume the nonce it answers, failing with
+    /// `NonceAlreadyConsumed` if a report answering that nonce was verified before. The consumed
+    /// nonces stay locked from the check to the insert, so two concurrent calls with the same
+    /// report can not both succeed. A report whose body can not be parsed, or which answers no
+    /// nonce, is rejected before `verify` runs.
+    fn verify_unconsumed_ias_report(
+        &self,
+        ias_report: VerificationReport,
+        verify: impl FnOnce(VerificationReport) -> Result<()>,
+    ) -> Result<()> {
+        let nonce = VerificationReportData::try_from(&ias_report)?
+            .nonce
+            .ok_or(NonceError::Missing)?;
+        let mut consumed_ias_nonces = self.consumed_ias_nonces.lock()?;
+        if consumed_ias_nonces.contains(&nonce) {
+            return Err(Error::NonceAlreadyConsumed);
+        }
+        verify(ias_report)?;
+        consumed_ias_nonces.push_back(nonce);
+        if consumed_ias_nonces.len() > CONSUMED_IAS_NONCE_HISTORY {
+            consumed_ias_nonces.pop_front();
+        }
+        Ok(())
+    }
+
+    /// Seal the block signing key in `old_sealed` for the enclave whose report body is
+    /// `target_body`, as `export_signing_key` does once it has verified the target.
+    fn seal_signing_key_for(
+        &self,
+        old_sealed: &SealedBlockSigningKey,
+        target_body: &ReportBody,
+    ) -> Result<Vec<u8>> {
+        // Only this enclave can unseal the key, so exporting it is what authorizes the migration.
+        let key = unseal_tagged(old_sealed, SIGNING_KEY_SEAL_TAG)?;
+
+        // The target can not read our sealing key, so seal to the signer the two of us share.
+        let message = signing_key_migration_message(&target_body.mr_enclave(), old_sealed);
+        seal_tagged_to_signer(&key, &message)
+    }
+
+    /// Install `keypair` as the block signing key, keeping the one it replaces as a retired
+    /// signer. `sealed` is the new key sealed under the current enclave identity.
+    fn replace_signing_key(
+        &self,
+        keypair: Ed25519Pair,
+        sealed: &SealedBlockSigningKey,
+    ) -> Result<()> {
+        // Lock in the same order as enclave_init, and keep a repeated init call from handing
+        // back the key that was just replaced.
+        let mut init_args = self.init_args.lock()?;
+        let mut lock = self.ake.get_identity().signing_keypair.lock()?;
+        let mut retired_signers = self.retired_signers.lock()?;
+        retired_signers.push_back(lock.public_key());
+        if retired_signers.len() > RETIRED_SIGNER_HISTORY {
+            retired_signers.pop_front();
+        }
+        *lock = keypair;
+        if let Some(args) = init_args.as_mut() {
+            args.result = sealed.clone();
+        }
+        Ok(())
+    }
+
+    /// Fails with `NotInitialized` unless `enclave_init` has succeeded. Until then the block
+    /// signing key is a random placeholder, so nothing signed with it could be trusted.
+    fn check_initialized(&self) -> Result<()> {
+        if self.init_args.lock()?.is_none() {
+            return Err(Error::NotInitialized);
+        }
+        Ok(())
+    }
+
+    /// Sign a receipt acknowledging that `tx_hash` was received at `block_index`.
+    fn sign_propose_receipt(&self, tx_hash: TxHash, block_index: u64) -> Result<ProposeReceipt> {
+        self.check_initialized()?;
+
+        let message = ProposeReceipt::message(&tx_hash, block_index);
+        let signature = self
+            .ake
+            .get_identity()
+            .signing_keypair
+            .lock()?
+            .try_sign(&message)?;
+
+        Ok(ProposeReceipt {
+            tx_hash,
+            block_index,
+            signature,
+        })
+    }
+
+    /// Locally encrypt a decoded proposed tx, and gather what untrusted needs to know about it.
+    fn tx_context<R: RngCore + CryptoRng>(
+        &self,
+        tx: &Tx,
+        tx_bytes: Vec<u8>,
+        rng: &mut R,
+    ) -> Result<TxContext> {
+        let locally_encrypted_tx = LocallyEncryptedTx(
+            self.locally_encrypted_tx_cipher
+                .lock()?
+                .encrypt_bytes(rng, tx_bytes),
+        );
+
+        Ok(TxContext::new(
+            locally_encrypted_tx,
+            tx.tx_hash(),
+            tx.get_membership_proof_highest_indices(),
+            tx.key_images(),
+            tx.prefix.tombstone_block,
+        ))
+    }
+
+    /// Convert the Prost-encoded txs relayed by a peer to TxContexts. Each tx is decoded on its own
+    /// so that a corrupt entry only fails itself, and txs whose hashes are in `known_hashes` fail
+    /// with `Error::KnownTx` without being re-encrypted.
+    fn peer_tx_contexts(
+        &self,
+        txs: Vec<Vec<u8>>,
+        known_hashes: &[TxHash],
+    ) -> Vec<Result<TxContext>> {
+        let mut rng = McRng::default();
+        txs.into_iter()
+            .map(|tx_bytes| {
+                let tx = mcserial::decode::<Tx>(&tx_bytes)?;
+                let tx_hash = tx.tx_hash();
+                if known_hashes.contains(&tx_hash) {
+                    return Err(Error::KnownTx(tx_hash));
+                }
+                self.tx_context(&tx, tx_bytes, &mut rng)
+            })
+            .collect()
+    }
+
+    /// Decode the decrypted payload of a message from `txs_for_peer` or `txs_for_peer_chunked`.
+    fn peer_tx_chunk(&self, data: &[u8], known_hashes: &[TxHash]) -> Result<PeerTxChunk> {
+        // Use prost
+        let tx_list = mcserial::decode::<TxList>(data)?;
+        Ok(PeerTxChunk {
+            chunk_index: tx_list.chunk_index,
+            // Enclaves predating chunking leave the total unset, and send each batch whole.
+            chunk_total: core::cmp::max(tx_list.chunk_total, 1),
+            tx_contexts: self.peer_tx_contexts(tx_list.txs, known_hashes),
+        })
+    }
+
     fn encrypt_well_formed_tx<R: RngCore + CryptoRng>(
         &self,
         well_formed_tx: &WellFormedTx,
         rng: &mut R,
     ) -> Result<WellFormedEncryptedTx> {
         let well_formed_tx_bytes = mcserial::encode(well_formed_tx);
+        let ciphertext = self
+            .well_formed_encrypted_tx_cipher
+            .lock()?
+            .encrypt_bytes(rng, well_formed_tx_bytes);
+
+        // The layout version goes in front, outside the ciphertext, so that it can be checked
+        // before decrypting.
+        let mut encrypted = Vec::with_capacity(1 + ciphertext.len());
+        encrypted.push(WELL_FORMED_TX_FORMAT_VERSION);
+        encrypted.extend_from_slice(&ciphertext);
+        Ok(WellFormedEncryptedTx(encrypted))
+    }
 
-        Ok(WellFormedEncryptedTx(
-            self.well_formed_encrypted_tx_cipher
-                .lock()?
-                .encrypt_bytes(rng, well_formed_tx_bytes),
-        ))
+    /// Decrypt well-formed-encrypted transactions and Prost-encode them for inclusion in a
+    /// `TxList`.
+    fn encode_well_formed_txs(
+        &self,
+        encrypted_txs: &[WellFormedEncryptedTx],
+    ) -> Result<Vec<Vec<u8>>> {
+        encrypted_txs
+            .iter()
+            .map(|encrypted_tx| {
+                let well_formed_tx = self.decrypt_well_formed_tx(encrypted_tx)?;
+                Ok(mcserial::encode(well_formed_tx.tx()))
+            })
+            .collect()
+    }
+
+    fn decrypt_locally_encrypted_tx(&self, encrypted: &LocallyEncryptedTx) -> Result<Tx> {
+        let mut cipher = self.locally_encrypted_tx_cipher.lock()?;
+        let plaintext = cipher.decrypt_bytes(encrypted.0.clone())?;
+        let tx: Tx = mcserial::decode(&plaintext)?;
+        Ok(tx)
     }
 
     fn decrypt_well_formed_tx(&self, encrypted: &WellFormedEncryptedTx) -> Result<WellFormedTx> {
+        if encrypted.format_version() != WELL_FORMED_TX_FORMAT_VERSION {
+            return Err(Error::UnsupportedTxFormat(encrypted.format_version()));
+        }
         let mut cipher = self.well_formed_encrypted_tx_cipher.lock()?;
-        let plaintext = cipher.decrypt_bytes(encrypted.0.clone())?;
+        let plaintext = cipher.decrypt_bytes(encrypted.0[1..].to_vec())?;
         let well_formed_tx: WellFormedTx = mcserial::decode(&plaintext)?;
         Ok(well_formed_tx)
     }
@@ -144,15 +498,37 @@ impl ConsensusEnclave for SgxConsensusEnclave {
         client_self_id: &ResponderId,
         sealed_key: &Option<SealedBlockSigningKey>,
     ) -> Result<SealedBlockSigningKey> {
+        validate_responder_id(peer_self_id)?;
+        validate_responder_id(client_self_id)?;
+
+        // Holding this lock for the whole call serializes racing initializations.
+        let mut init_args = self.init_args.lock()?;
+        if let Some(previous) = init_args.as_ref() {
+            // Repeating the original call is harmless, anything else would clobber the state
+            // set up by it.
+            return if &previous.peer_self_id == peer_self_id
+                && &previous.client_self_id == client_self_id
+                && &previous.sealed_key == sealed_key
+            {
+                Ok(previous.result.clone())
+            } else {
+                Err(Error::AlreadyInitialized)
+            };
+        }
+
+        // Unseal before initializing anything, so a bad sealed key leaves the enclave untouched.
+        let unsealed_key = sealed_key
+            .as_ref()
+            .map(|sealed| unseal_tagged(sealed, SIGNING_KEY_SEAL_TAG))
+            .transpose()?;
+
         self.ake
             .init(peer_self_id.clone(), client_self_id.clone())?;
 
-        // if we were passed a sealed key, unseal it and overwrite the private key
+        // if we were passed a sealed key, overwrite the private key with it
 
-        match sealed_key {
-            Some(sealed) => {
-                let cached = IntelSealed::try_from(sealed.clone()).unwrap();
-                let (key, _mac) = cached.unseal_raw()?;
+        match unsealed_key {
+            Some(key) => {
                 let mut lock = self.ake.get_identity().signing_keypair.lock().unwrap();
                 *lock = Ed25519Pair::try_from(&key[..]).unwrap();
             }
@@ -162,9 +538,153 @@ impl ConsensusEnclave for SgxConsensusEnclave {
         // either way seal the private key and return it
         let lock = self.ake.get_identity().signing_keypair.lock().unwrap();
         let key = (*lock).private_key();
-        let sealed = IntelSealed::seal_raw(key.as_ref(), &[]).unwrap();
+        let sealed = IntelSealed::seal_raw(key.as_ref(), SIGNING_KEY_SEAL_TAG).unwrap();
+        let result = sealed.as_ref().to_vec();
+
+        *init_args = Some(InitArgs {
+            peer_self_id: peer_self_id.clone(),
+            client_self_id: client_self_id.clone(),
+            sealed_key: sealed_key.clone(),
+            result: result.clone(),
+        });
+
+        Ok(result)
+    }
+
+    fn export_signing_key(
+        &self,
+        old_sealed: &SealedBlockSigningKey,
+        target: &AttestationBundle,
+    ) -> Result<Vec<u8>> {
+        // Only hand the key to an enclave IAS vouches for: the report body naming the target's
+        // MRENCLAVE must be the one in the quote IAS verified.
+        let ias_report = target
+            .ias_report
+            .as_ref()
+            .ok_or(AttestEnclaveError::NoReportAvailable)?;
+        self.validate_ias_report(ias_report)?;
+        let quote_body = VerificationReportData::try_from(ias_report)?
+            .quote
+            .report_body()
+            .map_err(QuoteError::from)?;
+        if quote_body != target.report_body {
+            return Err(VerifyError::IasQuoteMismatch.into());
+        }
+
+        self.seal_signing_key_for(old_sealed, &target.report_body)
+    }
+
+    fn reseal_signing_key(
+        &self,
+        old_sealed: &SealedBlockSigningKey,
+        migration_blob: &[u8],
+    ) -> Result<SealedBlockSigningKey> {
+        self.check_initialized()?;
+
+        // Accept only a blob exported for this enclave, carrying the key in `old_sealed`.
+        let mr_enclave = self.ake.report_body()?.mr_enclave();
+        let message = signing_key_migration_message(&mr_enclave, old_sealed);
+        let key = unseal_tagged(migration_blob, &message)?;
+        let keypair = Ed25519Pair::try_from(&key[..]).map_err(|_| Error::Serialization)?;
+
+        // Re-seal under the current enclave identity.
+        let sealed = seal_tagged(keypair.private_key().as_ref(), SIGNING_KEY_SEAL_TAG)?;
+        self.replace_signing_key(keypair, &sealed)?;
 
-        Ok(sealed.as_ref().to_vec())
+        Ok(sealed)
+    }
+
+    fn rotate_signing_key(&self) -> Result<(SealedBlockSigningKey, Ed25519Public)> {
+        self.check_initialized()?;
+
+        let keypair = Ed25519Pair::from_random(&mut McRng::default());
+        let sealed = seal_tagged(keypair.private_key().as_ref(), SIGNING_KEY_SEAL_TAG)?;
+        let public_key = keypair.public_key();
+        self.replace_signing_key(keypair, &sealed)?;
+
+        Ok((sealed, public_key))
+    }
+
+    fn seal_blob(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        seal_tagged(plaintext, BLOB_SEAL_TAG)
+    }
+
+    fn unseal_blob(&self, sealed: &[u8]) -> Result<Vec<u8>> {
+        unseal_tagged(sealed, BLOB_SEAL_TAG)
+    }
+
+    fn enclave_version(&self) -> Result<EnclaveVersion> {
+        // Only profiling builds record the timings `last_call_timings` returns.
+        let profiling = if cfg!(feature = "profiling") {
+            EnclaveCapabilities::CALL_TIMINGS
+        } else {
+            EnclaveCapabilities::empty()
+        };
+
+        // List the new capability here whenever an optional method is added.
+        Ok(EnclaveVersion {
+            major: 0,
+            minor: 1,
+            patch: 0,
+            capabilities: EnclaveCapabilities::BATCH_WELL_FORMED
+                | EnclaveCapabilities::MINIMUM_FEE
+                | EnclaveCapabilities::STRUCTURED_WELL_FORMED_ERRORS
+                | EnclaveCapabilities::PEER_TX_PROPOSE_PARTIAL_FAILURE
+                | EnclaveCapabilities::VERSIONED_BLOCK_FORMATION
+                | EnclaveCapabilities::TX_PRIORITY
+                | EnclaveCapabilities::SIGNING_KEY_MIGRATION
+                | EnclaveCapabilities::SIGNER_FINGERPRINT
+                | EnclaveCapabilities::PROPOSE_RECEIPT
+                | EnclaveCapabilities::CHUNKED_TXS_FOR_PEER
+                | EnclaveCapabilities::PEER_CLOSE_ALL
+                | EnclaveCapabilities::SESSION_STATS
+                | EnclaveCapabilities::IAS_REPORT_FRESHNESS
+                | EnclaveCapabilities::IAS_REPORT_INFO
+                | EnclaveCapabilities::VERIFY_OWN_BLOCK_SIGNATURE
+                | EnclaveCapabilities::CANCEL_EREPORT
+                | EnclaveCapabilities::CLIENT_SESSIONS
+                | EnclaveCapabilities::FORM_BLOCK_WITH_REPORT
+                | EnclaveCapabilities::BLOCK_LIMITS
+                | EnclaveCapabilities::CLIENT_ACCEPT_BUDGET
+                | EnclaveCapabilities::REPORT_BODY
+                | EnclaveCapabilities::RING_SIGNATURE_PREVALIDATION
+                | EnclaveCapabilities::SEALED_BLOBS
+                | EnclaveCapabilities::SIGNING_HEIGHT
+                | EnclaveCapabilities::CLOSE_SESSIONS
+                | EnclaveCapabilities::MINIMUM_FEES
+                | EnclaveCapabilities::PEER_IDENTITY_POLICY
+                | EnclaveCapabilities::TX_HASH_VERIFICATION
+                | EnclaveCapabilities::FORM_BLOCK_COST_ESTIMATE
+                | EnclaveCapabilities::PEER_INIT_PINNED
+                | EnclaveCapabilities::BLOCK_VERSION_RANGE
+                | EnclaveCapabilities::ENCLAVE_STATUS
+                | EnclaveCapabilities::SIGNING_KEY_ROTATION
+                | EnclaveCapabilities::BATCH_CLIENT_DISCARD
+                | EnclaveCapabilities::IAS_REPORT_VALIDATION
+                | EnclaveCapabilities::INCLUSION_LOOKUP
+                | EnclaveCapabilities::FEE_POLICY_DIGEST
+                | EnclaveCapabilities::REDACT_SINGLE
+                | EnclaveCapabilities::IAS_REPORT_BLOCKS_REMAINING
+                | EnclaveCapabilities::SELF_RESPONDER_IDS
+                | EnclaveCapabilities::BLOCK_TOTAL_FEES
+                | EnclaveCapabilities::INTEGRITY_CHECK
+                | EnclaveCapabilities::TOMBSTONE_WINDOW
+                | EnclaveCapabilities::SPENT_KEY_IMAGE_CHECK
+                | EnclaveCapabilities::RECENT_KEY_IMAGES
+                | EnclaveCapabilities::IAS_REPORT_TIME
+                | EnclaveCapabilities::FORM_BLOCK_EXCLUDE
+                | EnclaveCapabilities::PEER_TX_DEDUP
+                | EnclaveCapabilities::TRUSTED_SIGNER_CHECK
+                | EnclaveCapabilities::BATCH_OWN_BLOCK_SIGNATURES
+                | EnclaveCapabilities::TX_FEE_PREVIEW
+                | EnclaveCapabilities::FORM_EMPTY_BLOCK
+                | EnclaveCapabilities::CLIENT_CONNECT_INFO
+                | EnclaveCapabilities::CONTEXT_OF
+                | EnclaveCapabilities::ATTESTATION_BUNDLE
+                | EnclaveCapabilities::MAX_PROPOSAL_BYTES
+                | EnclaveCapabilities::PEER_TX_CHUNK_POSITION
+                | profiling,
+        })
     }
 
     fn get_identity(&self) -> Result<X25519Public> {
@@ -172,9 +692,68 @@ impl ConsensusEnclave for SgxConsensusEnclave {
     }
 
     fn get_signer(&self) -> Result<Ed25519Public> {
+        self.check_initialized()?;
         Ok(self.ake.get_identity().get_public_key())
     }
 
+    fn get_signer_fingerprint(&self) -> Result<[u8; 32]> {
+        self.check_initialized()?;
+        Ok(signer_fingerprint(&self.ake.get_identity().get_public_key()))
+    }
+
+    fn self_peer_id(&self) -> Result<ResponderId> {
+        self.init_args
+            .lock()?
+            .as_ref()
+            .map(|args| args.peer_self_id.clone())
+            .ok_or(Error::NotInitialized)
+    }
+
+    fn self_client_id(&self) -> Result<ResponderId> {
+        self.init_args
+            .lock()?
+            .as_ref()
+            .map(|args| args.client_self_id.clone())
+            .ok_or(Error::NotInitialized)
+    }
+
+    fn client_connect_info(&self) -> Result<(ResponderId, X25519Public)> {
+        Ok((self.self_client_id()?, self.get_identity()?))
+    }
+
+    fn session_stats(&self) -> Result<SessionStats> {
+        Ok(SessionStats {
+            client_sessions: self.ake.client_session_count()?,
+            peer_sessions: self.ake.peer_session_count()?,
+        })
+    }
+
+    fn enclave_status(&self) -> Result<EnclaveStatus> {
+        // Holding the height lock keeps blocks from being signed while the AKE state is read.
+        let signing_height = self.signing_height.lock()?;
+        let (client_sessions, peer_sessions, ias_report) = self.ake.status()?;
+        Ok(EnclaveStatus {
+            sessions: SessionStats {
+                client_sessions,
+                peer_sessions,
+            },
+            ias_report_info: ias_report.map(IasReportInfo::try_from).transpose()?,
+            signing_height: *signing_height,
+        })
+    }
+
+    #[cfg(feature = "profiling")]
+    fn last_call_timings(&self) -> Result<CallTimings> {
+        self.last_call_timings
+            .lock()?
+            .ok_or(Error::ProfilingUnavailable)
+    }
+
+    #[cfg(not(feature = "profiling"))]
+    fn last_call_timings(&self) -> Result<CallTimings> {
+        Err(Error::ProfilingUnavailable)
+    }
+
     fn new_ereport(&self, qe_info: TargetInfo) -> Result<(Report, QuoteNonce)> {
         Ok(self.ake.new_ereport(qe_info)?)
     }
@@ -183,101 +762,218 @@ impl ConsensusEnclave for SgxConsensusEnclave {
         Ok(self.ake.verify_quote(quote, qe_report)?)
     }
 
+    fn cancel_ereport(&self) -> Result<()> {
+        Ok(self.ake.cancel_ereport()?)
+    }
+
     fn verify_ias_report(&self, ias_report: VerificationReport) -> Result<()> {
-        self.ake.verify_ias_report(ias_report)?;
-        Ok(())
+        self.verify_unconsumed_ias_report(ias_report, |ias_report| {
+            Ok(self.ake.verify_ias_report(ias_report)?)
+        })
+    }
+
+    fn verify_ias_report_at(
+        &self,
+        ias_report: VerificationReport,
+        now_unix_secs: u64,
+    ) -> Result<()> {
+        self.verify_unconsumed_ias_report(ias_report, |ias_report| {
+            Ok(self.ake.verify_ias_report_at(
+                ias_report,
+                now_unix_secs,
+                IAS_REPORT_MAX_AGE_SECS,
+            )?)
+        })
+    }
+
+    fn validate_ias_report(&self, ias_report: &VerificationReport) -> Result<()> {
+        Ok(self.ake.validate_ias_report(ias_report)?)
     }
 
     fn get_ias_report(&self) -> Result<VerificationReport> {
         Ok(self.ake.get_ias_report()?)
     }
 
+    fn get_ias_report_info(&self) -> Result<IasReportInfo> {
+        IasReportInfo::try_from(self.ake.get_ias_report()?)
+    }
+
+    fn ias_report_time(&self, report: &VerificationReport) -> Result<u64> {
+        ias_report_issued_at(report)
+    }
+
+    fn ias_report_blocks_remaining(
+        &self,
+        now_unix_secs: u64,
+        secs_per_block: u64,
+        blocks_per_refresh: u64,
+    ) -> Result<u64> {
+        // Fails with `NoReportAvailable` if no report is cached.
+        report_blocks_remaining(
+            &self.ake.get_ias_report()?,
+            now_unix_secs,
+            secs_per_block,
+            blocks_per_refresh,
+        )
+    }
+
+    fn report_body(&self) -> Result<ReportBody> {
+        Ok(self.ake.report_body()?)
+    }
+
+    fn attestation_bundle(&self) -> Result<AttestationBundle> {
+        let ias_report = match self.ake.get_ias_report() {
+            Ok(ias_report) => Some(ias_report),
+            Err(AttestEnclaveError::NoReportAvailable) => None,
+            Err(err) => return Err(err.into()),
+        };
+        Ok(AttestationBundle {
+            report_body: self.ake.report_body()?,
+            identity: self.ake.get_kex_identity(),
+            ias_report,
+        })
+    }
+
     fn client_accept(&self, req: ClientAuthRequest) -> Result<(ClientAuthResponse, ClientSession)> {
         Ok(self.ake.client_accept(req)?)
     }
 
+    fn client_accept_with_budget(
+        &self,
+        req: ClientAuthRequest,
+        max_sessions: usize,
+    ) -> Result<(ClientAuthResponse, ClientSession)> {
+        Ok(self.ake.client_accept_with_budget(req, max_sessions)?)
+    }
+
     fn client_close(&self, channel_id: ClientSession) -> Result<()> {
         Ok(self.ake.client_close(channel_id)?)
     }
 
+    fn client_sessions(&self) -> Result<Vec<ClientSession>> {
+        Ok(self.ake.client_sessions()?)
+    }
+
     fn client_discard_message(&self, msg: EnclaveMessage<ClientSession>) -> Result<()> {
         let _ = self.ake.client_decrypt(msg)?;
         Ok(())
     }
 
+    fn client_discard_messages(
+        &self,
+        msgs: Vec<EnclaveMessage<ClientSession>>,
+    ) -> Result<Vec<Result<()>>> {
+        Ok(msgs
+            .into_iter()
+            .map(|msg| self.client_discard_message(msg))
+            .collect())
+    }
+
     fn peer_init(&self, peer_id: &ResponderId) -> Result<PeerAuthRequest> {
         Ok(self.ake.peer_init(peer_id)?)
     }
 
+    fn peer_init_pinned(
+        &self,
+        peer_id: &ResponderId,
+        expected: Measurement,
+    ) -> Result<PeerAuthRequest> {
+        self.check_peer_identity(&expected)?;
+        Ok(self.ake.peer_init_pinned(peer_id, expected)?)
+    }
+
     fn peer_accept(&self, req: PeerAuthRequest) -> Result<(PeerAuthResponse, PeerSession)> {
+        self.check_peer_identity(&self.own_measurement()?)?;
         Ok(self.ake.peer_accept(req)?)
     }
 
     fn peer_connect(&self, peer_id: &ResponderId, msg: PeerAuthResponse) -> Result<PeerSession> {
+        // The policy may have changed since the connection was started.
+        self.check_peer_identity(&self.ake.pending_peer_measurement(peer_id)?)?;
         Ok(self.ake.peer_connect(peer_id, msg)?)
     }
 
+    fn set_peer_identity_policy(&self, allowed: Vec<Measurement>) -> Result<()> {
+        *self.peer_identity_policy.lock()? = Some(allowed);
+        Ok(())
+    }
+
     fn peer_close(&self, session_id: &PeerSession) -> Result<()> {
         Ok(self.ake.peer_close(session_id)?)
     }
 
+    fn peer_close_all(&self) -> Result<()> {
+        Ok(self.ake.peer_close_all()?)
+    }
+
+    fn close_sessions(
+        &self,
+        clients: &[ClientSession],
+        peers: &[PeerSession],
+    ) -> Result<Vec<(SessionRef, Result<()>)>> {
+        let (client_results, peer_results) = self.ake.close_sessions(clients, peers)?;
+        Ok(clients
+            .iter()
+            .cloned()
+            .map(SessionRef::Client)
+            .zip(client_results)
+            .chain(peers.iter().cloned().map(SessionRef::Peer).zip(peer_results))
+            .map(|(session, result)| (session, result.map_err(Error::from)))
+            .collect())
+    }
+
     fn client_tx_propose(&self, msg: EnclaveMessage<ClientSession>) -> Result<TxContext> {
+        // Reject oversized messages before doing any work on them.
+        let size = msg.aad.len() + msg.data.len();
+        let limit = *self.max_proposal_bytes.lock()?;
+        if size > limit {
+            return Err(Error::ProposalTooLarge { size, limit });
+        }
+
         let tx_bytes = self.ake.client_decrypt(msg)?;
 
         // Try and deserialize.
         let tx: Tx = mcserial::decode(&tx_bytes)?;
 
         // Convert to TxContext
-        let maybe_locally_encrypted_tx: Result<LocallyEncryptedTx> = {
-            let mut cipher = self.locally_encrypted_tx_cipher.lock()?;
-            let mut rng = McRng::default();
+        let mut rng = McRng::default();
+        self.tx_context(&tx, tx_bytes, &mut rng)
+    }
 
-            Ok(LocallyEncryptedTx(cipher.encrypt_bytes(&mut rng, tx_bytes)))
-        };
-        let locally_encrypted_tx = maybe_locally_encrypted_tx?;
+    fn client_tx_propose_with_receipt(
+        &self,
+        msg: EnclaveMessage<ClientSession>,
+        block_index: u64,
+    ) -> Result<(TxContext, ProposeReceipt)> {
+        let tx_context = self.client_tx_propose(msg)?;
+        let receipt = self.sign_propose_receipt(tx_context.tx_hash, block_index)?;
+        Ok((tx_context, receipt))
+    }
 
-        let tx_hash = tx.tx_hash();
-        let highest_indices = tx.get_membership_proof_highest_indices();
-        let key_images: Vec<KeyImage> = tx.key_images();
+    fn peer_tx_propose(
+        &self,
+        msg: EnclaveMessage<PeerSession>,
+    ) -> Result<Vec<Result<TxContext>>> {
+        self.peer_tx_propose_dedup(msg, &[])
+    }
 
-        Ok(TxContext {
-            locally_encrypted_tx,
-            tx_hash,
-            highest_indices,
-            key_images,
-        })
+    fn peer_tx_propose_dedup(
+        &self,
+        msg: EnclaveMessage<PeerSession>,
+        known_hashes: &[TxHash],
+    ) -> Result<Vec<Result<TxContext>>> {
+        Ok(self.peer_tx_propose_chunk(msg, known_hashes)?.tx_contexts)
     }
 
-    fn peer_tx_propose(&self, msg: EnclaveMessage<PeerSession>) -> Result<Vec<TxContext>> {
+    fn peer_tx_propose_chunk(
+        &self,
+        msg: EnclaveMessage<PeerSession>,
+        known_hashes: &[TxHash],
+    ) -> Result<PeerTxChunk> {
         // Try and decrypt the message.
         let data = self.ake.peer_decrypt(msg)?;
 
-        // Try and deserialize.
-        // Use prost
-        let txs = mcserial::decode::<TxList>(&data)?.txs;
-
-        // Convert to TxContexts
-        let mut rng = McRng::default();
-        txs.into_iter()
-            .map(|tx| {
-                let tx_bytes = mcserial::encode(&tx);
-                let maybe_locally_encrypted_tx: Result<LocallyEncryptedTx> = {
-                    let mut cipher = self.locally_encrypted_tx_cipher.lock()?;
-                    Ok(LocallyEncryptedTx(cipher.encrypt_bytes(&mut rng, tx_bytes)))
-                };
-                let locally_encrypted_tx = maybe_locally_encrypted_tx?;
-                let tx_hash = tx.tx_hash();
-                let highest_indices = tx.get_membership_proof_highest_indices();
-                let key_images: Vec<KeyImage> = tx.key_images();
-
-                Ok(TxContext {
-                    locally_encrypted_tx,
-                    tx_hash,
-                    highest_indices,
-                    key_images,
-                })
-            })
-            .collect()
+        self.peer_tx_chunk(&data, known_hashes)
     }
 
     fn tx_is_well_formed(
@@ -286,6 +982,18 @@ impl ConsensusEnclave for SgxConsensusEnclave {
         block_index: u64,
         proofs: Vec<TxOutMembershipProof>,
     ) -> Result<(WellFormedEncryptedTx, WellFormedTxContext)> {
+        self.tx_is_well_formed_unspent(locally_encrypted_tx, block_index, proofs, &[])
+    }
+
+    fn tx_is_well_formed_unspent(
+        &self,
+        locally_encrypted_tx: LocallyEncryptedTx,
+        block_index: u64,
+        proofs: Vec<TxOutMembershipProof>,
+        spent_key_images: &[KeyImage],
+    ) -> Result<(WellFormedEncryptedTx, WellFormedTxContext)> {
+        let _timer = self.call_timer(TimedOperation::TxIsWellFormed);
+
         // Enforce that all membership proofs provided by the untrusted system for transaction validation
         // came from the same ledger state. This can be checked by requiring all proofs to have the same root hash.
         let mut root_elements = BTreeSet::new();
@@ -296,27 +1004,156 @@ impl ConsensusEnclave for SgxConsensusEnclave {
                 .ok_or(Error::InvalidLocalMembershipProof)?;
             root_elements.insert(root_element);
         }
-        if root_elements.len() != 1 {
+        // No proofs at all is left for the count check below, once the expected count is known.
+        if root_elements.len() > 1 {
             return Err(Error::InvalidLocalMembershipProof);
         }
 
-        // Decrypt the locally encrypted transaction.
-        let decrypted_bytes = self
-            .locally_encrypted_tx_cipher
+        // A block formed at some index was formed against the ledger's root at that index, so
+        // proofs sharing the root of a block formed below `block_index` are outdated.
+        let stale_block = self
+            .recent_inclusions
             .lock()?
-            .decrypt_bytes(locally_encrypted_tx.0)?;
-        let tx: Tx = mcserial::decode(&decrypted_bytes)?;
-
-        // Validate.
-        let mut csprng = McRng::default();
-        transaction::validation::validate(&tx, block_index, &proofs, &mut csprng)?;
+            .iter()
+            .find(|block| block.index < block_index && root_elements.contains(&block.root_element))
+            .map(|block| block.index);
+        if let Some(index) = stale_block {
+            return Err(Error::StaleMembershipProof { index });
+        }
 
-        // Convert into a well formed encrypted transaction + context.
-        let well_formed_tx_context = WellFormedTxContext::from(&tx);
-        let well_formed_tx = WellFormedTx::from(tx);
-        let well_formed_encrypted_tx = self.encrypt_well_formed_tx(&well_formed_tx, &mut csprng)?;
+        // Decrypt the locally encrypted transaction.
+        let tx = self.decrypt_locally_encrypted_tx(&locally_encrypted_tx)?;
+
+        // Untrusted must supply one proof per ring member. Checking the count up front tells it
+        // plainly when it fetched the wrong proofs, before any of them are verified.
+        let expected = tx.get_membership_proof_highest_indices().len();
+        if proofs.len() != expected {
+            return Err(Error::ProofCountMismatch {
+                expected,
+                got: proofs.len(),
+            });
+        }
 
-        Ok((well_formed_encrypted_tx, well_formed_tx_context))
+        // The proofs were all made against the same ledger, whose last TxOut is at
+        // `highest_index`. Anything the transaction refers to must lie within it.
+        let ledger_size = proofs
+            .iter()
+            .map(|proof| proof.highest_index.saturating_add(1))
+            .max()
+            .unwrap_or(0);
+        if let Some(index) = tx
+            .get_membership_proof_highest_indices()
+            .into_iter()
+            .find(|index| *index >= ledger_size)
+        {
+            return Err(Error::IndexOutOfRange { index, ledger_size });
+        }
+
+        // Report expired transactions specifically, so clients can tell how late they were. The
+        // tombstone block itself is the first block the transaction can not be included in.
+        if block_index >= tx.prefix.tombstone_block {
+            return Err(Error::TombstoneExpired {
+                tombstone: tx.prefix.tombstone_block,
+                current: block_index,
+            });
+        }
+
+        // Turn away transactions outside the admission window, if one was set.
+        if let Some(window) = *self.tombstone_window.lock()? {
+            let earliest = block_index.saturating_add(window.min_offset);
+            let latest = block_index.saturating_add(window.max_offset);
+            let tombstone = tx.prefix.tombstone_block;
+            if tombstone < earliest || tombstone > latest {
+                return Err(Error::TombstoneOutsideWindow {
+                    tombstone,
+                    earliest,
+                    latest,
+                });
+            }
+        }
+
+        // Turn away transactions spending a key image untrusted knows to be spent.
+        if let Some(key_image) = tx
+            .key_images()
+            .into_iter()
+            .find(|key_image| spent_key_images.contains(key_image))
+        {
+            return Err(WellFormedError::SpentKeyImage(key_image).into());
+        }
+
+        // Validate. This also enforces the minimum fee of the default token, the only entry in
+        // `MINIMUM_FEES`.
+        let mut csprng = McRng::default();
+        transaction::validation::validate(&tx, block_index, &proofs, &mut csprng)
+            .map_err(|err| well_formed_error(err, &tx, block_index))?;
+
+        // Convert into a well formed encrypted transaction + context.
+        let well_formed_tx_context = WellFormedTxContext::from(&tx);
+        let well_formed_tx = WellFormedTx::from(tx);
+        let well_formed_encrypted_tx = self.encrypt_well_formed_tx(&well_formed_tx, &mut csprng)?;
+
+        Ok((well_formed_encrypted_tx, well_formed_tx_context))
+    }
+
+    fn txs_are_well_formed(
+        &self,
+        txs: Vec<(LocallyEncryptedTx, u64, Vec<TxOutMembershipProof>)>,
+    ) -> Result<Vec<Result<(WellFormedEncryptedTx, WellFormedTxContext)>>> {
+        Ok(txs
+            .into_iter()
+            .map(|(locally_encrypted_tx, block_index, proofs)| {
+                self.tx_is_well_formed(locally_encrypted_tx, block_index, proofs)
+            })
+            .collect())
+    }
+
+    fn tx_ring_signature_is_valid(&self, locally_encrypted_tx: &LocallyEncryptedTx) -> Result<()> {
+        let tx = self.decrypt_locally_encrypted_tx(locally_encrypted_tx)?;
+
+        let mut csprng = McRng::default();
+        transaction::validation::validate_transaction_signature(&tx, &mut csprng)
+            .map_err(|_| WellFormedError::InvalidRingSignature)?;
+
+        Ok(())
+    }
+
+    fn integrity_check(&self, locally_encrypted_tx: &LocallyEncryptedTx) -> Result<()> {
+        self.locally_encrypted_tx_cipher
+            .lock()?
+            .verify_bytes(&locally_encrypted_tx.0)
+            .or(Err(Error::Integrity))
+    }
+
+    fn verify_tx_hash(
+        &self,
+        locally_encrypted_tx: &LocallyEncryptedTx,
+        expected: &TxHash,
+    ) -> Result<()> {
+        let tx = self.decrypt_locally_encrypted_tx(locally_encrypted_tx)?;
+        if tx.tx_hash() != *expected {
+            return Err(Error::TxHashMismatch);
+        }
+        Ok(())
+    }
+
+    fn tx_fee_preview(&self, locally_encrypted_tx: &LocallyEncryptedTx) -> Result<(u64, u64)> {
+        let tx = self.decrypt_locally_encrypted_tx(locally_encrypted_tx)?;
+        // The current transaction format can only pay fees in the default token, see
+        // `WellFormedTxContext::from`.
+        Ok((DEFAULT_TOKEN_ID, tx.prefix.fee))
+    }
+
+    fn get_minimum_fee(&self, _block_index: u64) -> Result<u64> {
+        // The fee policy does not currently depend on the block index.
+        Ok(BASE_FEE)
+    }
+
+    fn get_minimum_fees(&self) -> Result<Vec<(u64, u64)>> {
+        Ok(MINIMUM_FEES.to_vec())
+    }
+
+    fn fee_policy_digest(&self) -> Result<[u8; 32]> {
+        Ok(digest_fee_policy(MINIMUM_FEES))
     }
 
     fn txs_for_peer(
@@ -329,35 +1166,254 @@ impl ConsensusEnclave for SgxConsensusEnclave {
         // check, this allows us to quickly bail out and skip expensive work if the peer is
         // definitely not known to us. This also lets us figure whether we are referencing an
         // incoming or outgoing connection
+        self.check_initialized()?;
         if !self.ake.is_peer_known(peer)? {
             return Err(Error::Attest(AttestEnclaveError::NotFound));
         }
 
         // Decrypt transactions
-        let txs: Result<Vec<Tx>> =
-            encrypted_txs
-                .iter()
-                .try_fold(Vec::new(), |mut init, encrypted_tx| {
-                    let well_formed_tx = self.decrypt_well_formed_tx(encrypted_tx)?;
-                    init.push(well_formed_tx.tx().clone());
-                    Ok(init)
-                });
+        let txs = self.encode_well_formed_txs(encrypted_txs)?;
 
         // Serialize this for the peer.
-        let serialized_txs = mcserial::encode(&TxList { txs: txs? });
+        let serialized_txs = mcserial::encode(&TxList {
+            txs,
+            chunk_index: 0,
+            chunk_total: 1,
+        });
 
         // Encrypt for the peer.
         Ok(self.ake.peer_encrypt(peer, aad, &serialized_txs)?)
     }
 
+    fn txs_for_peer_chunked(
+        &self,
+        encrypted_txs: &[WellFormedEncryptedTx],
+        aad: &[u8],
+        peer: &PeerSession,
+        max_chunk_bytes: usize,
+    ) -> Result<Vec<EnclaveMessage<PeerSession>>> {
+        // See `txs_for_peer`.
+        self.check_initialized()?;
+        if !self.ake.is_peer_known(peer)? {
+            return Err(Error::Attest(AttestEnclaveError::NotFound));
+        }
+
+        let txs = self.encode_well_formed_txs(encrypted_txs)?;
+
+        // Split before encrypting anything, so that a transaction which does not fit does not leave
+        // the peer channel with a partially sent batch.
+        let max_tx_list_len = max_tx_list_len(max_chunk_bytes, aad.len(), peer.as_ref().len())
+            .ok_or(Error::ChunkTooSmall(max_chunk_bytes))?;
+        let chunks =
+            chunk_tx_list(txs, max_tx_list_len).ok_or(Error::ChunkTooSmall(max_chunk_bytes))?;
+
+        // Encrypt each chunk for the peer, in order.
+        chunks
+            .iter()
+            .map(|chunk| Ok(self.ake.peer_encrypt(peer, aad, &mcserial::encode(chunk))?))
+            .collect()
+    }
+
     fn form_block(
         &self,
         parent_block: &Block,
         encrypted_txs_with_proofs: &[(WellFormedEncryptedTx, Vec<TxOutMembershipProof>)],
     ) -> Result<(Block, Vec<RedactedTx>, BlockSignature)> {
+        self.form_block_versioned(parent_block, encrypted_txs_with_proofs, BLOCK_VERSION)
+    }
+
+    fn form_block_versioned(
+        &self,
+        parent_block: &Block,
+        encrypted_txs_with_proofs: &[(WellFormedEncryptedTx, Vec<TxOutMembershipProof>)],
+        block_version: u32,
+    ) -> Result<(Block, Vec<RedactedTx>, BlockSignature)> {
+        let (block, redacted_transactions, signature, _report) = self.form_block_and_report(
+            parent_block,
+            encrypted_txs_with_proofs,
+            block_version,
+            &[],
+        )?;
+        Ok((block, redacted_transactions, signature))
+    }
+
+    fn block_version_range(&self) -> Result<(u32, u32)> {
+        // Only the current block format is supported, see `form_block_and_report`.
+        Ok((BLOCK_VERSION, BLOCK_VERSION))
+    }
+
+    fn form_block_with_report(
+        &self,
+        parent_block: &Block,
+        encrypted_txs_with_proofs: &[(WellFormedEncryptedTx, Vec<TxOutMembershipProof>)],
+    ) -> Result<(Block, Vec<RedactedTx>, BlockSignature, Vec<(TxHash, InclusionStatus)>)> {
+        self.form_block_and_report(parent_block, encrypted_txs_with_proofs, BLOCK_VERSION, &[])
+    }
+
+    fn form_block_excluding(
+        &self,
+        parent_block: &Block,
+        encrypted_txs_with_proofs: &[(WellFormedEncryptedTx, Vec<TxOutMembershipProof>)],
+        exclude: &[TxHash],
+    ) -> Result<(Block, Vec<RedactedTx>, BlockSignature)> {
+        let (block, redacted_transactions, signature, _report) = self.form_block_and_report(
+            parent_block,
+            encrypted_txs_with_proofs,
+            BLOCK_VERSION,
+            exclude,
+        )?;
+        Ok((block, redacted_transactions, signature))
+    }
+
+    fn form_empty_block(
+        &self,
+        parent_block: &Block,
+        root_proof: &TxOutMembershipProof,
+    ) -> Result<(Block, Vec<RedactedTx>, BlockSignature)> {
+        self.check_initialized()?;
+
+        // The new block is chained to the parent by its id, so the id must be the parent's own.
+        if !parent_block.is_block_id_valid() {
+            return Err(Error::BrokenChain);
+        }
+
+        // As in `form_block`, the block states the root of the ledger the proof was made against.
+        let root_element = root_proof
+            .elements
+            .last() // The last element contains the root hash.
+            .ok_or(Error::InvalidLocalMembershipProof)?;
+
+        // The ledger does not accept blocks without transactions, so mint a zero-value fee
+        // output. Its keys are derived from the parent's id, so that every node forms the same
+        // block, and empty blocks on different parents do not share an output public key.
+        let (fee_tx_private_key, blinding) = empty_block_fee_secrets(parent_block);
+        let redacted_transactions = vec![mint_aggregate_fee(&fee_tx_private_key, 0, blinding)?];
+
+        let block = Block::new(
+            BLOCK_VERSION,
+            &parent_block.id,
+            parent_block.index + 1,
+            root_element,
+            &redacted_transactions,
+        );
+        let signature = self.sign_and_record(&block, Vec::new(), Vec::new())?;
+
+        Ok((block, redacted_transactions, signature))
+    }
+
+    fn estimate_form_block_cost(
+        &self,
+        txs: &[(WellFormedEncryptedTx, Vec<TxOutMembershipProof>)],
+    ) -> Result<usize> {
+        Ok(txs
+            .iter()
+            .map(|(tx, proofs)| form_block_cost(tx, proofs))
+            .sum())
+    }
+
+    fn redact_single(&self, encrypted_tx: &WellFormedEncryptedTx) -> Result<RedactedTx> {
+        Ok(self.decrypt_well_formed_tx(encrypted_tx)?.tx.redact())
+    }
+
+    fn context_of(&self, encrypted_tx: &WellFormedEncryptedTx) -> Result<WellFormedTxContext> {
+        // This is how `tx_is_well_formed` derives the context, so the two always agree.
+        let well_formed_tx = self.decrypt_well_formed_tx(encrypted_tx)?;
+        Ok(WellFormedTxContext::from(&well_formed_tx.tx))
+    }
+
+    fn block_total_fees(&self, block: &Block, redacted: &[RedactedTx]) -> Result<u64> {
+        if block.contents_hash != hash_block_contents(redacted) {
+            return Err(Error::BlockContentsMismatch);
+        }
+        minted_aggregate_fee(redacted)
+    }
+
+    fn set_block_limits(&self, limits: BlockLimits) -> Result<()> {
+        *self.block_limits.lock()? = limits;
+        Ok(())
+    }
+
+    fn set_max_proposal_bytes(&self, max_proposal_bytes: usize) -> Result<()> {
+        *self.max_proposal_bytes.lock()? = max_proposal_bytes;
+        Ok(())
+    }
+
+    fn set_tombstone_window(&self, window: TombstoneWindow) -> Result<()> {
+        *self.tombstone_window.lock()? = Some(window);
+        Ok(())
+    }
+
+    fn current_signing_height(&self) -> Result<u64> {
+        Ok(*self.signing_height.lock()?)
+    }
+
+    fn included_in_block(&self, tx_hash: &TxHash) -> Result<Option<u64>> {
+        Ok(self
+            .recent_inclusions
+            .lock()?
+            .iter()
+            .find(|block| block.tx_hashes.contains(tx_hash))
+            .map(|block| block.index))
+    }
+
+    fn recent_key_images(&self, since_block: u64) -> Result<Vec<KeyImage>> {
+        Ok(self
+            .recent_inclusions
+            .lock()?
+            .iter()
+            .filter(|block| block.index >= since_block)
+            .flat_map(|block| block.key_images.iter().copied())
+            .collect())
+    }
+
+    fn verify_own_block_signature(&self, block: &Block, signature: &BlockSignature) -> Result<()> {
+        if signature.signer() != &self.ake.get_identity().get_public_key() {
+            return Err(Error::Signature);
+        }
+        Ok(signature.verify(block)?)
+    }
+
+    fn verify_own_block_signatures(&self, blocks: &[(Block, BlockSignature)]) -> Result<Vec<bool>> {
+        Ok(blocks
+            .iter()
+            .map(|(block, signature)| self.verify_own_block_signature(block, signature).is_ok())
+            .collect())
+    }
+
+    fn is_trusted_signer(&self, key: &Ed25519Public) -> Result<bool> {
+        self.check_initialized()?;
+        Ok(key == &self.ake.get_identity().get_public_key()
+            || self.retired_signers.lock()?.contains(key))
+    }
+}
+
+impl SgxConsensusEnclave {
+    /// Forms a block at `block_version` from as many of the given transactions as fit in the
+    /// current `BlockLimits`, and reports which of them were included. Transactions whose hashes
+    /// are in `exclude` are dropped before anything else, and do not appear in the report.
+    fn form_block_and_report(
+        &self,
+        parent_block: &Block,
+        encrypted_txs_with_proofs: &[(WellFormedEncryptedTx, Vec<TxOutMembershipProof>)],
+        block_version: u32,
+        exclude: &[TxHash],
+    ) -> Result<(Block, Vec<RedactedTx>, BlockSignature, Vec<(TxHash, InclusionStatus)>)> {
+        let _timer = self.call_timer(TimedOperation::FormBlock);
+        self.check_initialized()?;
+
+        // Only the current block format is supported.
+        if block_version != BLOCK_VERSION {
+            return Err(Error::UnsupportedBlockVersion(block_version));
+        }
+
+        // The new block is chained to the parent by its id, so the id must be the parent's own.
+        if !parent_block.is_block_id_valid() {
+            return Err(Error::BrokenChain);
+        }
+
         // This implicitly converts Vec<Result<(Tx Vec<TxOutMembershipProof>),_>> into Result<Vec<(Tx, Vec<TxOutMembershipProof>)>, _>,
         // and terminates the iteration when the first Error is encountered.
-        let transactions_with_proofs = encrypted_txs_with_proofs
+        let mut transactions_with_proofs = encrypted_txs_with_proofs
             .iter()
             .map(|(encrypted_tx, proofs)| {
                 Ok((
@@ -367,6 +1423,42 @@ impl ConsensusEnclave for SgxConsensusEnclave {
             })
             .collect::<Result<Vec<(Tx, Vec<TxOutMembershipProof>)>>>()?;
 
+        // Leave out the transactions untrusted asked to exclude.
+        transactions_with_proofs.retain(|(tx, _proofs)| !exclude.contains(&tx.tx_hash()));
+
+        // Order transactions by priority, and deterministically, so that honest nodes forming a
+        // block from the same candidate set produce identical blocks regardless of the order they
+        // were supplied in.
+        transactions_with_proofs
+            .sort_by_cached_key(|(tx, _proofs)| (Reverse(tx_priority(tx)), tx.tx_hash()));
+
+        // Take transactions in order until the next one would break a limit, and leave it and
+        // everything after it for a later block.
+        let limits = *self.block_limits.lock()?;
+        let mut block_bytes = 0u64;
+        let exclusion = transactions_with_proofs.iter().enumerate().find_map(
+            |(index, (tx, _proofs))| {
+                block_bytes += tx.encoded_len() as u64;
+                if index >= limits.max_txs as usize {
+                    Some((index, ExclusionReason::BlockFull))
+                } else if block_bytes > u64::from(limits.max_bytes) {
+                    Some((index, ExclusionReason::BlockTooLarge))
+                } else {
+                    None
+                }
+            },
+        );
+        let (excluded_transactions, exclusion_reason) = match exclusion {
+            Some((0, _reason)) => {
+                return Err(Error::NoTxFitsBlockLimits {
+                    max_txs: limits.max_txs,
+                    max_bytes: limits.max_bytes,
+                })
+            }
+            Some((index, reason)) => (transactions_with_proofs.split_off(index), reason),
+            None => (Vec::new(), ExclusionReason::BlockFull),
+        };
+
         // root_elements contains the root hash of the Merkle tree of all TxOuts in the ledger
         // that were used to validate the tranasctions.
         let mut root_elements = Vec::new();
@@ -411,17 +1503,14 @@ impl ConsensusEnclave for SgxConsensusEnclave {
             tx_hashes.insert(tx_hash);
         }
 
-        // Duplicate key images are not allowed.
+        // Duplicate key images are not allowed, since at most one of the transactions spending
+        // them could be valid.
         let mut used_key_images = BTreeSet::default();
         for tx in &transactions {
             for key_image in tx.key_images() {
-                if used_key_images.contains(&key_image) {
-                    return Err(Error::RedactTxs(format!(
-                        "Duplicate key image: {:?}",
-                        key_image
-                    )));
+                if !used_key_images.insert(key_image) {
+                    return Err(Error::DuplicateKeyImageInBlock(key_image));
                 }
-                used_key_images.insert(key_image);
             }
         }
 
@@ -461,12 +1550,29 @@ impl ConsensusEnclave for SgxConsensusEnclave {
             Blinding::from_bytes_mod_order(hash_value)
         };
 
-        let total_fee: u64 = transactions.iter().map(|tx| tx.prefix.fee).sum();
+        let total_fee = aggregate_fee(&transactions)?;
+        if total_fee > limits.max_fee {
+            return Err(Error::BlockFeeCapExceeded {
+                total_fee,
+                max_fee: limits.max_fee,
+            });
+        }
         let fee_minting_transaction = mint_aggregate_fee(&fee_tx_private_key, total_fee, blinding)?;
 
         // The fee is created in the zero-th redacted transaction.
         redacted_transactions.push(fee_minting_transaction);
 
+        let excluded = InclusionStatus::Excluded(exclusion_reason);
+        let report: Vec<(TxHash, InclusionStatus)> = transactions
+            .iter()
+            .map(|tx| (tx.tx_hash(), InclusionStatus::Included))
+            .chain(
+                excluded_transactions
+                    .iter()
+                    .map(|(tx, _proofs)| (tx.tx_hash(), excluded)),
+            )
+            .collect();
+
         // Redact each input transaction.
         for tx in transactions {
             redacted_transactions.push(tx.redact());
@@ -474,21 +1580,265 @@ impl ConsensusEnclave for SgxConsensusEnclave {
 
         // Form the block
         let block = Block::new(
-            BLOCK_VERSION,
+            block_version,
             &parent_block.id,
             parent_block.index + 1,
             &root_elements[0],
             &redacted_transactions,
         );
 
-        // Sign the block
+        let tx_hashes = report
+            .iter()
+            .filter(|(_tx_hash, status)| *status == InclusionStatus::Included)
+            .map(|(tx_hash, _status)| *tx_hash)
+            .collect();
+        let key_images = redacted_transactions
+            .iter()
+            .flat_map(|redacted| redacted.key_images.iter().copied())
+            .collect();
+        let signature = self.sign_and_record(&block, tx_hashes, key_images)?;
+
+        Ok((block, redacted_transactions, signature, report))
+    }
+
+    /// Signs a newly formed block and remembers it in the recent inclusion history, unless a
+    /// block at its height or above was already signed.
+    fn sign_and_record(
+        &self,
+        block: &Block,
+        tx_hashes: Vec<TxHash>,
+        key_images: Vec<KeyImage>,
+    ) -> Result<BlockSignature> {
+        // Holding the height lock until the signature is made keeps racing calls from both
+        // signing.
+        let mut signing_height = self.signing_height.lock()?;
+        if block.index <= *signing_height {
+            return Err(Error::NonMonotonicBlock {
+                attempted: block.index,
+                last: *signing_height,
+            });
+        }
         let public_key = self.ake.get_identity().signing_keypair.lock()?;
-        let signature = BlockSignature::from_block_and_keypair(&block, &public_key)?;
+        let signature = BlockSignature::from_block_and_keypair(block, &public_key)?;
+        *signing_height = block.index;
 
-        Ok((block, redacted_transactions, signature))
+        let mut recent_inclusions = self.recent_inclusions.lock()?;
+        recent_inclusions.push_back(RecentBlock {
+            index: block.index,
+            tx_hashes,
+            key_images,
+            root_element: block.root_element.clone(),
+        });
+        if recent_inclusions.len() > INCLUSION_HISTORY_BLOCKS {
+            recent_inclusions.pop_front();
+        }
+
+        Ok(signature)
+    }
+}
+
+/// The most bytes a `TxList` chunk can encode to, so that its message from `txs_for_peer_chunked`,
+/// sent with `aad_len` bytes of AAD on a channel whose id is `channel_id_len` bytes long, holds at
+/// most `max_chunk_bytes`. Returns `None` if the AAD and channel id leave no room for a payload.
+fn max_tx_list_len(max_chunk_bytes: usize, aad_len: usize, channel_id_len: usize) -> Option<usize> {
+    max_chunk_bytes
+        .checked_sub(PEER_MESSAGE_TAG_LEN)?
+        .checked_sub(aad_len)?
+        .checked_sub(channel_id_len)
+}
+
+/// Splits Prost-encoded transactions into `TxList` chunks which each encode to at most
+/// `max_len` bytes, keeping the transactions in order. Returns a single, empty chunk if there
+/// are no transactions, and `None` if some transaction does not fit in a chunk by itself.
+///
+/// # Arguments:
+/// * `txs` - Prost-encoded transactions.
+/// * `max_len` - The maximum encoded length of each chunk.
+fn chunk_tx_list(txs: Vec<Vec<u8>>, max_len: usize) -> Option<Vec<TxList>> {
+    let max_txs_len = max_len.checked_sub(TX_LIST_CHUNK_HEADER_LEN)?;
+
+    let mut chunks: Vec<Vec<Vec<u8>>> = vec![Vec::new()];
+    let mut current_len = 0;
+    for tx in txs {
+        // Each entry of a repeated bytes field is encoded as a one byte key, a length varint and
+        // the bytes themselves.
+        let tx_len = 1 + prost::encoding::encoded_len_varint(tx.len() as u64) + tx.len();
+        if tx_len > max_txs_len {
+            return None;
+        }
+
+        if current_len + tx_len > max_txs_len {
+            chunks.push(Vec::new());
+            current_len = 0;
+        }
+        current_len += tx_len;
+        chunks.last_mut().expect("chunks is never empty").push(tx);
+    }
+
+    let chunk_total = chunks.len() as u32;
+    Some(chunks
+        .into_iter()
+        .enumerate()
+        .map(|(chunk_index, txs)| TxList {
+            txs,
+            chunk_index: chunk_index as u32,
+            chunk_total,
+        })
+        .collect())
+}
+
+/// The additional MAC text sealed alongside a block signing key. It is empty, so keys sealed
+/// before blobs were tagged still unseal.
+const SIGNING_KEY_SEAL_TAG: &[u8] = b"";
+
+/// The additional MAC text sealed alongside blobs from `seal_blob`. It keeps untrusted from
+/// unsealing the block signing key through `unseal_blob`, or installing a key of its choosing by
+/// passing a sealed blob to `enclave_init`.
+const BLOB_SEAL_TAG: &[u8] = b"mc-consensus-enclave-sealed-blob";
+
+/// Seals `plaintext` with `tag` as its additional MAC text.
+fn seal_tagged(plaintext: &[u8], tag: &[u8]) -> Result<Vec<u8>> {
+    let sealed = IntelSealed::seal_raw(plaintext, tag).map_err(|err| match err {
+        IntelSealingError::Sgx(err) => Error::Sgx(err),
+        IntelSealingError::SealFormat(_) => Error::Serialization,
+    })?;
+    Ok(sealed.as_ref().to_vec())
+}
+
+/// Seals `plaintext` to the enclave signer, with `tag` as its additional MAC text, so that a
+/// later enclave from the same signer can unseal it.
+fn seal_tagged_to_signer(plaintext: &[u8], tag: &[u8]) -> Result<Vec<u8>> {
+    let sealed = IntelSealed::seal_raw_to_signer(plaintext, tag).map_err(|err| match err {
+        IntelSealingError::Sgx(err) => Error::Sgx(err),
+        IntelSealingError::SealFormat(_) => Error::Serialization,
+    })?;
+    Ok(sealed.as_ref().to_vec())
+}
+
+/// Unseals `sealed`, failing unless it was sealed with `tag` as its additional MAC text.
+fn unseal_tagged(sealed: &[u8], tag: &[u8]) -> Result<Vec<u8>> {
+    let cached = IntelSealed::try_from(sealed.to_vec()).map_err(|_| Error::Serialization)?;
+    let (plaintext, mac_txt) = cached.unseal_raw()?;
+    if mac_txt != tag {
+        return Err(Error::SealedPurposeMismatch);
+    }
+    Ok(plaintext)
+}
+
+/// Rejects a responder id which is not of the `host:port` form. `enclave_init` would otherwise
+/// accept it, and only the first AKE handshake using it would fail.
+fn validate_responder_id(responder_id: &ResponderId) -> Result<()> {
+    let mut parts = responder_id.0.rsplitn(2, ':');
+    let port = parts.next().unwrap_or_default();
+    let host = parts.next().unwrap_or_default();
+    if host.is_empty() || port.parse::<u16>().is_err() {
+        return Err(Error::InvalidResponderId(responder_id.clone()));
+    }
+    Ok(())
+}
+
+/// The number of copies of each candidate tx `form_block` holds at once: the ciphertext, its
+/// plaintext, the decoded tx and its redacted copy, each about the size of the ciphertext.
+const FORM_BLOCK_TX_COPIES: usize = 4;
+
+/// The number of copies of each candidate's membership proofs `form_block` holds at once: the
+/// ones it was given, and the ones it validates the decoded tx against.
+const FORM_BLOCK_PROOF_COPIES: usize = 2;
+
+/// Estimates the enclave memory, in bytes, `form_block` needs for one candidate tx.
+fn form_block_cost(tx: &WellFormedEncryptedTx, proofs: &[TxOutMembershipProof]) -> usize {
+    let proofs_len: usize = proofs
+        .iter()
+        .map(|proof| {
+            size_of::<TxOutMembershipProof>()
+                + proof.elements.len() * size_of::<TxOutMembershipElement>()
+        })
+        .sum();
+    FORM_BLOCK_TX_COPIES * tx.encrypted_len() + FORM_BLOCK_PROOF_COPIES * proofs_len
+}
+
+/// The fee schedule enforced by `tx_is_well_formed`, as `(token_id, minimum_fee)` pairs. Every
+/// fee is paid in the default token, whose minimum is the `BASE_FEE` transaction validation
+/// enforces.
+const MINIMUM_FEES: &[(u64, u64)] = &[(DEFAULT_TOKEN_ID, BASE_FEE)];
+
+/// Converts a validation failure into the structured error reported by `tx_is_well_formed`.
+///
+/// # Arguments:
+/// * `err` - The error returned by transaction validation.
+/// * `tx` - The transaction that failed validation.
+/// * `block_index` - The block index the transaction was validated against.
+fn well_formed_error(
+    err: TransactionValidationError,
+    tx: &Tx,
+    block_index: u64,
+) -> WellFormedError {
+    match err {
+        TransactionValidationError::TxFeeError => WellFormedError::InsufficientFee {
+            fee: tx.prefix.fee,
+            minimum_fee: BASE_FEE,
+        },
+        TransactionValidationError::TombstoneBlockTooFar => WellFormedError::TombstoneBlockTooFar {
+            tombstone_block: tx.prefix.tombstone_block,
+            block_index,
+        },
+        TransactionValidationError::DuplicateKeyImages => {
+            let mut seen = BTreeSet::new();
+            tx.key_images()
+                .into_iter()
+                .find(|key_image| !seen.insert(*key_image))
+                .map(WellFormedError::DuplicateKeyImage)
+                .unwrap_or(WellFormedError::Validation(err))
+        }
+        TransactionValidationError::InvalidTransactionSignature => {
+            WellFormedError::InvalidRingSignature
+        }
+        err => WellFormedError::Validation(err),
     }
 }
 
+/// Sums the fees paid by `transactions`, failing rather than wrapping if the total does not fit.
+fn aggregate_fee(transactions: &[Tx]) -> Result<u64> {
+    transactions.iter().try_fold(0u64, |total, tx| {
+        total.checked_add(tx.prefix.fee).ok_or(Error::FeeOverflow)
+    })
+}
+
+/// The number of blocks left before `report` is due for a refresh, as returned by
+/// `ias_report_blocks_remaining` for a cached report.
+fn report_blocks_remaining(
+    report: &VerificationReport,
+    now_unix_secs: u64,
+    secs_per_block: u64,
+    blocks_per_refresh: u64,
+) -> Result<u64> {
+    Ok(ias_report_blocks_remaining_at(
+        ias_report_issued_at(report)?,
+        now_unix_secs,
+        secs_per_block,
+        blocks_per_refresh,
+    ))
+}
+
+/// The fee output private key and blinding of the empty block formed on `parent_block`.
+fn empty_block_fee_secrets(parent_block: &Block) -> (RistrettoPrivate, Blinding) {
+    let hash_with_prefix = |prefix: &[u8]| -> [u8; 32] {
+        let mut hasher = Blake2b256::new();
+        hasher.input(prefix);
+        parent_block.id.digest(&mut hasher);
+        hasher
+            .result()
+            .as_slice()
+            .try_into()
+            .expect("Wrong length.")
+    };
+    let fee_tx_private_key = RistrettoPrivate::from(Scalar::from_bytes_mod_order(
+        hash_with_prefix(EMPTY_BLOCK_FEE_KEY_PREFIX),
+    ));
+    let blinding = Blinding::from_bytes_mod_order(hash_with_prefix(BLINDING_HASH_PREFIX));
+    (fee_tx_private_key, blinding)
+}
+
 /// Creates a RedactedTx with a single output belonging to the fee recipient account.
 ///
 /// # Arguments:
@@ -537,19 +1887,45 @@ fn mint_aggregate_fee(
     Ok(fee_minting_transaction)
 }
 
+/// Recovers the total fee minted by `mint_aggregate_fee` into the zero-th of a block's
+/// redacted transactions.
+fn minted_aggregate_fee(redacted_transactions: &[RedactedTx]) -> Result<u64> {
+    let fee_output = match redacted_transactions.first() {
+        Some(fee_tx) if fee_tx.key_images.is_empty() && fee_tx.outputs.len() == 1 => {
+            &fee_tx.outputs[0]
+        }
+        _ => return Err(Error::RedactTxs("Block has no aggregate fee output".into())),
+    };
+
+    let view_private_key = RistrettoPrivate::try_from(&FEE_VIEW_PRIVATE_KEY).unwrap();
+    let tx_public_key = RistrettoPublic::try_from(&fee_output.public_key)
+        .map_err(|e| Error::RedactTxs(format!("KeyError: {:?}", e)))?;
+    let shared_secret = compute_shared_secret(&tx_public_key, &view_private_key);
+    let (total_fee, _blinding) = fee_output
+        .amount
+        .get_value(&shared_secret)
+        .map_err(|e| Error::RedactTxs(format!("AmountError: {:?}", e)))?;
+    Ok(total_fee)
+}
+
 #[cfg(test)]
 mod tests {
 
     use super::*;
+    use consensus_enclave_api::{PeerTxChunks, PRIORITY_SIZE_UNIT};
+    use core::str::FromStr;
     use ledger_db::Ledger;
     use rand_core::SeedableRng;
     use rand_hc::Hc128Rng;
     use transaction::{
-        account_keys::AccountKey, constants::FEE_VIEW_PRIVATE_KEY,
-        onetime_keys::view_key_matches_output, tx::TxOutMembershipHash,
-        validation::TransactionValidationError, view_key::ViewKey,
+        account_keys::AccountKey, get_tx_out_shared_secret, onetime_keys::view_key_matches_output,
+        tx::TxOutMembershipHash, validation::TransactionValidationError, view_key::ViewKey,
     };
-    use transaction_test_utils::{create_ledger, create_transaction, initialize_ledger};
+    use transaction_test_utils::{
+        create_ledger, create_transaction, create_transaction_with_amount, initialize_ledger,
+    };
+
+    const IAS_WITH_PIB: &str = include_str!("../../../../attest/core/data/test/ias_with_pib.json");
 
     #[test]
     fn test_tx_is_well_formed_works() {
@@ -620,11 +1996,40 @@ mod tests {
     }
 
     #[test]
-    fn test_tx_is_well_formed_works_errors_on_bad_inputs() {
+    // Well-formed encrypted txs carry the current layout version and decrypt back to the same tx,
+    // while a tx claiming any other version is rejected before decrypting.
+    fn test_well_formed_encrypted_tx_format_version() {
+        let mut rng = Hc128Rng::from_seed([63u8; 32]);
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        let (_parent_block, input_transactions, well_formed_encrypted_txs_with_proofs) =
+            form_block_candidates(&enclave, &[BASE_FEE], &mut rng);
+        let (encrypted_tx, _proofs) = &well_formed_encrypted_txs_with_proofs[0];
+
+        assert_eq!(encrypted_tx.format_version(), WELL_FORMED_TX_FORMAT_VERSION);
+        assert_eq!(
+            enclave.redact_single(encrypted_tx),
+            Ok(input_transactions[0].redact())
+        );
+
+        let mut bumped = encrypted_tx.clone();
+        bumped.0[0] = WELL_FORMED_TX_FORMAT_VERSION + 1;
+        assert_eq!(
+            enclave.redact_single(&bumped),
+            Err(Error::UnsupportedTxFormat(WELL_FORMED_TX_FORMAT_VERSION + 1))
+        );
+        assert_eq!(
+            enclave.redact_single(&WellFormedEncryptedTx::default()),
+            Err(Error::UnsupportedTxFormat(0))
+        );
+    }
+
+    #[test]
+    // The fee preview of a proposed tx matches the fee in the context `tx_is_well_formed` returns
+    // for it later.
+    fn test_tx_fee_preview_matches_well_formed_context() {
         let enclave = SgxConsensusEnclave::default();
-        let mut rng = Hc128Rng::from_seed([77u8; 32]);
+        let mut rng = Hc128Rng::from_seed([64u8; 32]);
 
-        // Create a valid test transaction.
         let sender = AccountKey::random(&mut rng);
         let recipient = AccountKey::random(&mut rng);
 
@@ -632,7 +2037,6 @@ mod tests {
         let n_blocks = 3;
         initialize_ledger(&mut ledger, n_blocks, &sender, &mut rng);
 
-        // Choose a TxOut to spend. Only the TxOut in the last block is unspent.
         let mut transactions = ledger.get_transactions_by_block(n_blocks - 1).unwrap();
         let tx_stored = transactions.pop().unwrap();
         let tx_out = tx_stored.outputs[0].clone();
@@ -645,34 +2049,108 @@ mod tests {
             n_blocks + 1,
             &mut rng,
         );
-
-        // Create a LocallyEncryptedTx that can be fed into `tx_is_well_formed`.
-        let tx_bytes = mcserial::encode(&tx);
         let locally_encrypted_tx = LocallyEncryptedTx(
             enclave
                 .locally_encrypted_tx_cipher
                 .lock()
                 .unwrap()
-                .encrypt_bytes(&mut rng, tx_bytes.clone()),
+                .encrypt_bytes(&mut rng, mcserial::encode(&tx)),
         );
 
-        // Call `tx_is_well_formed` with a block index that puts us past the tombstone block.
+        // No membership proofs are needed for the preview.
+        let preview = enclave.tx_fee_preview(&locally_encrypted_tx).unwrap();
+        assert_eq!(preview, (DEFAULT_TOKEN_ID, tx.prefix.fee));
+
         let highest_indices = tx.get_membership_proof_highest_indices();
         let proofs = ledger
             .get_tx_out_proof_of_memberships(&highest_indices)
             .expect("failed getting proofs");
         let block_index = ledger.num_blocks().unwrap();
+        let (_well_formed_encrypted_tx, well_formed_tx_context) = enclave
+            .tx_is_well_formed(locally_encrypted_tx.clone(), block_index, proofs)
+            .unwrap();
+        assert_eq!(preview, well_formed_tx_context.fee_token());
 
-        assert_eq!(
-            enclave.tx_is_well_formed(
-                locally_encrypted_tx.clone(),
-                block_index + transaction::constants::MAX_TOMBSTONE_BLOCKS,
-                proofs.clone(),
-            ),
-            Err(Error::MalformedTx(
-                TransactionValidationError::TombstoneBlockExceeded
-            ))
-        );
+        // Another enclave can not read the tx.
+        let other_enclave = SgxConsensusEnclave::default();
+        assert!(other_enclave.tx_fee_preview(&locally_encrypted_tx).is_err());
+    }
+
+    #[test]
+    fn test_tx_is_well_formed_works_errors_on_bad_inputs() {
+        let enclave = SgxConsensusEnclave::default();
+        let mut rng = Hc128Rng::from_seed([77u8; 32]);
+
+        // Create a valid test transaction.
+        let sender = AccountKey::random(&mut rng);
+        let recipient = AccountKey::random(&mut rng);
+
+        let mut ledger = create_ledger();
+        let n_blocks = 3;
+        initialize_ledger(&mut ledger, n_blocks, &sender, &mut rng);
+
+        // Choose a TxOut to spend. Only the TxOut in the last block is unspent.
+        let mut transactions = ledger.get_transactions_by_block(n_blocks - 1).unwrap();
+        let tx_stored = transactions.pop().unwrap();
+        let tx_out = tx_stored.outputs[0].clone();
+
+        let tx = create_transaction(
+            &mut ledger,
+            &tx_out,
+            &sender,
+            &recipient.default_subaddress(),
+            n_blocks + 1,
+            &mut rng,
+        );
+
+        // Create a LocallyEncryptedTx that can be fed into `tx_is_well_formed`.
+        let tx_bytes = mcserial::encode(&tx);
+        let locally_encrypted_tx = LocallyEncryptedTx(
+            enclave
+                .locally_encrypted_tx_cipher
+                .lock()
+                .unwrap()
+                .encrypt_bytes(&mut rng, tx_bytes.clone()),
+        );
+
+        // Call `tx_is_well_formed` with a block index that puts us past the tombstone block.
+        let highest_indices = tx.get_membership_proof_highest_indices();
+        let proofs = ledger
+            .get_tx_out_proof_of_memberships(&highest_indices)
+            .expect("failed getting proofs");
+        let block_index = ledger.num_blocks().unwrap();
+
+        assert_eq!(
+            enclave.tx_is_well_formed(
+                locally_encrypted_tx.clone(),
+                block_index + transaction::constants::MAX_TOMBSTONE_BLOCKS,
+                proofs.clone(),
+            ),
+            Err(Error::TombstoneExpired {
+                tombstone: tx.prefix.tombstone_block,
+                current: block_index + transaction::constants::MAX_TOMBSTONE_BLOCKS,
+            })
+        );
+
+        // The tombstone block itself is already expired, the block before it is not.
+        assert_eq!(
+            enclave.tx_is_well_formed(
+                locally_encrypted_tx.clone(),
+                tx.prefix.tombstone_block,
+                proofs.clone(),
+            ),
+            Err(Error::TombstoneExpired {
+                tombstone: tx.prefix.tombstone_block,
+                current: tx.prefix.tombstone_block,
+            })
+        );
+        assert!(enclave
+            .tx_is_well_formed(
+                locally_encrypted_tx.clone(),
+                tx.prefix.tombstone_block - 1,
+                proofs.clone(),
+            )
+            .is_ok());
 
         // Call `tx_is_well_formed` with a wrong proof.
         let mut bad_proofs = proofs.clone();
@@ -680,9 +2158,50 @@ mod tests {
 
         assert_eq!(
             enclave.tx_is_well_formed(locally_encrypted_tx.clone(), block_index, bad_proofs,),
-            Err(Error::MalformedTx(
+            Err(Error::WellFormedCheck(WellFormedError::Validation(
                 TransactionValidationError::InvalidTxOutMembershipProof
-            ))
+            )))
+        );
+
+        // Call `tx_is_well_formed` with proofs from a ledger too small to hold the ring members,
+        // as a stale proof would be.
+        let mut stale_proofs = proofs.clone();
+        for proof in stale_proofs.iter_mut() {
+            proof.highest_index = 0;
+        }
+        assert!(highest_indices[0] > 0);
+        assert_eq!(
+            enclave.tx_is_well_formed(locally_encrypted_tx.clone(), block_index, stale_proofs),
+            Err(Error::IndexOutOfRange {
+                index: highest_indices[0],
+                ledger_size: 1,
+            })
+        );
+
+        // Call `tx_is_well_formed` with one proof too few, one too many, and none at all.
+        let too_few_proofs = proofs[1..].to_vec();
+        assert_eq!(
+            enclave.tx_is_well_formed(locally_encrypted_tx.clone(), block_index, too_few_proofs),
+            Err(Error::ProofCountMismatch {
+                expected: proofs.len(),
+                got: proofs.len() - 1,
+            })
+        );
+        let mut too_many_proofs = proofs.clone();
+        too_many_proofs.push(proofs[0].clone());
+        assert_eq!(
+            enclave.tx_is_well_formed(locally_encrypted_tx.clone(), block_index, too_many_proofs),
+            Err(Error::ProofCountMismatch {
+                expected: proofs.len(),
+                got: proofs.len() + 1,
+            })
+        );
+        assert_eq!(
+            enclave.tx_is_well_formed(locally_encrypted_tx.clone(), block_index, Vec::new()),
+            Err(Error::ProofCountMismatch {
+                expected: proofs.len(),
+                got: 0,
+            })
         );
 
         // Corrupt the encrypted data.
@@ -696,320 +2215,3299 @@ mod tests {
     }
 
     #[test]
-    // tx_is_well_formed rejects inconsistent root elements.
-    fn test_tx_is_well_form_rejects_inconsistent_root_elements() {
-        // Construct TxOutMembershipProofs.
-        let mut ledger = create_ledger();
-        let n_blocks = 16;
-        let mut rng = Hc128Rng::from_seed([77u8; 32]);
-        let account_key = AccountKey::random(&mut rng);
-        initialize_ledger(&mut ledger, n_blocks, &account_key, &mut rng);
-
-        let n_proofs = 10;
-        let indexes: Vec<u64> = (0..n_proofs as u64).into_iter().collect();
-        let mut membership_proofs = ledger.get_tx_out_proof_of_memberships(&indexes).unwrap();
-        // Modify one of the proofs to have a different root hash.
-        let inconsistent_proof = &mut membership_proofs[7];
-        let root_element = inconsistent_proof.elements.last_mut().unwrap();
-        root_element.hash = TxOutMembershipHash::from([33u8; 32]);
-
-        // The membership proofs supplied by the server are checked before this is decrypted and
-        // validated, so it can just be contstructed from an empty vector of bytes.
-        let locally_encrypted_tx = LocallyEncryptedTx(Vec::new());
-        let block_index = 77;
-        let result = SgxConsensusEnclave::default().tx_is_well_formed(
-            locally_encrypted_tx,
-            block_index,
-            membership_proofs,
-        );
-        let expected = Err(Error::InvalidLocalMembershipProof);
-        assert_eq!(result, expected);
-    }
-
-    #[test]
-    fn test_form_block_works() {
-        let mut rng = Hc128Rng::from_seed([77u8; 32]);
+    // A tombstone block on either edge of the admission window is admitted, and one just outside
+    // it is rejected.
+    fn test_tx_is_well_formed_enforces_tombstone_window() {
         let enclave = SgxConsensusEnclave::default();
+        let mut rng = Hc128Rng::from_seed([76u8; 32]);
 
-        // Create a valid test transaction.
         let sender = AccountKey::random(&mut rng);
         let recipient = AccountKey::random(&mut rng);
 
         let mut ledger = create_ledger();
-        let n_blocks = 2;
+        let n_blocks = 3;
         initialize_ledger(&mut ledger, n_blocks, &sender, &mut rng);
 
-        // A transaction from the ledger, whose outputs will be "spent" in this test.
-        // This assumes the first transaction in the ledger has enough TxOuts.
-        let tx_stored = {
-            let mut transactions = ledger.get_transactions_by_block(0).unwrap();
-            transactions.pop().unwrap()
-        };
-
-        let input_transactions: Vec<Tx> = (0..3)
-            .map(|i| {
-                let tx_out = tx_stored.outputs[i].clone();
-
-                create_transaction(
-                    &mut ledger,
-                    &tx_out,
-                    &sender,
-                    &recipient.default_subaddress(),
-                    n_blocks + 1,
-                    &mut rng,
-                )
-            })
-            .collect();
-
-        let total_fee: u64 = input_transactions.iter().map(|tx| tx.prefix.fee).sum();
-
-        // Create WellFormedEncryptedTxs + proofs
-        let well_formed_encrypted_txs_with_proofs: Vec<_> = input_transactions
-            .iter()
-            .map(|tx| {
-                let well_formed_tx = WellFormedTx::from(tx.clone());
-                let encrypted_tx = enclave
-                    .encrypt_well_formed_tx(&well_formed_tx, &mut rng)
-                    .unwrap();
-
-                let highest_indices = well_formed_tx.tx.get_membership_proof_highest_indices();
-                let membership_proofs = ledger
-                    .get_tx_out_proof_of_memberships(&highest_indices)
-                    .expect("failed getting proof");
-                (encrypted_tx, membership_proofs)
-            })
-            .collect();
-
-        // Form block
-        let parent_block = ledger.get_block(ledger.num_blocks().unwrap() - 1).unwrap();
-
-        let (block, redacted_transactions, signature) = enclave
-            .form_block(&parent_block, &well_formed_encrypted_txs_with_proofs)
-            .unwrap();
+        // Choose a TxOut to spend. Only the TxOut in the last block is unspent.
+        let mut transactions = ledger.get_transactions_by_block(n_blocks - 1).unwrap();
+        let tx_stored = transactions.pop().unwrap();
+        let tx_out = tx_stored.outputs[0].clone();
 
-        // Verify signature.
-        assert_eq!(
-            signature.signer(),
-            &enclave
-                .ake
-                .get_identity()
-                .signing_keypair
+        let tombstone = n_blocks + 1;
+        let tx = create_transaction(
+            &mut ledger,
+            &tx_out,
+            &sender,
+            &recipient.default_subaddress(),
+            tombstone,
+            &mut rng,
+        );
+        let locally_encrypted_tx = LocallyEncryptedTx(
+            enclave
+                .locally_encrypted_tx_cipher
                 .lock()
                 .unwrap()
-                .public_key()
+                .encrypt_bytes(&mut rng, mcserial::encode(&tx)),
         );
+        let highest_indices = tx.get_membership_proof_highest_indices();
+        let proofs = ledger
+            .get_tx_out_proof_of_memberships(&highest_indices)
+            .expect("failed getting proofs");
+        let block_index = ledger.num_blocks().unwrap();
+        let offset = tombstone - block_index;
 
-        let signature_verification_result = signature.verify(&block);
-        assert!(signature_verification_result.is_ok());
-
-        // `redacted_transactions` should include an additional transaction for the aggregate fee.
-        assert_eq!(redacted_transactions.len(), input_transactions.len() + 1);
+        let check = |min_offset: u64, max_offset: u64| {
+            enclave
+                .set_tombstone_window(TombstoneWindow {
+                    min_offset,
+                    max_offset,
+                })
+                .unwrap();
+            enclave
+                .tx_is_well_formed(locally_encrypted_tx.clone(), block_index, proofs.clone())
+                .map(|_| ())
+        };
 
-        // The zero-th RedactedTx should send a single output to the Fee recipient account.
-        let fee_minting_transaction = &redacted_transactions[0];
-        assert_eq!(fee_minting_transaction.key_images.len(), 0);
-        assert_eq!(fee_minting_transaction.outputs.len(), 1);
-        let aggregate_fee_output = &fee_minting_transaction.outputs[0];
+        // The tombstone block sits on the near edge, the far edge, or both.
+        assert_eq!(check(offset, offset + 10), Ok(()));
+        assert_eq!(check(0, offset), Ok(()));
+        assert_eq!(check(offset, offset), Ok(()));
 
-        let view_secret_key = RistrettoPrivate::try_from(&FEE_VIEW_PRIVATE_KEY).unwrap();
-        let public_address = PublicAddress::new(
-            &RistrettoPublic::try_from(&FEE_SPEND_PUBLIC_KEY).unwrap(),
-            &RistrettoPublic::from(&view_secret_key),
+        // Just past the near edge: the transaction would expire too soon.
+        assert_eq!(
+            check(offset + 1, offset + 10),
+            Err(Error::TombstoneOutsideWindow {
+                tombstone,
+                earliest: block_index + offset + 1,
+                latest: block_index + offset + 10,
+            })
         );
 
-        // The FEE address should be the recipient of the aggregate fee.
-        let fee_view_key = ViewKey::new(view_secret_key, *public_address.spend_public_key());
-        let output_target_key: RistrettoPublic =
-            RistrettoPublic::try_from(&aggregate_fee_output.target_key).unwrap();
-        let tx_public_key = RistrettoPublic::try_from(&aggregate_fee_output.public_key).unwrap();
-
-        assert!(view_key_matches_output(
-            &fee_view_key,
-            &output_target_key,
-            &tx_public_key
-        ));
-
-        // The value of the aggregate fee should equal the total value of fees in the input transaction.
-        let shared_secret = compute_shared_secret(&tx_public_key, &view_secret_key);
-        let (value, _blinding) = aggregate_fee_output
-            .amount
-            .get_value(&shared_secret)
-            .unwrap();
-        assert_eq!(value, total_fee);
-
-        // Each of the input transactions should be redacted.
-        for (i, tx) in input_transactions.into_iter().enumerate() {
-            let expected = tx.redact();
-
-            assert_eq!(expected, redacted_transactions[i + 1]);
-        }
+        // Just past the far edge: the transaction lives too long.
+        assert_eq!(
+            check(0, offset - 1),
+            Err(Error::TombstoneOutsideWindow {
+                tombstone,
+                earliest: block_index,
+                latest: block_index + offset - 1,
+            })
+        );
     }
 
     #[test]
-    /// form_block should return an error if the input transactions contain a double-spend.
-    fn test_form_block_prevents_duplicate_spend() {
+    // A transaction spending a key image untrusted reports as spent is not well-formed, while the
+    // same transaction is accepted against a spent set that does not hold its key images.
+    fn test_tx_is_well_formed_unspent_rejects_spent_key_image() {
         let enclave = SgxConsensusEnclave::default();
         let mut rng = Hc128Rng::from_seed([77u8; 32]);
 
-        // Initialize a ledger. `sender` is the owner of all outputs in the initial ledger.
         let sender = AccountKey::random(&mut rng);
+        let recipient = AccountKey::random(&mut rng);
+
         let mut ledger = create_ledger();
         let n_blocks = 3;
         initialize_ledger(&mut ledger, n_blocks, &sender, &mut rng);
 
-        // Create a few transactions from `sender` to `recipient`.
-        let num_transactions = 5;
-        let recipient = AccountKey::random(&mut rng);
-
-        // The first block contains a single transaction with MIN_RING_SIZE outputs.
-        let block_zero_transactions = ledger.get_transactions_by_block(0).unwrap();
-        let block_zero_redacted_tx = block_zero_transactions.get(0).unwrap();
+        // Choose a TxOut to spend. Only the TxOut in the last block is unspent.
+        let mut transactions = ledger.get_transactions_by_block(n_blocks - 1).unwrap();
+        let tx_stored = transactions.pop().unwrap();
+        let tx_out = tx_stored.outputs[0].clone();
 
-        let mut new_transactions = Vec::new();
-        for i in 0..num_transactions {
-            let tx_out = &block_zero_redacted_tx.outputs[i];
+        let tx = create_transaction(
+            &mut ledger,
+            &tx_out,
+            &sender,
+            &recipient.default_subaddress(),
+            n_blocks + 1,
+            &mut rng,
+        );
+        let locally_encrypted_tx = LocallyEncryptedTx(
+            enclave
+                .locally_encrypted_tx_cipher
+                .lock()
+                .unwrap()
+                .encrypt_bytes(&mut rng, mcserial::encode(&tx)),
+        );
+        let highest_indices = tx.get_membership_proof_highest_indices();
+        let proofs = ledger
+            .get_tx_out_proof_of_memberships(&highest_indices)
+            .expect("failed getting proofs");
+        let block_index = ledger.num_blocks().unwrap();
 
-            let tx = create_transaction(
+        // Key images spent by other transactions do not matter.
+        let other_key_images: Vec<KeyImage> = ledger
+            .get_transactions_by_block(0)
+            .unwrap()
+            .iter()
+            .flat_map(|redacted| redacted.key_images.clone())
+            .chain(core::iter::once(KeyImage::default()))
+            .collect();
+        assert!(enclave
+            .tx_is_well_formed_unspent(
+                locally_encrypted_tx.clone(),
+                block_index,
+                proofs.clone(),
+                &other_key_images,
+            )
+            .is_ok());
+
+        let spent_key_image = tx.key_images()[0];
+        let mut spent_key_images = other_key_images;
+        spent_key_images.push(spent_key_image);
+        assert_eq!(
+            enclave
+                .tx_is_well_formed_unspent(
+                    locally_encrypted_tx,
+                    block_index,
+                    proofs,
+                    &spent_key_images,
+                )
+                .map(|_| ()),
+            Err(Error::WellFormedCheck(WellFormedError::SpentKeyImage(
+                spent_key_image
+            )))
+        );
+    }
+
+    #[test]
+    // The TxContext returned for a proposed tx carries the tx's tombstone block. Proposals arrive
+    // over attested sessions, which need an IAS-verified report, so this exercises the helper
+    // shared by `client_tx_propose` and `peer_tx_propose` directly.
+    fn test_tx_context_has_tombstone_block() {
+        let enclave = SgxConsensusEnclave::default();
+        let mut rng = Hc128Rng::from_seed([42u8; 32]);
+
+        let sender = AccountKey::random(&mut rng);
+        let recipient = AccountKey::random(&mut rng);
+
+        let mut ledger = create_ledger();
+        let n_blocks = 3;
+        initialize_ledger(&mut ledger, n_blocks, &sender, &mut rng);
+
+        // Choose a TxOut to spend. Only the TxOut in the last block is unspent.
+        let mut transactions = ledger.get_transactions_by_block(n_blocks - 1).unwrap();
+        let tx_stored = transactions.pop().unwrap();
+        let tx_out = tx_stored.outputs[0].clone();
+
+        let tx = create_transaction(
+            &mut ledger,
+            &tx_out,
+            &sender,
+            &recipient.default_subaddress(),
+            n_blocks + 7,
+            &mut rng,
+        );
+
+        let tx_context = enclave
+            .tx_context(&tx, mcserial::encode(&tx), &mut rng)
+            .unwrap();
+        assert_eq!(tx_context.tombstone_block, tx.prefix.tombstone_block);
+        assert_eq!(tx_context.tombstone_block, n_blocks + 7);
+        assert_eq!(tx_context.tx_hash, tx.tx_hash());
+        assert_eq!(
+            enclave
+                .decrypt_locally_encrypted_tx(&tx_context.locally_encrypted_tx)
+                .unwrap(),
+            tx
+        );
+    }
+
+    #[test]
+    // tx_ring_signature_is_valid accepts a correctly signed tx without any membership proofs, and
+    // rejects txs whose signature no longer matches their contents.
+    fn test_tx_ring_signature_is_valid() {
+        let enclave = SgxConsensusEnclave::default();
+        let mut rng = Hc128Rng::from_seed([41u8; 32]);
+
+        let sender = AccountKey::random(&mut rng);
+        let recipient = AccountKey::random(&mut rng);
+
+        let mut ledger = create_ledger();
+        let n_blocks = 3;
+        initialize_ledger(&mut ledger, n_blocks, &sender, &mut rng);
+
+        // Choose a TxOut to spend. Only the TxOut in the last block is unspent.
+        let mut transactions = ledger.get_transactions_by_block(n_blocks - 1).unwrap();
+        let tx_stored = transactions.pop().unwrap();
+        let tx_out = tx_stored.outputs[0].clone();
+
+        let tx = create_transaction(
+            &mut ledger,
+            &tx_out,
+            &sender,
+            &recipient.default_subaddress(),
+            n_blocks + 1,
+            &mut rng,
+        );
+
+        let encrypt = |tx: &Tx, rng: &mut Hc128Rng| {
+            LocallyEncryptedTx(
+                enclave
+                    .locally_encrypted_tx_cipher
+                    .lock()
+                    .unwrap()
+                    .encrypt_bytes(rng, mcserial::encode(tx)),
+            )
+        };
+
+        assert_eq!(enclave.tx_ring_signature_is_valid(&encrypt(&tx, &mut rng)), Ok(()));
+
+        // Changing the prefix invalidates the signature over it.
+        let mut bad_fee_tx = tx.clone();
+        bad_fee_tx.prefix.fee += 1;
+        assert_eq!(
+            enclave.tx_ring_signature_is_valid(&encrypt(&bad_fee_tx, &mut rng)),
+            Err(Error::WellFormedCheck(WellFormedError::InvalidRingSignature))
+        );
+
+        // A ring that no longer matches the signature's shape is rejected too.
+        let mut bad_ring_tx = tx.clone();
+        bad_ring_tx.prefix.inputs[0].ring.pop();
+        assert_eq!(
+            enclave.tx_ring_signature_is_valid(&encrypt(&bad_ring_tx, &mut rng)),
+            Err(Error::WellFormedCheck(WellFormedError::InvalidRingSignature))
+        );
+    }
+
+    #[test]
+    // integrity_check accepts an intact sealed tx, and detects a single flipped byte anywhere.
+    fn test_integrity_check() {
+        let enclave = SgxConsensusEnclave::default();
+        let mut rng = Hc128Rng::from_seed([75u8; 32]);
+
+        let sealed = LocallyEncryptedTx(
+            enclave
+                .locally_encrypted_tx_cipher
+                .lock()
+                .unwrap()
+                .encrypt_bytes(&mut rng, mcserial::encode(&Tx::default())),
+        );
+        assert_eq!(enclave.integrity_check(&sealed), Ok(()));
+
+        for index in [0, sealed.0.len() / 2, sealed.0.len() - 1].iter() {
+            let mut corrupted = sealed.clone();
+            corrupted.0[*index] ^= 0x80;
+            assert_eq!(enclave.integrity_check(&corrupted), Err(Error::Integrity));
+        }
+
+        // Another enclave's sealed tx does not pass either.
+        let other_enclave = SgxConsensusEnclave::default();
+        assert_eq!(other_enclave.integrity_check(&sealed), Err(Error::Integrity));
+    }
+
+    #[test]
+    // verify_tx_hash accepts the hash of the sealed tx, and rejects any other.
+    fn test_verify_tx_hash() {
+        let enclave = SgxConsensusEnclave::default();
+        let mut rng = Hc128Rng::from_seed([51u8; 32]);
+
+        let sender = AccountKey::random(&mut rng);
+        let recipient = AccountKey::random(&mut rng);
+
+        let mut ledger = create_ledger();
+        let n_blocks = 3;
+        initialize_ledger(&mut ledger, n_blocks, &sender, &mut rng);
+
+        let mut transactions = ledger.get_transactions_by_block(n_blocks - 1).unwrap();
+        let tx_stored = transactions.pop().unwrap();
+        let tx_out = tx_stored.outputs[0].clone();
+
+        let tx = create_transaction(
+            &mut ledger,
+            &tx_out,
+            &sender,
+            &recipient.default_subaddress(),
+            n_blocks + 1,
+            &mut rng,
+        );
+        let locally_encrypted_tx = LocallyEncryptedTx(
+            enclave
+                .locally_encrypted_tx_cipher
+                .lock()
+                .unwrap()
+                .encrypt_bytes(&mut rng, mcserial::encode(&tx)),
+        );
+
+        assert_eq!(enclave.verify_tx_hash(&locally_encrypted_tx, &tx.tx_hash()), Ok(()));
+
+        // The hash of a slightly different tx.
+        let mut other_tx = tx.clone();
+        other_tx.prefix.fee += 1;
+        assert_eq!(
+            enclave.verify_tx_hash(&locally_encrypted_tx, &other_tx.tx_hash()),
+            Err(Error::TxHashMismatch)
+        );
+        assert_eq!(
+            enclave.verify_tx_hash(&locally_encrypted_tx, &TxHash::default()),
+            Err(Error::TxHashMismatch)
+        );
+    }
+
+    #[test]
+    // tx_is_well_formed rejects inconsistent root elements.
+    fn test_tx_is_well_form_rejects_inconsistent_root_elements() {
+        // Construct TxOutMembershipProofs.
+        let mut ledger = create_ledger();
+        let n_blocks = 16;
+        let mut rng = Hc128Rng::from_seed([77u8; 32]);
+        let account_key = AccountKey::random(&mut rng);
+        initialize_ledger(&mut ledger, n_blocks, &account_key, &mut rng);
+
+        let n_proofs = 10;
+        let indexes: Vec<u64> = (0..n_proofs as u64).into_iter().collect();
+        let mut membership_proofs = ledger.get_tx_out_proof_of_memberships(&indexes).unwrap();
+        // Modify one of the proofs to have a different root hash.
+        let inconsistent_proof = &mut membership_proofs[7];
+        let root_element = inconsistent_proof.elements.last_mut().unwrap();
+        root_element.hash = TxOutMembershipHash::from([33u8; 32]);
+
+        // The membership proofs supplied by the server are checked before this is decrypted and
+        // validated, so it can just be contstructed from an empty vector of bytes.
+        let locally_encrypted_tx = LocallyEncryptedTx(Vec::new());
+        let block_index = 77;
+        let result = SgxConsensusEnclave::default().tx_is_well_formed(
+            locally_encrypted_tx,
+            block_index,
+            membership_proofs,
+        );
+        let expected = Err(Error::InvalidLocalMembershipProof);
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    // txs_are_well_formed returns one result per input, in input order, and a bad tx does not
+    // affect the rest of the batch.
+    fn test_txs_are_well_formed_handles_partial_failure() {
+        let enclave = SgxConsensusEnclave::default();
+        let mut rng = Hc128Rng::from_seed([1u8; 32]);
+
+        let sender = AccountKey::random(&mut rng);
+        let recipient = AccountKey::random(&mut rng);
+
+        let mut ledger = create_ledger();
+        let n_blocks = 2;
+        initialize_ledger(&mut ledger, n_blocks, &sender, &mut rng);
+
+        // A transaction from the ledger, whose outputs will be "spent" in this test.
+        let tx_stored = {
+            let mut transactions = ledger.get_transactions_by_block(0).unwrap();
+            transactions.pop().unwrap()
+        };
+
+        let transactions: Vec<Tx> = (0..3)
+            .map(|i| {
+                create_transaction(
+                    &mut ledger,
+                    &tx_stored.outputs[i],
+                    &sender,
+                    &recipient.default_subaddress(),
+                    n_blocks + 1,
+                    &mut rng,
+                )
+            })
+            .collect();
+
+        let block_index = ledger.num_blocks().unwrap();
+        let mut batch: Vec<(LocallyEncryptedTx, u64, Vec<TxOutMembershipProof>)> = transactions
+            .iter()
+            .map(|tx| {
+                let locally_encrypted_tx = LocallyEncryptedTx(
+                    enclave
+                        .locally_encrypted_tx_cipher
+                        .lock()
+                        .unwrap()
+                        .encrypt_bytes(&mut rng, mcserial::encode(tx)),
+                );
+                let proofs = ledger
+                    .get_tx_out_proof_of_memberships(&tx.get_membership_proof_highest_indices())
+                    .expect("failed getting proofs");
+                (locally_encrypted_tx, block_index, proofs)
+            })
+            .collect();
+
+        // Corrupt the encrypted data of the middle transaction.
+        let corrupted = &mut (batch[1].0).0;
+        corrupted[0] = !corrupted[0];
+
+        let results = enclave.txs_are_well_formed(batch).unwrap();
+        assert_eq!(results.len(), transactions.len());
+
+        let (_, first_context) = results[0].as_ref().unwrap();
+        assert_eq!(first_context.tx_hash(), &transactions[0].tx_hash());
+
+        assert_eq!(
+            results[1],
+            Err(Error::CacheCipher(message_cipher::CipherError::MacFailure))
+        );
+
+        let (_, last_context) = results[2].as_ref().unwrap();
+        assert_eq!(last_context.tx_hash(), &transactions[2].tx_hash());
+    }
+
+    #[test]
+    // A tx paying exactly the fee returned by `get_minimum_fee` is well-formed, and one paying
+    // less is not.
+    fn test_get_minimum_fee_is_enforced_by_tx_is_well_formed() {
+        let enclave = SgxConsensusEnclave::default();
+        let mut rng = Hc128Rng::from_seed([1u8; 32]);
+
+        let sender = AccountKey::random(&mut rng);
+        let recipient = AccountKey::random(&mut rng);
+
+        let mut ledger = create_ledger();
+        let n_blocks = 3;
+        initialize_ledger(&mut ledger, n_blocks, &sender, &mut rng);
+        let block_index = ledger.num_blocks().unwrap();
+
+        let minimum_fee = enclave.get_minimum_fee(block_index).unwrap();
+
+        // Choose a TxOut to spend. Only the TxOut in the last block is unspent.
+        let mut transactions = ledger.get_transactions_by_block(n_blocks - 1).unwrap();
+        let tx_stored = transactions.pop().unwrap();
+        let tx_out = tx_stored.outputs[0].clone();
+        let value = {
+            let tx_out_public_key = RistrettoPublic::try_from(&tx_out.public_key).unwrap();
+            let shared_secret =
+                get_tx_out_shared_secret(sender.view_private_key(), &tx_out_public_key);
+            let (value, _blinding) = tx_out.amount.get_value(&shared_secret).unwrap();
+            value
+        };
+
+        let mut check_with_fee = |fee: u64| {
+            let tx = create_transaction_with_amount(
                 &mut ledger,
-                tx_out,
+                &tx_out,
                 &sender,
                 &recipient.default_subaddress(),
+                value - fee,
+                fee,
                 n_blocks + 1,
                 &mut rng,
             );
-            new_transactions.push(tx);
+            let locally_encrypted_tx = LocallyEncryptedTx(
+                enclave
+                    .locally_encrypted_tx_cipher
+                    .lock()
+                    .unwrap()
+                    .encrypt_bytes(&mut rng, mcserial::encode(&tx)),
+            );
+            let proofs = ledger
+                .get_tx_out_proof_of_memberships(&tx.get_membership_proof_highest_indices())
+                .expect("failed getting proofs");
+            enclave
+                .tx_is_well_formed(locally_encrypted_tx, block_index, proofs)
+                .map(|(_, well_formed_tx_context)| well_formed_tx_context.fee())
+        };
+
+        assert_eq!(check_with_fee(minimum_fee), Ok(minimum_fee));
+        assert_eq!(
+            check_with_fee(minimum_fee - 1),
+            Err(Error::WellFormedCheck(WellFormedError::InsufficientFee {
+                fee: minimum_fee - 1,
+                minimum_fee,
+            }))
+        );
+    }
+
+    #[test]
+    // The schedule holds the default token alone, at the fee `get_minimum_fee` reports.
+    fn test_get_minimum_fees() {
+        let enclave = SgxConsensusEnclave::default();
+        assert_eq!(
+            enclave.get_minimum_fees(),
+            Ok(vec![(DEFAULT_TOKEN_ID, enclave.get_minimum_fee(0).unwrap())])
+        );
+    }
+
+    #[test]
+    // The priority exposed by `tx_is_well_formed` follows the documented formula, and a tx
+    // paying a higher fee is prioritized over an otherwise similar tx paying less.
+    fn test_well_formed_tx_context_priority() {
+        let enclave = SgxConsensusEnclave::default();
+        let mut rng = Hc128Rng::from_seed([1u8; 32]);
+
+        let sender = AccountKey::random(&mut rng);
+        let recipient = AccountKey::random(&mut rng);
+
+        let mut ledger = create_ledger();
+        let n_blocks = 3;
+        initialize_ledger(&mut ledger, n_blocks, &sender, &mut rng);
+        let block_index = ledger.num_blocks().unwrap();
+
+        // Choose a TxOut to spend. Only the TxOut in the last block is unspent.
+        let mut transactions = ledger.get_transactions_by_block(n_blocks - 1).unwrap();
+        let tx_stored = transactions.pop().unwrap();
+        let tx_out = tx_stored.outputs[0].clone();
+        let value = {
+            let tx_out_public_key = RistrettoPublic::try_from(&tx_out.public_key).unwrap();
+            let shared_secret =
+                get_tx_out_shared_secret(sender.view_private_key(), &tx_out_public_key);
+            let (value, _blinding) = tx_out.amount.get_value(&shared_secret).unwrap();
+            value
+        };
+
+        let mut context_with_fee = |fee: u64| {
+            let tx = create_transaction_with_amount(
+                &mut ledger,
+                &tx_out,
+                &sender,
+                &recipient.default_subaddress(),
+                value - fee,
+                fee,
+                n_blocks + 1,
+                &mut rng,
+            );
+            let locally_encrypted_tx = LocallyEncryptedTx(
+                enclave
+                    .locally_encrypted_tx_cipher
+                    .lock()
+                    .unwrap()
+                    .encrypt_bytes(&mut rng, mcserial::encode(&tx)),
+            );
+            let proofs = ledger
+                .get_tx_out_proof_of_memberships(&tx.get_membership_proof_highest_indices())
+                .expect("failed getting proofs");
+            let (_, well_formed_tx_context) = enclave
+                .tx_is_well_formed(locally_encrypted_tx, block_index, proofs)
+                .unwrap();
+            (tx, well_formed_tx_context)
+        };
+
+        let (low_fee_tx, low_fee_context) = context_with_fee(BASE_FEE);
+        let (_, high_fee_context) = context_with_fee(BASE_FEE * 10);
+
+        assert_eq!(
+            low_fee_context.priority(),
+            BASE_FEE * PRIORITY_SIZE_UNIT / mcserial::encode(&low_fee_tx).len() as u64
+        );
+        assert!(high_fee_context.priority() > low_fee_context.priority());
+    }
+
+    #[test]
+    fn test_form_block_works() {
+        let mut rng = Hc128Rng::from_seed([77u8; 32]);
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+
+        // Create a valid test transaction.
+        let sender = AccountKey::random(&mut rng);
+        let recipient = AccountKey::random(&mut rng);
+
+        let mut ledger = create_ledger();
+        let n_blocks = 2;
+        initialize_ledger(&mut ledger, n_blocks, &sender, &mut rng);
+
+        // A transaction from the ledger, whose outputs will be "spent" in this test.
+        // This assumes the first transaction in the ledger has enough TxOuts.
+        let tx_stored = {
+            let mut transactions = ledger.get_transactions_by_block(0).unwrap();
+            transactions.pop().unwrap()
+        };
+
+        let input_transactions: Vec<Tx> = (0..3)
+            .map(|i| {
+                let tx_out = tx_stored.outputs[i].clone();
+
+                create_transaction(
+                    &mut ledger,
+                    &tx_out,
+                    &sender,
+                    &recipient.default_subaddress(),
+                    n_blocks + 1,
+                    &mut rng,
+                )
+            })
+            .collect();
+
+        let total_fee: u64 = input_transactions.iter().map(|tx| tx.prefix.fee).sum();
+
+        // Create WellFormedEncryptedTxs + proofs
+        let well_formed_encrypted_txs_with_proofs: Vec<_> = input_transactions
+            .iter()
+            .map(|tx| {
+                let well_formed_tx = WellFormedTx::from(tx.clone());
+                let encrypted_tx = enclave
+                    .encrypt_well_formed_tx(&well_formed_tx, &mut rng)
+                    .unwrap();
+
+                let highest_indices = well_formed_tx.tx.get_membership_proof_highest_indices();
+                let membership_proofs = ledger
+                    .get_tx_out_proof_of_memberships(&highest_indices)
+                    .expect("failed getting proof");
+                (encrypted_tx, membership_proofs)
+            })
+            .collect();
+
+        // Form block
+        let parent_block = ledger.get_block(ledger.num_blocks().unwrap() - 1).unwrap();
+
+        let (block, redacted_transactions, signature) = enclave
+            .form_block(&parent_block, &well_formed_encrypted_txs_with_proofs)
+            .unwrap();
+
+        // Verify signature.
+        assert_eq!(
+            signature.signer(),
+            &enclave
+                .ake
+                .get_identity()
+                .signing_keypair
+                .lock()
+                .unwrap()
+                .public_key()
+        );
+
+        let signature_verification_result = signature.verify(&block);
+        assert!(signature_verification_result.is_ok());
+
+        // `redacted_transactions` should include an additional transaction for the aggregate fee.
+        assert_eq!(redacted_transactions.len(), input_transactions.len() + 1);
+
+        // The zero-th RedactedTx should send a single output to the Fee recipient account.
+        let fee_minting_transaction = &redacted_transactions[0];
+        assert_eq!(fee_minting_transaction.key_images.len(), 0);
+        assert_eq!(fee_minting_transaction.outputs.len(), 1);
+        let aggregate_fee_output = &fee_minting_transaction.outputs[0];
+
+        let view_secret_key = RistrettoPrivate::try_from(&FEE_VIEW_PRIVATE_KEY).unwrap();
+        let public_address = PublicAddress::new(
+            &RistrettoPublic::try_from(&FEE_SPEND_PUBLIC_KEY).unwrap(),
+            &RistrettoPublic::from(&view_secret_key),
+        );
+
+        // The FEE address should be the recipient of the aggregate fee.
+        let fee_view_key = ViewKey::new(view_secret_key, *public_address.spend_public_key());
+        let output_target_key: RistrettoPublic =
+            RistrettoPublic::try_from(&aggregate_fee_output.target_key).unwrap();
+        let tx_public_key = RistrettoPublic::try_from(&aggregate_fee_output.public_key).unwrap();
+
+        assert!(view_key_matches_output(
+            &fee_view_key,
+            &output_target_key,
+            &tx_public_key
+        ));
+
+        // The value of the aggregate fee should equal the total value of fees in the input transaction.
+        let shared_secret = compute_shared_secret(&tx_public_key, &view_secret_key);
+        let (value, _blinding) = aggregate_fee_output
+            .amount
+            .get_value(&shared_secret)
+            .unwrap();
+        assert_eq!(value, total_fee);
+
+        // Each of the input transactions should be redacted, in priority and then tx hash order.
+        let mut input_transactions = input_transactions;
+        input_transactions.sort_by_key(|tx| (Reverse(tx_priority(tx)), tx.tx_hash()));
+        for (i, tx) in input_transactions.into_iter().enumerate() {
+            let expected = tx.redact();
+
+            assert_eq!(expected, redacted_transactions[i + 1]);
+        }
+    }
+
+    #[test]
+    // form_block should produce the same block, with txs ordered by priority descending and then
+    // tx hash ascending, however its inputs are ordered.
+    fn test_form_block_orders_txs_deterministically() {
+        let mut rng = Hc128Rng::from_seed([19u8; 32]);
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+
+        let sender = AccountKey::random(&mut rng);
+        let recipient = AccountKey::random(&mut rng);
+
+        let mut ledger = create_ledger();
+        let n_blocks = 2;
+        initialize_ledger(&mut ledger, n_blocks, &sender, &mut rng);
+
+        let tx_stored = {
+            let mut transactions = ledger.get_transactions_by_block(0).unwrap();
+            transactions.pop().unwrap()
+        };
+
+        // Two pairs of txs with identical fees and sizes, so ordering relies on the tie-break.
+        let fees = [BASE_FEE, BASE_FEE * 2, BASE_FEE, BASE_FEE * 2];
+        let input_transactions: Vec<Tx> = fees
+            .iter()
+            .enumerate()
+            .map(|(i, fee)| {
+                let tx_out = tx_stored.outputs[i].clone();
+                let tx_out_public_key = RistrettoPublic::try_from(&tx_out.public_key).unwrap();
+                let shared_secret =
+                    get_tx_out_shared_secret(sender.view_private_key(), &tx_out_public_key);
+                let (value, _blinding) = tx_out.amount.get_value(&shared_secret).unwrap();
+
+                create_transaction_with_amount(
+                    &mut ledger,
+                    &tx_out,
+                    &sender,
+                    &recipient.default_subaddress(),
+                    value - fee,
+                    *fee,
+                    n_blocks + 1,
+                    &mut rng,
+                )
+            })
+            .collect();
+
+        let well_formed_encrypted_txs_with_proofs: Vec<_> = input_transactions
+            .iter()
+            .map(|tx| {
+                let well_formed_tx = WellFormedTx::from(tx.clone());
+                let encrypted_tx = enclave
+                    .encrypt_well_formed_tx(&well_formed_tx, &mut rng)
+                    .unwrap();
+
+                let highest_indices = well_formed_tx.tx.get_membership_proof_highest_indices();
+                let membership_proofs = ledger
+                    .get_tx_out_proof_of_memberships(&highest_indices)
+                    .expect("failed getting proof");
+                (encrypted_tx, membership_proofs)
+            })
+            .collect();
+
+        let parent_block = ledger.get_block(ledger.num_blocks().unwrap() - 1).unwrap();
+        let expected = enclave
+            .form_block(&parent_block, &well_formed_encrypted_txs_with_proofs)
+            .unwrap();
+
+        // Redacted txs follow the priority and tx hash order, after the aggregate fee output.
+        let mut sorted_transactions = input_transactions.clone();
+        sorted_transactions.sort_by_key(|tx| (Reverse(tx_priority(tx)), tx.tx_hash()));
+        assert_eq!(sorted_transactions[0].prefix.fee, BASE_FEE * 2);
+        assert_eq!(sorted_transactions[1].prefix.fee, BASE_FEE * 2);
+        assert_eq!(
+            tx_priority(&sorted_transactions[0]),
+            tx_priority(&sorted_transactions[1])
+        );
+        assert!(sorted_transactions[0].tx_hash() < sorted_transactions[1].tx_hash());
+        let expected_redacted: Vec<RedactedTx> =
+            sorted_transactions.iter().map(|tx| tx.redact()).collect();
+        assert_eq!(&expected.1[1..], &expected_redacted[..]);
+
+        // Any permutation of the inputs forms the same block.
+        let mut shuffled = well_formed_encrypted_txs_with_proofs.clone();
+        for _ in 0..shuffled.len() {
+            shuffled.rotate_left(1);
+            forget_signing_height(&enclave);
+            assert_eq!(enclave.form_block(&parent_block, &shuffled), Ok(expected.clone()));
+        }
+        shuffled.reverse();
+        forget_signing_height(&enclave);
+        assert_eq!(enclave.form_block(&parent_block, &shuffled), Ok(expected));
+    }
+
+    /// Lets a test form another block at a height the enclave already signed.
+    fn forget_signing_height(enclave: &SgxConsensusEnclave) {
+        *enclave.signing_height.lock().unwrap() = 0;
+    }
+
+    /// An empty block one below `block`, with an id matching its contents.
+    fn block_below(block: &Block) -> Block {
+        Block::new(
+            block.version,
+            &block.parent_id,
+            block.index - 1,
+            &block.root_element,
+            &[],
+        )
+    }
+
+    /// Creates one well-formed, encrypted transaction paying each of `fees`, spending outputs
+    /// of a fresh ledger, with the block they should be formed on top of.
+    fn form_block_candidates(
+        enclave: &SgxConsensusEnclave,
+        fees: &[u64],
+        rng: &mut Hc128Rng,
+    ) -> (
+        Block,
+        Vec<Tx>,
+        Vec<(WellFormedEncryptedTx, Vec<TxOutMembershipProof>)>,
+    ) {
+        let sender = AccountKey::random(rng);
+        let recipient = AccountKey::random(rng);
+
+        let mut ledger = create_ledger();
+        let n_blocks = 2;
+        initialize_ledger(&mut ledger, n_blocks, &sender, rng);
+
+        let tx_stored = {
+            let mut transactions = ledger.get_transactions_by_block(0).unwrap();
+            transactions.pop().unwrap()
+        };
+
+        let input_transactions: Vec<Tx> = fees
+            .iter()
+            .enumerate()
+            .map(|(i, fee)| {
+                let tx_out = tx_stored.outputs[i].clone();
+                let tx_out_public_key = RistrettoPublic::try_from(&tx_out.public_key).unwrap();
+                let shared_secret =
+                    get_tx_out_shared_secret(sender.view_private_key(), &tx_out_public_key);
+                let (value, _blinding) = tx_out.amount.get_value(&shared_secret).unwrap();
+
+                create_transaction_with_amount(
+                    &mut ledger,
+                    &tx_out,
+                    &sender,
+                    &recipient.default_subaddress(),
+                    value - fee,
+                    *fee,
+                    n_blocks + 1,
+                    rng,
+                )
+            })
+            .collect();
+
+        let well_formed_encrypted_txs_with_proofs: Vec<_> = input_transactions
+            .iter()
+            .map(|tx| {
+                let well_formed_tx = WellFormedTx::from(tx.clone());
+                let encrypted_tx = enclave
+                    .encrypt_well_formed_tx(&well_formed_tx, rng)
+                    .unwrap();
+
+                let highest_indices = well_formed_tx.tx.get_membership_proof_highest_indices();
+                let membership_proofs = ledger
+                    .get_tx_out_proof_of_memberships(&highest_indices)
+                    .expect("failed getting proof");
+                (encrypted_tx, membership_proofs)
+            })
+            .collect();
+
+        let parent_block = ledger.get_block(ledger.num_blocks().unwrap() - 1).unwrap();
+        (
+            parent_block,
+            input_transactions,
+            well_formed_encrypted_txs_with_proofs,
+        )
+    }
+
+    #[test]
+    // Candidates beyond `max_txs` are left out of the block and reported as excluded, and can be
+    // formed into a later block.
+    fn test_form_block_with_report_excludes_txs_over_max_txs() {
+        let mut rng = Hc128Rng::from_seed([33u8; 32]);
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        enclave
+            .set_block_limits(BlockLimits {
+                max_txs: 2,
+                ..Default::default()
+            })
+            .unwrap();
+
+        let fees = [BASE_FEE, BASE_FEE * 4, BASE_FEE * 2, BASE_FEE * 3];
+        let (parent_block, input_transactions, well_formed_encrypted_txs_with_proofs) =
+            form_block_candidates(&enclave, &fees, &mut rng);
+
+        let (block, redacted_txs, signature, report) = enclave
+            .form_block_with_report(&parent_block, &well_formed_encrypted_txs_with_proofs)
+            .unwrap();
+
+        // The two highest-priority transactions, which pay the highest fees, are included after
+        // the aggregate fee output.
+        let excluded = InclusionStatus::Excluded(ExclusionReason::BlockFull);
+        let expected_report = vec![
+            (input_transactions[1].tx_hash(), InclusionStatus::Included),
+            (input_transactions[3].tx_hash(), InclusionStatus::Included),
+            (input_transactions[2].tx_hash(), excluded),
+            (input_transactions[0].tx_hash(), excluded),
+        ];
+        assert_eq!(report, expected_report);
+        assert_eq!(
+            &redacted_txs[1..],
+            &[
+                input_transactions[1].redact(),
+                input_transactions[3].redact()
+            ][..]
+        );
+
+        // `form_block` forms the same block, without the report.
+        forget_signing_height(&enclave);
+        assert_eq!(
+            enclave.form_block(&parent_block, &well_formed_encrypted_txs_with_proofs),
+            Ok((block, redacted_txs, signature))
+        );
+
+        // The excluded transactions can be proposed again and fit in a block of their own.
+        let requeued = vec![
+            well_formed_encrypted_txs_with_proofs[0].clone(),
+            well_formed_encrypted_txs_with_proofs[2].clone(),
+        ];
+        forget_signing_height(&enclave);
+        let (_block, _redacted_txs, _signature, report) = enclave
+            .form_block_with_report(&parent_block, &requeued)
+            .unwrap();
+        assert!(report
+            .iter()
+            .all(|(_tx_hash, status)| *status == InclusionStatus::Included));
+    }
+
+    #[test]
+    // Proofs against the ledger a block was formed on are current at that block's index, and
+    // stale above it.
+    fn test_tx_is_well_formed_rejects_stale_membership_proofs() {
+        let mut rng = Hc128Rng::from_seed([65u8; 32]);
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        let (parent_block, input_transactions, well_formed_encrypted_txs_with_proofs) =
+            form_block_candidates(&enclave, &[BASE_FEE, BASE_FEE], &mut rng);
+        let (block, _redacted_txs, _signature) = enclave
+            .form_block(&parent_block, &well_formed_encrypted_txs_with_proofs[..1])
+            .unwrap();
+
+        let locally_encrypted_tx = LocallyEncryptedTx(
+            enclave
+                .locally_encrypted_tx_cipher
+                .lock()
+                .unwrap()
+                .encrypt_bytes(&mut rng, mcserial::encode(&input_transactions[1])),
+        );
+        let (_encrypted_tx, proofs) = &well_formed_encrypted_txs_with_proofs[1];
+
+        assert!(enclave
+            .tx_is_well_formed(locally_encrypted_tx.clone(), block.index, proofs.clone())
+            .is_ok());
+        assert_eq!(
+            enclave.tx_is_well_formed(locally_encrypted_tx, block.index + 1, proofs.clone()),
+            Err(Error::StaleMembershipProof { index: block.index })
+        );
+    }
+
+    #[test]
+    // Excluded candidates are left out of the block, which is otherwise formed as by `form_block`.
+    fn test_form_block_excluding() {
+        let mut rng = Hc128Rng::from_seed([59u8; 32]);
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+
+        let fees = [BASE_FEE, BASE_FEE * 2, BASE_FEE * 3];
+        let (parent_block, input_transactions, well_formed_encrypted_txs_with_proofs) =
+            form_block_candidates(&enclave, &fees, &mut rng);
+
+        let exclude = [input_transactions[1].tx_hash()];
+        let (block, redacted_txs, signature) = enclave
+            .form_block_excluding(&parent_block, &well_formed_encrypted_txs_with_proofs, &exclude)
+            .unwrap();
+        assert!(!redacted_txs.contains(&input_transactions[1].redact()));
+        assert_eq!(
+            &redacted_txs[1..],
+            &[
+                input_transactions[2].redact(),
+                input_transactions[0].redact()
+            ][..]
+        );
+
+        // Leaving the excluded transaction out of the candidates forms the same block.
+        let remaining = vec![
+            well_formed_encrypted_txs_with_proofs[0].clone(),
+            well_formed_encrypted_txs_with_proofs[2].clone(),
+        ];
+        forget_signing_height(&enclave);
+        assert_eq!(
+            enclave.form_block(&parent_block, &remaining),
+            Ok((block, redacted_txs, signature))
+        );
+    }
+
+    #[test]
+    // An empty block chains off its parent at the current ledger root, is signed by the enclave
+    // like any other block at its height, and can be appended to the ledger.
+    fn test_form_empty_block() {
+        let mut rng = Hc128Rng::from_seed([84u8; 32]);
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        let sender = AccountKey::random(&mut rng);
+        let mut ledger = create_ledger();
+        let n_blocks = 2;
+        let blocks = initialize_ledger(&mut ledger, n_blocks, &sender, &mut rng);
+        let parent_block = blocks.last().unwrap().clone();
+
+        let root_proof = ledger.get_tx_out_proof_of_memberships(&[0]).unwrap()[0].clone();
+        let (block, redacted_txs, signature) =
+            enclave.form_empty_block(&parent_block, &root_proof).unwrap();
+        assert_eq!(block.parent_id, parent_block.id);
+        assert_eq!(block.index, parent_block.index + 1);
+        assert_eq!(&block.root_element, root_proof.elements.last().unwrap());
+        assert_eq!(signature.signer(), &enclave.get_signer().unwrap());
+        assert!(signature.verify(&block).is_ok());
+
+        // The block holds nothing but a zero-value fee output.
+        assert_eq!(redacted_txs.len(), 1);
+        assert!(redacted_txs[0].key_images.is_empty());
+        assert_eq!(enclave.block_total_fees(&block, &redacted_txs), Ok(0));
+
+        ledger
+            .append_block(&block, &redacted_txs, Some(&signature))
+            .unwrap();
+        assert_eq!(ledger.num_blocks().unwrap(), n_blocks + 1);
+
+        // Forming it again gives the same block, once the height is free to be signed again.
+        assert_eq!(
+            enclave.form_empty_block(&parent_block, &root_proof),
+            Err(Error::NonMonotonicBlock {
+                attempted: block.index,
+                last: block.index
+            })
+        );
+        forget_signing_height(&enclave);
+        assert_eq!(
+            enclave.form_empty_block(&parent_block, &root_proof).unwrap().0,
+            block
+        );
+
+        // A second empty block appends on top of the first, with an output of its own.
+        forget_signing_height(&enclave);
+        let root_proof = ledger.get_tx_out_proof_of_memberships(&[0]).unwrap()[0].clone();
+        let (next_block, next_redacted_txs, next_signature) =
+            enclave.form_empty_block(&block, &root_proof).unwrap();
+        assert_ne!(
+            next_redacted_txs[0].outputs[0].public_key,
+            redacted_txs[0].outputs[0].public_key
+        );
+        ledger
+            .append_block(&next_block, &next_redacted_txs, Some(&next_signature))
+            .unwrap();
+        assert_eq!(ledger.num_blocks().unwrap(), n_blocks + 2);
+    }
+
+    #[test]
+    #[cfg(feature = "profiling")]
+    // Forming a block records its timings, which are unavailable before any timed call.
+    fn test_last_call_timings_after_form_block() {
+        let mut rng = Hc128Rng::from_seed([80u8; 32]);
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        assert_eq!(enclave.last_call_timings(), Err(Error::ProfilingUnavailable));
+
+        let (parent_block, _input_transactions, well_formed_encrypted_txs_with_proofs) =
+            form_block_candidates(&enclave, &[BASE_FEE], &mut rng);
+        enclave
+            .form_block(&parent_block, &well_formed_encrypted_txs_with_proofs)
+            .unwrap();
+        let timings = enclave.last_call_timings().unwrap();
+        assert_eq!(timings.operation, TimedOperation::FormBlock);
+        assert!(timings.end_cycles >= timings.start_cycles);
+        assert!(timings.cycles() > 0);
+    }
+
+    #[test]
+    #[cfg(not(feature = "profiling"))]
+    // Builds without the `profiling` feature never record timings.
+    fn test_last_call_timings_unavailable() {
+        let mut rng = Hc128Rng::from_seed([81u8; 32]);
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+
+        let (parent_block, _input_transactions, well_formed_encrypted_txs_with_proofs) =
+            form_block_candidates(&enclave, &[BASE_FEE], &mut rng);
+        enclave
+            .form_block(&parent_block, &well_formed_encrypted_txs_with_proofs)
+            .unwrap();
+        assert_eq!(enclave.last_call_timings(), Err(Error::ProfilingUnavailable));
+    }
+
+    #[test]
+    // Relayed txs untrusted already knows fail with `Error::KnownTx` instead of being re-encrypted,
+    // while the new ones are handled as usual.
+    fn test_peer_tx_contexts_skips_known_txs() {
+        let mut rng = Hc128Rng::from_seed([60u8; 32]);
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        let (_parent_block, input_transactions, _well_formed_encrypted_txs_with_proofs) =
+            form_block_candidates(&enclave, &[BASE_FEE, BASE_FEE, BASE_FEE], &mut rng);
+
+        let relayed: Vec<Vec<u8>> = input_transactions.iter().map(mcserial::encode).collect();
+        let known_hashes = [input_transactions[1].tx_hash()];
+        let results = enclave.peer_tx_contexts(relayed, &known_hashes);
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(
+            results[0].as_ref().unwrap().tx_hash,
+            input_transactions[0].tx_hash()
+        );
+        assert_eq!(
+            results[1],
+            Err(Error::KnownTx(input_transactions[1].tx_hash()))
+        );
+        assert_eq!(
+            results[2].as_ref().unwrap().tx_hash,
+            input_transactions[2].tx_hash()
+        );
+    }
+
+    #[test]
+    // Once the next candidate would take the block over `max_bytes`, it and every candidate
+    // ordered after it are left out.
+    fn test_form_block_with_report_excludes_txs_over_max_bytes() {
+        let mut rng = Hc128Rng::from_seed([34u8; 32]);
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+
+        let fees = [BASE_FEE * 3, BASE_FEE, BASE_FEE * 2];
+        let (parent_block, input_transactions, well_formed_encrypted_txs_with_proofs) =
+            form_block_candidates(&enclave, &fees, &mut rng);
+
+        // Leave room for the two highest-priority transactions, but not quite all three.
+        let encoded_len = |tx: &Tx| tx.encoded_len() as u32;
+        let max_bytes = encoded_len(&input_transactions[0]) + encoded_len(&input_transactions[2]);
+        enclave
+            .set_block_limits(BlockLimits {
+                max_bytes,
+                ..Default::default()
+            })
+            .unwrap();
+
+        let (_block, redacted_txs, _signature, report) = enclave
+            .form_block_with_report(&parent_block, &well_formed_encrypted_txs_with_proofs)
+            .unwrap();
+        assert_eq!(
+            report,
+            vec![
+                (input_transactions[0].tx_hash(), InclusionStatus::Included),
+                (input_transactions[2].tx_hash(), InclusionStatus::Included),
+                (
+                    input_transactions[1].tx_hash(),
+                    InclusionStatus::Excluded(ExclusionReason::BlockTooLarge)
+                ),
+            ]
+        );
+        assert_eq!(redacted_txs.len(), 3);
+
+        // One byte less, and only the highest-priority transaction fits.
+        forget_signing_height(&enclave);
+        enclave
+            .set_block_limits(BlockLimits {
+                max_bytes: max_bytes - 1,
+                ..Default::default()
+            })
+            .unwrap();
+        let (_block, _redacted_txs, _signature, report) = enclave
+            .form_block_with_report(&parent_block, &well_formed_encrypted_txs_with_proofs)
+            .unwrap();
+        let statuses: Vec<InclusionStatus> =
+            report.into_iter().map(|(_tx_hash, status)| status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                InclusionStatus::Included,
+                InclusionStatus::Excluded(ExclusionReason::BlockTooLarge),
+                InclusionStatus::Excluded(ExclusionReason::BlockTooLarge),
+            ]
+        );
+
+        // Restoring the default limits includes everything again.
+        forget_signing_height(&enclave);
+        enclave.set_block_limits(BlockLimits::default()).unwrap();
+        let (_block, redacted_txs, _signature) = enclave
+            .form_block(&parent_block, &well_formed_encrypted_txs_with_proofs)
+            .unwrap();
+        assert_eq!(redacted_txs.len(), fees.len() + 1);
+    }
+
+    #[test]
+    // Limits which leave out every candidate fail with an error of their own, rather than with
+    // one about the missing membership proofs of an empty block.
+    fn test_form_block_rejects_limits_excluding_every_tx() {
+        let mut rng = Hc128Rng::from_seed([85u8; 32]);
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        let (parent_block, input_transactions, well_formed_encrypted_txs_with_proofs) =
+            form_block_candidates(&enclave, &[BASE_FEE * 2, BASE_FEE * 3], &mut rng);
+
+        // No room for any transaction.
+        enclave
+            .set_block_limits(BlockLimits {
+                max_txs: 0,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(
+            enclave.form_block(&parent_block, &well_formed_encrypted_txs_with_proofs),
+            Err(Error::NoTxFitsBlockLimits {
+                max_txs: 0,
+                max_bytes: BlockLimits::default().max_bytes,
+            })
+        );
+
+        // No room for the highest-priority transaction, which goes first.
+        let max_bytes = input_transactions[1].encoded_len() as u32 - 1;
+        enclave
+            .set_block_limits(BlockLimits {
+                max_bytes,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(
+            enclave.form_block(&parent_block, &well_formed_encrypted_txs_with_proofs),
+            Err(Error::NoTxFitsBlockLimits {
+                max_txs: BlockLimits::default().max_txs,
+                max_bytes,
+            })
+        );
+    }
+
+    #[test]
+    // Block fees are summed with checked arithmetic. A candidate set whose fees exceed `u64::MAX`
+    // can not pass validation, so the summation is exercised directly.
+    fn test_aggregate_fee_rejects_overflow() {
+        let mut rng = Hc128Rng::from_seed([62u8; 32]);
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        let (_parent_block, input_transactions, _well_formed_encrypted_txs_with_proofs) =
+            form_block_candidates(&enclave, &[BASE_FEE, 2 * BASE_FEE], &mut rng);
+        assert_eq!(aggregate_fee(&input_transactions), Ok(3 * BASE_FEE));
+        assert_eq!(aggregate_fee(&[]), Ok(0));
+
+        let mut transactions = input_transactions;
+        transactions[0].prefix.fee = u64::MAX;
+        transactions[1].prefix.fee = 1;
+        assert_eq!(aggregate_fee(&transactions), Err(Error::FeeOverflow));
+
+        transactions[1].prefix.fee = 0;
+        assert_eq!(aggregate_fee(&transactions), Ok(u64::MAX));
+    }
+
+    #[test]
+    // Included transactions can be looked up by hash, while excluded and unknown ones can not.
+    fn test_included_in_block() {
+        let mut rng = Hc128Rng::from_seed([66u8; 32]);
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        enclave
+            .set_block_limits(BlockLimits {
+                max_txs: 1,
+                ..Default::default()
+            })
+            .unwrap();
+
+        let (parent_block, input_transactions, well_formed_encrypted_txs_with_proofs) =
+            form_block_candidates(&enclave, &[BASE_FEE, BASE_FEE * 2], &mut rng);
+        let low_fee_hash = input_transactions[0].tx_hash();
+        let high_fee_hash = input_transactions[1].tx_hash();
+        assert_eq!(enclave.included_in_block(&high_fee_hash), Ok(None));
+
+        // Only the higher-fee transaction fits in the first block.
+        let grandparent_block = block_below(&parent_block);
+        let (block, _redacted_txs, _signature) = enclave
+            .form_block(&grandparent_block, &well_formed_encrypted_txs_with_proofs)
+            .unwrap();
+        assert_eq!(enclave.included_in_block(&high_fee_hash), Ok(Some(block.index)));
+        assert_eq!(enclave.included_in_block(&low_fee_hash), Ok(None));
+
+        // The excluded transaction lands in the next block, and the first one stays put.
+        let (next_block, _redacted_txs, _signature) = enclave
+            .form_block(&parent_block, &well_formed_encrypted_txs_with_proofs[..1])
+            .unwrap();
+        assert_eq!(enclave.included_in_block(&low_fee_hash), Ok(Some(next_block.index)));
+        assert_eq!(enclave.included_in_block(&high_fee_hash), Ok(Some(block.index)));
+
+        assert_eq!(enclave.included_in_block(&TxHash::default()), Ok(None));
+    }
+
+    #[test]
+    // The key images spent by formed blocks are reported from the requested block on, oldest
+    // block first.
+    fn test_recent_key_images() {
+        let mut rng = Hc128Rng::from_seed([79u8; 32]);
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        enclave
+            .set_block_limits(BlockLimits {
+                max_txs: 1,
+                ..Default::default()
+            })
+            .unwrap();
+
+        let (parent_block, input_transactions, well_formed_encrypted_txs_with_proofs) =
+            form_block_candidates(&enclave, &[BASE_FEE, BASE_FEE * 2], &mut rng);
+        let low_fee_key_images = input_transactions[0].key_images();
+        let high_fee_key_images = input_transactions[1].key_images();
+        assert_eq!(enclave.recent_key_images(0), Ok(vec![]));
+
+        // Only the higher-fee transaction fits in the first block.
+        let grandparent_block = block_below(&parent_block);
+        let (block, _redacted_txs, _signature) = enclave
+            .form_block(&grandparent_block, &well_formed_encrypted_txs_with_proofs)
+            .unwrap();
+        assert_eq!(enclave.recent_key_images(0), Ok(high_fee_key_images.clone()));
+
+        let (next_block, _redacted_txs, _signature) = enclave
+            .form_block(&parent_block, &well_formed_encrypted_txs_with_proofs[..1])
+            .unwrap();
+        let all_key_images: Vec<KeyImage> = high_fee_key_images
+            .iter()
+            .chain(low_fee_key_images.iter())
+            .copied()
+            .collect();
+        assert_eq!(enclave.recent_key_images(0), Ok(all_key_images.clone()));
+        assert_eq!(enclave.recent_key_images(block.index), Ok(all_key_images));
+        assert_eq!(
+            enclave.recent_key_images(next_block.index),
+            Ok(low_fee_key_images)
+        );
+        assert_eq!(enclave.recent_key_images(next_block.index + 1), Ok(vec![]));
+    }
+
+    #[test]
+    // A transaction redacted on its own matches its entry in the block formed from it.
+    fn test_redact_single_matches_form_block() {
+        let mut rng = Hc128Rng::from_seed([70u8; 32]);
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        let (parent_block, input_transactions, well_formed_encrypted_txs_with_proofs) =
+            form_block_candidates(&enclave, &[BASE_FEE, BASE_FEE * 3, BASE_FEE * 2], &mut rng);
+
+        let singles: Vec<RedactedTx> = well_formed_encrypted_txs_with_proofs
+            .iter()
+            .map(|(encrypted_tx, _proofs)| enclave.redact_single(encrypted_tx).unwrap())
+            .collect();
+        for (single, tx) in singles.iter().zip(input_transactions) {
+            assert_eq!(single, &tx.redact());
+        }
+
+        // Transactions follow the aggregate fee output, highest priority first.
+        let (_block, redacted_txs, _signature) = enclave
+            .form_block(&parent_block, &well_formed_encrypted_txs_with_proofs)
+            .unwrap();
+        assert_eq!(
+            &redacted_txs[1..],
+            &[
+                singles[1].clone(),
+                singles[2].clone(),
+                singles[0].clone()
+            ][..]
+        );
+    }
+
+    #[test]
+    // A block whose fees add up to more than the cap is not formed, while one at the cap is.
+    fn test_form_block_enforces_max_fee() {
+        let mut rng = Hc128Rng::from_seed([83u8; 32]);
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        let (parent_block, _input_transactions, well_formed_encrypted_txs_with_proofs) =
+            form_block_candidates(&enclave, &[BASE_FEE, BASE_FEE * 2], &mut rng);
+
+        enclave
+            .set_block_limits(BlockLimits {
+                max_fee: BASE_FEE * 3 - 1,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(
+            enclave.form_block(&parent_block, &well_formed_encrypted_txs_with_proofs),
+            Err(Error::BlockFeeCapExceeded {
+                total_fee: BASE_FEE * 3,
+                max_fee: BASE_FEE * 3 - 1,
+            })
+        );
+
+        enclave
+            .set_block_limits(BlockLimits {
+                max_fee: BASE_FEE * 3,
+                ..Default::default()
+            })
+            .unwrap();
+        let (block, redacted_txs, _signature) = enclave
+            .form_block(&parent_block, &well_formed_encrypted_txs_with_proofs)
+            .unwrap();
+        assert_eq!(
+            enclave.block_total_fees(&block, &redacted_txs),
+            Ok(BASE_FEE * 3)
+        );
+    }
+
+    #[test]
+    // A transaction left out of a block can be re-queued with the context recovered from it,
+    // which is the one `tx_is_well_formed` gives for it.
+    fn test_context_of_excluded_tx() {
+        let mut rng = Hc128Rng::from_seed([82u8; 32]);
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        enclave
+            .set_block_limits(BlockLimits {
+                max_txs: 1,
+                ..Default::default()
+            })
+            .unwrap();
+
+        let (parent_block, input_transactions, well_formed_encrypted_txs_with_proofs) =
+            form_block_candidates(&enclave, &[BASE_FEE, BASE_FEE * 2], &mut rng);
+        let (_block, _redacted_txs, _signature, report) = enclave
+            .form_block_with_report(&parent_block, &well_formed_encrypted_txs_with_proofs)
+            .unwrap();
+        assert_eq!(
+            report[1],
+            (
+                input_transactions[0].tx_hash(),
+                InclusionStatus::Excluded(ExclusionReason::BlockFull)
+            )
+        );
+
+        let (excluded_tx, _proofs) = &well_formed_encrypted_txs_with_proofs[0];
+        let context = enclave.context_of(excluded_tx).unwrap();
+        assert_eq!(context, WellFormedTxContext::from(&input_transactions[0]));
+        assert_eq!(context.tx_hash(), &input_transactions[0].tx_hash());
+        assert_eq!(context.fee(), BASE_FEE);
+    }
+
+    #[test]
+    // The fee recovered from a formed block is the sum of the fees its transactions paid.
+    fn test_block_total_fees() {
+        let mut rng = Hc128Rng::from_seed([74u8; 32]);
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        let fees = [BASE_FEE, BASE_FEE * 3, BASE_FEE * 2];
+        let (parent_block, _input_transactions, well_formed_encrypted_txs_with_proofs) =
+            form_block_candidates(&enclave, &fees, &mut rng);
+
+        let (block, redacted_txs, _signature) = enclave
+            .form_block(&parent_block, &well_formed_encrypted_txs_with_proofs)
+            .unwrap();
+        assert_eq!(
+            enclave.block_total_fees(&block, &redacted_txs),
+            Ok(fees.iter().sum())
+        );
+
+        // Transactions from another block are rejected.
+        assert_eq!(
+            enclave.block_total_fees(&block, &redacted_txs[1..]),
+            Err(Error::BlockContentsMismatch)
+        );
+        assert_eq!(
+            enclave.block_total_fees(&parent_block, &redacted_txs),
+            Err(Error::BlockContentsMismatch)
+        );
+    }
+
+    #[test]
+    // Consecutive blocks are signed, and each one advances the signing height.
+    fn test_form_block_advances_signing_height() {
+        let mut rng = Hc128Rng::from_seed([44u8; 32]);
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        assert_eq!(enclave.current_signing_height(), Ok(0));
+
+        let (parent_block, _input_transactions, well_formed_encrypted_txs_with_proofs) =
+            form_block_candidates(&enclave, &[BASE_FEE], &mut rng);
+        let grandparent_block = block_below(&parent_block);
+
+        let (block, _redacted_txs, _signature) = enclave
+            .form_block(&grandparent_block, &well_formed_encrypted_txs_with_proofs)
+            .unwrap();
+        assert_eq!(block.index, parent_block.index);
+        assert_eq!(enclave.current_signing_height(), Ok(parent_block.index));
+
+        let (block, _redacted_txs, _signature) = enclave
+            .form_block(&parent_block, &well_formed_encrypted_txs_with_proofs)
+            .unwrap();
+        assert_eq!(block.index, parent_block.index + 1);
+        assert_eq!(enclave.current_signing_height(), Ok(parent_block.index + 1));
+    }
+
+    #[test]
+    // Once a block is signed, neither another block at its height nor an older one is signed.
+    fn test_form_block_rejects_non_monotonic_block() {
+        let mut rng = Hc128Rng::from_seed([45u8; 32]);
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+
+        let (parent_block, _input_transactions, well_formed_encrypted_txs_with_proofs) =
+            form_block_candidates(&enclave, &[BASE_FEE, BASE_FEE * 2], &mut rng);
+        let signed_height = parent_block.index + 1;
+        enclave
+            .form_block(&parent_block, &well_formed_encrypted_txs_with_proofs)
+            .unwrap();
+
+        // A different block at the same height would be an equivocation.
+        assert_eq!(
+            enclave.form_block(&parent_block, &well_formed_encrypted_txs_with_proofs[..1]),
+            Err(Error::NonMonotonicBlock {
+                attempted: signed_height,
+                last: signed_height,
+            })
+        );
+
+        // Every variant refuses to go back to an older height.
+        let grandparent_block = block_below(&parent_block);
+        assert_eq!(
+            enclave.form_block_with_report(
+                &grandparent_block,
+                &well_formed_encrypted_txs_with_proofs
+            ),
+            Err(Error::NonMonotonicBlock {
+                attempted: parent_block.index,
+                last: signed_height,
+            })
+        );
+
+        // Refused blocks leave the signing height alone.
+        assert_eq!(enclave.current_signing_height(), Ok(signed_height));
+    }
+
+    #[test]
+    // A parent block whose id does not match its contents is refused before anything is signed.
+    fn test_form_block_rejects_broken_chain() {
+        let mut rng = Hc128Rng::from_seed([46u8; 32]);
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+
+        let (parent_block, _input_transactions, well_formed_encrypted_txs_with_proofs) =
+            form_block_candidates(&enclave, &[BASE_FEE], &mut rng);
+
+        // The id of another block.
+        let mismatched_id = Block {
+            id: parent_block.parent_id.clone(),
+            ..parent_block.clone()
+        };
+        assert_eq!(
+            enclave.form_block(&mismatched_id, &well_formed_encrypted_txs_with_proofs),
+            Err(Error::BrokenChain)
+        );
+
+        // The right id, attached to different contents.
+        let mismatched_index = Block {
+            index: parent_block.index + 1,
+            ..parent_block.clone()
+        };
+        assert_eq!(
+            enclave.form_block_with_report(
+                &mismatched_index,
+                &well_formed_encrypted_txs_with_proofs
+            ),
+            Err(Error::BrokenChain)
+        );
+        assert_eq!(enclave.current_signing_height(), Ok(0));
+
+        // The genuine parent is accepted.
+        assert!(enclave
+            .form_block(&parent_block, &well_formed_encrypted_txs_with_proofs)
+            .is_ok());
+    }
+
+    #[test]
+    // The estimated cost of forming a block grows linearly with the number of candidates, and
+    // covers at least the candidates themselves.
+    fn test_estimate_form_block_cost_scales_with_candidates() {
+        let mut rng = Hc128Rng::from_seed([54u8; 32]);
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        assert_eq!(enclave.estimate_form_block_cost(&[]), Ok(0));
+
+        let (_parent_block, _input_transactions, well_formed_encrypted_txs_with_proofs) =
+            form_block_candidates(&enclave, &[BASE_FEE], &mut rng);
+        let (encrypted_tx, proofs) = &well_formed_encrypted_txs_with_proofs[0];
+        let single_cost = enclave
+            .estimate_form_block_cost(&well_formed_encrypted_txs_with_proofs)
+            .unwrap();
+        assert!(single_cost > encrypted_tx.encrypted_len());
+
+        for count in &[2, 10, 100] {
+            let candidates = vec![(encrypted_tx.clone(), proofs.clone()); *count];
+            assert_eq!(
+                enclave.estimate_form_block_cost(&candidates),
+                Ok(single_cost * count)
+            );
+        }
+    }
+
+    #[test]
+    // The advertised block version range is non-empty, and versions above it are rejected.
+    fn test_block_version_range() {
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        let (min, max) = enclave.block_version_range().unwrap();
+        assert!(min <= max);
+        assert!(min <= BLOCK_VERSION && BLOCK_VERSION <= max);
+
+        let parent_block = Block::new_origin_block(&[]);
+        assert_eq!(
+            enclave.form_block_versioned(&parent_block, &[], max + 1),
+            Err(Error::UnsupportedBlockVersion(max + 1))
+        );
+    }
+
+    #[test]
+    /// form_block_versioned should form blocks at the current version and reject any other.
+    fn test_form_block_versioned() {
+        let mut rng = Hc128Rng::from_seed([77u8; 32]);
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+
+        let sender = AccountKey::random(&mut rng);
+        let recipient = AccountKey::random(&mut rng);
+
+        let mut ledger = create_ledger();
+        let n_blocks = 2;
+        initialize_ledger(&mut ledger, n_blocks, &sender, &mut rng);
+
+        let tx_stored = {
+            let mut transactions = ledger.get_transactions_by_block(0).unwrap();
+            transactions.pop().unwrap()
+        };
+
+        let tx = create_transaction(
+            &mut ledger,
+            &tx_stored.outputs[0],
+            &sender,
+            &recipient.default_subaddress(),
+            n_blocks + 1,
+            &mut rng,
+        );
+
+        let well_formed_tx = WellFormedTx::from(tx);
+        let encrypted_tx = enclave
+            .encrypt_well_formed_tx(&well_formed_tx, &mut rng)
+            .unwrap();
+        let highest_indices = well_formed_tx.tx.get_membership_proof_highest_indices();
+        let membership_proofs = ledger
+            .get_tx_out_proof_of_memberships(&highest_indices)
+            .expect("failed getting proof");
+        let well_formed_encrypted_txs_with_proofs = vec![(encrypted_tx, membership_proofs)];
+
+        let parent_block = ledger.get_block(ledger.num_blocks().unwrap() - 1).unwrap();
+
+        // Forming a block at the current version matches `form_block`.
+        let (block, redacted_transactions, signature) = enclave
+            .form_block_versioned(
+                &parent_block,
+                &well_formed_encrypted_txs_with_proofs,
+                BLOCK_VERSION,
+            )
+            .unwrap();
+        assert_eq!(block.version, BLOCK_VERSION);
+        assert!(block.is_block_id_valid());
+        assert!(signature.verify(&block).is_ok());
+
+        forget_signing_height(&enclave);
+        let (expected_block, expected_redacted_transactions, _signature) = enclave
+            .form_block(&parent_block, &well_formed_encrypted_txs_with_proofs)
+            .unwrap();
+        assert_eq!(block, expected_block);
+        assert_eq!(redacted_transactions, expected_redacted_transactions);
+
+        // Any other version is rejected.
+        let unsupported_version = BLOCK_VERSION + 1;
+        assert_eq!(
+            enclave.form_block_versioned(
+                &parent_block,
+                &well_formed_encrypted_txs_with_proofs,
+                unsupported_version,
+            ),
+            Err(Error::UnsupportedBlockVersion(unsupported_version))
+        );
+    }
+
+    #[test]
+    /// form_block should return an error if the input transactions contain a double-spend.
+    fn test_form_block_prevents_duplicate_spend() {
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        let mut rng = Hc128Rng::from_seed([77u8; 32]);
+
+        // Initialize a ledger. `sender` is the owner of all outputs in the initial ledger.
+        let sender = AccountKey::random(&mut rng);
+        let mut ledger = create_ledger();
+        let n_blocks = 3;
+        initialize_ledger(&mut ledger, n_blocks, &sender, &mut rng);
+
+        // Create a few transactions from `sender` to `recipient`.
+        let num_transactions = 5;
+        let recipient = AccountKey::random(&mut rng);
+
+        // The first block contains a single transaction with MIN_RING_SIZE outputs.
+        let block_zero_transactions = ledger.get_transactions_by_block(0).unwrap();
+        let block_zero_redacted_tx = block_zero_transactions.get(0).unwrap();
+
+        let mut new_transactions = Vec::new();
+        for i in 0..num_transactions {
+            let tx_out = &block_zero_redacted_tx.outputs[i];
+
+            let tx = create_transaction(
+                &mut ledger,
+                tx_out,
+                &sender,
+                &recipient.default_subaddress(),
+                n_blocks + 1,
+                &mut rng,
+            );
+            new_transactions.push(tx);
+        }
+
+        // Create another transaction that spends the zero^th output in block zero.
+        let double_spend = {
+            let tx_out = &block_zero_redacted_tx.outputs[0];
+
+            create_transaction(
+                &mut ledger,
+                tx_out,
+                &sender,
+                &recipient.default_subaddress(),
+                n_blocks + 1,
+                &mut rng,
+            )
+        };
+        new_transactions.push(double_spend);
+
+        // Create WellFormedEncryptedTxs + proofs
+        let well_formed_encrypted_txs_with_proofs: Vec<_> = new_transactions
+            .iter()
+            .map(|tx| {
+                let well_formed_tx = WellFormedTx::from(tx.clone());
+                let encrypted_tx = enclave
+                    .encrypt_well_formed_tx(&well_formed_tx, &mut rng)
+                    .unwrap();
+
+                let highest_indices = well_formed_tx.tx.get_membership_proof_highest_indices();
+                let membership_proofs = ledger
+                    .get_tx_out_proof_of_memberships(&highest_indices)
+                    .expect("failed getting proof");
+                (encrypted_tx, membership_proofs)
+            })
+            .collect();
+
+        // Form block
+        let parent_block = ledger.get_block(ledger.num_blocks().unwrap() - 1).unwrap();
+
+        let form_block_result =
+            enclave.form_block(&parent_block, &well_formed_encrypted_txs_with_proofs);
+        let expected_duplicate_key_image = new_transactions[0].key_images()[0];
+
+        // Check
+        let expected = Err(Error::DuplicateKeyImageInBlock(expected_duplicate_key_image));
+
+        assert_eq!(form_block_result, expected);
+
+        // Without the double spend, the candidate set forms a block.
+        assert!(enclave
+            .form_block(
+                &parent_block,
+                &well_formed_encrypted_txs_with_proofs[..num_transactions]
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn form_block_refuses_duplicate_root_elements() {
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        let mut rng = Hc128Rng::from_seed([77u8; 32]);
+
+        // Initialize a ledger. `sender` is the owner of all outputs in the initial ledger.
+        let sender = AccountKey::random(&mut rng);
+        let mut ledger = create_ledger();
+        let n_blocks = 3;
+        initialize_ledger(&mut ledger, n_blocks, &sender, &mut rng);
+
+        let mut ledger2 = create_ledger();
+        initialize_ledger(&mut ledger2, n_blocks + 1, &sender, &mut rng);
+
+        // Create a few transactions from `sender` to `recipient`.
+        let num_transactions = 6;
+        let recipient = AccountKey::random(&mut rng);
+
+        // The first block contains a single transaction with MIN_RING_SIZE outputs.
+        let block_zero_transactions = ledger.get_transactions_by_block(0).unwrap();
+        let block_zero_redacted_tx = block_zero_transactions.get(0).unwrap();
+
+        let mut new_transactions = Vec::new();
+        for i in 0..num_transactions {
+            let tx_out = &block_zero_redacted_tx.outputs[i];
+
+            let tx = create_transaction(
+                &mut ledger,
+                tx_out,
+                &sender,
+                &recipient.default_subaddress(),
+                n_blocks + 1,
+                &mut rng,
+            );
+            new_transactions.push(tx);
+        }
+
+        // Create WellFormedEncryptedTxs + proofs
+        let well_formed_encrypted_txs_with_proofs: Vec<(
+            WellFormedEncryptedTx,
+            Vec<TxOutMembershipProof>,
+        )> = new_transactions
+            .iter()
+            .enumerate()
+            .map(|(tx_idx, tx)| {
+                let well_formed_tx = WellFormedTx::from(tx.clone());
+                let encrypted_tx = enclave
+                    .encrypt_well_formed_tx(&well_formed_tx, &mut rng)
+                    .unwrap();
+
+                let highest_indices = well_formed_tx.tx.get_membership_proof_highest_indices();
+                let membership_proofs = highest_indices
+                    .iter()
+                    .map(|index| {
+                        // Make one of the proofs have a different root element by creating it from a different
+                        if tx_idx == 0 {
+                            ledger2
+                                .get_tx_out_proof_of_memberships(&[*index])
+                                .expect("failed getting proof")[0]
+                                .clone()
+                        } else {
+                            ledger
+                                .get_tx_out_proof_of_memberships(&[*index])
+                                .expect("failed getting proof")[0]
+                                .clone()
+                        }
+                    })
+                    .collect();
+                (encrypted_tx, membership_proofs)
+            })
+            .collect();
+
+        // Form block
+        let parent_block = ledger.get_block(ledger.num_blocks().unwrap() - 1).unwrap();
+
+        let form_block_result =
+            enclave.form_block(&parent_block, &well_formed_encrypted_txs_with_proofs);
+
+        // Check
+        let expected = Err(Error::MalformedTx(
+            TransactionValidationError::InvalidTxOutMembershipProof,
+        ));
+        assert_eq!(form_block_result, expected);
+    }
+
+    /// Initializes an enclave and returns it together with its sealed signing key.
+    fn init_enclave_with_sealed_key() -> (SgxConsensusEnclave, SealedBlockSigningKey) {
+        let enclave = SgxConsensusEnclave::default();
+        let peer_id = ResponderId::from_str("node1.test.mobilecoin.com:8443").unwrap();
+        let client_id = ResponderId::from_str("node1.test.mobilecoin.com:3223").unwrap();
+        let sealed = enclave.enclave_init(&peer_id, &client_id, &None).unwrap();
+        (enclave, sealed)
+    }
+
+    #[test]
+    // Repeating `enclave_init` with the same arguments succeeds and changes nothing.
+    fn test_enclave_init_identical_reinit() {
+        let (enclave, sealed) = init_enclave_with_sealed_key();
+        let signer = enclave.get_signer().unwrap();
+        let peer_id = ResponderId::from_str("node1.test.mobilecoin.com:8443").unwrap();
+        let client_id = ResponderId::from_str("node1.test.mobilecoin.com:3223").unwrap();
+
+        assert_eq!(
+            enclave.enclave_init(&peer_id, &client_id, &None),
+            Ok(sealed.clone())
+        );
+        assert_eq!(enclave.get_signer(), Ok(signer));
+
+        // The same holds when starting from a sealed key.
+        let restarted_enclave = SgxConsensusEnclave::default();
+        let restarted_sealed = restarted_enclave
+            .enclave_init(&peer_id, &client_id, &Some(sealed.clone()))
+            .unwrap();
+        assert_eq!(
+            restarted_enclave.enclave_init(&peer_id, &client_id, &Some(sealed)),
+            Ok(restarted_sealed)
+        );
+        assert_eq!(restarted_enclave.get_signer(), Ok(signer));
+    }
+
+    #[test]
+    // The responder ids are the ones passed to `enclave_init`, and are unavailable before it.
+    fn test_self_responder_ids() {
+        let enclave = SgxConsensusEnclave::default();
+        assert_eq!(enclave.self_peer_id(), Err(Error::NotInitialized));
+        assert_eq!(enclave.self_client_id(), Err(Error::NotInitialized));
+
+        let peer_id = ResponderId::from_str("node2.test.mobilecoin.com:8443").unwrap();
+        let client_id = ResponderId::from_str("node2.test.mobilecoin.com:3223").unwrap();
+        enclave.enclave_init(&peer_id, &client_id, &None).unwrap();
+        assert_eq!(enclave.self_peer_id(), Ok(peer_id));
+        assert_eq!(enclave.self_client_id(), Ok(client_id));
+    }
+
+    #[test]
+    // The connect info pairs the client responder id with the key exchange identity, and is
+    // unavailable before `enclave_init`.
+    fn test_client_connect_info() {
+        let enclave = SgxConsensusEnclave::default();
+        assert_eq!(enclave.client_connect_info(), Err(Error::NotInitialized));
+
+        let peer_id = ResponderId::from_str("node3.test.mobilecoin.com:8443").unwrap();
+        let client_id = ResponderId::from_str("node3.test.mobilecoin.com:3223").unwrap();
+        enclave.enclave_init(&peer_id, &client_id, &None).unwrap();
+        assert_eq!(
+            enclave.client_connect_info(),
+            Ok((
+                enclave.self_client_id().unwrap(),
+                enclave.get_identity().unwrap()
+            ))
+        );
+        assert_eq!(enclave.client_connect_info().unwrap().0, client_id);
+    }
+
+    #[test]
+    // Responder ids which are empty or not `host:port` are rejected, and leave the enclave
+    // uninitialized.
+    fn test_enclave_init_validates_responder_ids() {
+        let enclave = SgxConsensusEnclave::default();
+        let peer_id = ResponderId::from_str("node1.test.mobilecoin.com:8443").unwrap();
+        let client_id = ResponderId::from_str("node1.test.mobilecoin.com:3223").unwrap();
+
+        let invalid_ids = ["", ":", "node1.test.mobilecoin.com", "node1.test.mobilecoin.com:http"];
+        for invalid in &invalid_ids {
+            let invalid_id = ResponderId((*invalid).into());
+            assert_eq!(
+                enclave.enclave_init(&invalid_id, &client_id, &None),
+                Err(Error::InvalidResponderId(invalid_id.clone()))
+            );
+            assert_eq!(
+                enclave.enclave_init(&peer_id, &invalid_id, &None),
+                Err(Error::InvalidResponderId(invalid_id.clone()))
+            );
+        }
+        assert_eq!(enclave.get_signer(), Err(Error::NotInitialized));
+
+        assert!(enclave.enclave_init(&peer_id, &client_id, &None).is_ok());
+    }
+
+    #[test]
+    // A second `enclave_init` with different arguments is rejected and leaves the key in place.
+    fn test_enclave_init_conflicting_reinit() {
+        let (enclave, sealed) = init_enclave_with_sealed_key();
+        let signer = enclave.get_signer().unwrap();
+        let peer_id = ResponderId::from_str("node1.test.mobilecoin.com:8443").unwrap();
+        let client_id = ResponderId::from_str("node1.test.mobilecoin.com:3223").unwrap();
+
+        // Another enclave's key.
+        let (_other_enclave, other_sealed) = init_enclave_with_sealed_key();
+        assert_eq!(
+            enclave.enclave_init(&peer_id, &client_id, &Some(other_sealed)),
+            Err(Error::AlreadyInitialized)
+        );
+        assert_eq!(
+            enclave.enclave_init(&peer_id, &client_id, &Some(sealed)),
+            Err(Error::AlreadyInitialized)
+        );
+
+        // Other responder IDs.
+        let other_peer_id = ResponderId::from_str("node2.test.mobilecoin.com:8443").unwrap();
+        assert_eq!(
+            enclave.enclave_init(&other_peer_id, &client_id, &None),
+            Err(Error::AlreadyInitialized)
+        );
+
+        assert_eq!(enclave.get_signer(), Ok(signer));
+    }
+
+    #[test]
+    // The capabilities reported by `enclave_version` are the ones this enclave implements.
+    fn test_enclave_version_capabilities_match_implementation() {
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        let version = enclave.enclave_version().unwrap();
+
+        // Every optional method defined by the API is implemented here.
+        assert_eq!(version.capabilities, EnclaveCapabilities::all());
+
+        // Spot-check that the advertised methods answer rather than fail.
+        assert!(version.supports(EnclaveCapabilities::BATCH_WELL_FORMED));
+        assert_eq!(enclave.txs_are_well_formed(Vec::new()), Ok(Vec::new()));
+
+        assert!(version.supports(EnclaveCapabilities::MINIMUM_FEE));
+        assert_eq!(enclave.get_minimum_fee(0), Ok(BASE_FEE));
+
+        assert!(version.supports(EnclaveCapabilities::MINIMUM_FEES));
+        assert_eq!(enclave.get_minimum_fees(), Ok(vec![(DEFAULT_TOKEN_ID, BASE_FEE)]));
+
+        assert!(version.supports(EnclaveCapabilities::PEER_IDENTITY_POLICY));
+        let own_measurement = Measurement::from(enclave.report_body().unwrap().mr_enclave());
+        assert_eq!(enclave.set_peer_identity_policy(vec![own_measurement]), Ok(()));
+
+        assert!(version.supports(EnclaveCapabilities::PEER_INIT_PINNED));
+        assert_eq!(
+            enclave.peer_init_pinned(&ResponderId::default(), own_measurement),
+            Err(Error::Attest(AttestEnclaveError::NoReportAvailable))
+        );
+
+        assert!(version.supports(EnclaveCapabilities::TX_HASH_VERIFICATION));
+        assert_eq!(
+            enclave.verify_tx_hash(&LocallyEncryptedTx(Vec::new()), &TxHash::default()),
+            Err(Error::CacheCipher(message_cipher::CipherError::TooShort))
+        );
+
+        assert!(version.supports(EnclaveCapabilities::FORM_BLOCK_COST_ESTIMATE));
+        assert_eq!(enclave.estimate_form_block_cost(&[]), Ok(0));
+
+        assert!(version.supports(EnclaveCapabilities::BLOCK_VERSION_RANGE));
+        assert_eq!(enclave.block_version_range(), Ok((BLOCK_VERSION, BLOCK_VERSION)));
+
+        assert!(version.supports(EnclaveCapabilities::ENCLAVE_STATUS));
+        assert_eq!(enclave.enclave_status(), Ok(EnclaveStatus::default()));
+
+        assert!(version.supports(EnclaveCapabilities::SIGNING_KEY_ROTATION));
+        assert!(enclave.rotate_signing_key().is_ok());
+
+        assert!(version.supports(EnclaveCapabilities::BATCH_CLIENT_DISCARD));
+        assert_eq!(enclave.client_discard_messages(Vec::new()), Ok(Vec::new()));
+
+        assert!(version.supports(EnclaveCapabilities::IAS_REPORT_VALIDATION));
+        assert!(enclave
+            .validate_ias_report(&VerificationReport::default())
+            .is_err());
+
+        assert!(version.supports(EnclaveCapabilities::INCLUSION_LOOKUP));
+        assert_eq!(enclave.included_in_block(&TxHash::default()), Ok(None));
+
+        assert!(version.supports(EnclaveCapabilities::RECENT_KEY_IMAGES));
+        assert_eq!(enclave.recent_key_images(0), Ok(vec![]));
+
+        assert!(version.supports(EnclaveCapabilities::IAS_REPORT_TIME));
+        assert!(enclave
+            .ias_report_time(&VerificationReport::default())
+            .is_err());
+
+        assert!(version.supports(EnclaveCapabilities::FORM_BLOCK_EXCLUDE));
+        assert_eq!(
+            enclave.form_block_excluding(&Block::new_origin_block(&[]), &[], &[]),
+            Err(Error::InvalidLocalMembershipProof)
+        );
+
+        assert!(version.supports(EnclaveCapabilities::PEER_TX_DEDUP));
+        assert!(enclave
+            .peer_tx_propose_dedup(EnclaveMessage::default(), &[])
+            .is_err());
+
+        assert!(version.supports(EnclaveCapabilities::TRUSTED_SIGNER_CHECK));
+        assert_eq!(
+            enclave.is_trusted_signer(&enclave.get_signer().unwrap()),
+            Ok(true)
+        );
+
+        assert!(version.supports(EnclaveCapabilities::BATCH_OWN_BLOCK_SIGNATURES));
+        assert_eq!(enclave.verify_own_block_signatures(&[]), Ok(vec![]));
+
+        assert!(version.supports(EnclaveCapabilities::TX_FEE_PREVIEW));
+        assert!(enclave
+            .tx_fee_preview(&LocallyEncryptedTx::default())
+            .is_err());
+
+        assert!(version.supports(EnclaveCapabilities::FORM_EMPTY_BLOCK));
+        assert_eq!(
+            enclave.form_empty_block(
+                &Block::new_origin_block(&[]),
+                &TxOutMembershipProof::default()
+            ),
+            Err(Error::InvalidLocalMembershipProof)
+        );
+
+        assert_eq!(
+            version.supports(EnclaveCapabilities::CALL_TIMINGS),
+            cfg!(feature = "profiling")
+        );
+        assert_eq!(
+            enclave.last_call_timings().is_ok(),
+            cfg!(feature = "profiling")
+        );
+
+        assert!(version.supports(EnclaveCapabilities::FEE_POLICY_DIGEST));
+        assert_eq!(
+            enclave.fee_policy_digest(),
+            Ok(digest_fee_policy(&enclave.get_minimum_fees().unwrap()))
+        );
+
+        assert!(version.supports(EnclaveCapabilities::REDACT_SINGLE));
+        assert!(enclave
+            .redact_single(&WellFormedEncryptedTx::default())
+            .is_err());
+
+        assert!(version.supports(EnclaveCapabilities::IAS_REPORT_BLOCKS_REMAINING));
+        assert_eq!(
+            enclave.ias_report_blocks_remaining(0, 1, 1),
+            Err(Error::Attest(AttestEnclaveError::NoReportAvailable))
+        );
+
+        assert!(version.supports(EnclaveCapabilities::SIGNER_FINGERPRINT));
+        assert!(enclave.get_signer_fingerprint().is_ok());
+
+        assert!(version.supports(EnclaveCapabilities::SELF_RESPONDER_IDS));
+        assert!(enclave.self_peer_id().is_ok());
+        assert!(enclave.self_client_id().is_ok());
+
+        assert!(version.supports(EnclaveCapabilities::CLIENT_CONNECT_INFO));
+        assert!(enclave.client_connect_info().is_ok());
+
+        assert!(version.supports(EnclaveCapabilities::CONTEXT_OF));
+        assert!(enclave
+            .context_of(&WellFormedEncryptedTx::default())
+            .is_err());
+
+        assert!(version.supports(EnclaveCapabilities::ATTESTATION_BUNDLE));
+        assert!(enclave.attestation_bundle().is_ok());
+
+        assert!(version.supports(EnclaveCapabilities::MAX_PROPOSAL_BYTES));
+        assert_eq!(
+            enclave.set_max_proposal_bytes(DEFAULT_MAX_PROPOSAL_BYTES),
+            Ok(())
+        );
+
+        assert!(version.supports(EnclaveCapabilities::BLOCK_TOTAL_FEES));
+        assert!(enclave
+            .block_total_fees(&Block::new_origin_block(&[]), &[])
+            .is_err());
+
+        assert!(version.supports(EnclaveCapabilities::INTEGRITY_CHECK));
+        assert_eq!(
+            enclave.integrity_check(&LocallyEncryptedTx::default()),
+            Err(Error::Integrity)
+        );
+
+        assert!(version.supports(EnclaveCapabilities::TOMBSTONE_WINDOW));
+        assert!(enclave
+            .set_tombstone_window(TombstoneWindow {
+                min_offset: 0,
+                max_offset: u64::max_value(),
+            })
+            .is_ok());
+
+        assert!(version.supports(EnclaveCapabilities::SPENT_KEY_IMAGE_CHECK));
+        assert!(enclave
+            .tx_is_well_formed_unspent(LocallyEncryptedTx::default(), 0, vec![], &[])
+            .is_err());
+
+        assert!(version.supports(EnclaveCapabilities::PROPOSE_RECEIPT));
+        assert!(enclave.sign_propose_receipt(TxHash::default(), 0).is_ok());
+
+        assert!(version.supports(EnclaveCapabilities::PEER_CLOSE_ALL));
+        assert_eq!(enclave.peer_close_all(), Ok(()));
+
+        assert!(version.supports(EnclaveCapabilities::CANCEL_EREPORT));
+        assert_eq!(enclave.cancel_ereport(), Ok(()));
+
+        assert!(version.supports(EnclaveCapabilities::CLIENT_SESSIONS));
+        assert_eq!(enclave.client_sessions(), Ok(Vec::new()));
+
+        assert!(version.supports(EnclaveCapabilities::SESSION_STATS));
+        assert_eq!(enclave.session_stats(), Ok(SessionStats::default()));
+
+        // No report has been verified, so there is nothing to describe.
+        assert!(version.supports(EnclaveCapabilities::IAS_REPORT_INFO));
+        assert_eq!(
+            enclave.get_ias_report_info(),
+            Err(Error::Attest(AttestEnclaveError::NoReportAvailable))
+        );
+
+        assert!(version.supports(EnclaveCapabilities::VERSIONED_BLOCK_FORMATION));
+        let origin_block = Block::new_origin_block(&[]);
+        assert_eq!(
+            enclave.form_block_versioned(&origin_block, &[], BLOCK_VERSION + 1),
+            Err(Error::UnsupportedBlockVersion(BLOCK_VERSION + 1))
+        );
+
+        assert!(version.supports(EnclaveCapabilities::CLIENT_ACCEPT_BUDGET));
+        assert_eq!(
+            enclave.client_accept_with_budget(ClientAuthRequest::from(vec![0u8; 64]), 0),
+            Err(Error::Attest(AttestEnclaveError::SessionLimitReached))
+        );
+
+        assert!(version.supports(EnclaveCapabilities::REPORT_BODY));
+        assert!(version.supports(EnclaveCapabilities::RING_SIGNATURE_PREVALIDATION));
+        assert!(version.supports(EnclaveCapabilities::SEALED_BLOBS));
+        assert!(version.supports(EnclaveCapabilities::SIGNING_HEIGHT));
+        assert!(version.supports(EnclaveCapabilities::CLOSE_SESSIONS));
+        assert!(enclave.report_body().is_ok());
+
+        assert!(version.supports(EnclaveCapabilities::BLOCK_LIMITS));
+        assert_eq!(enclave.set_block_limits(BlockLimits::default()), Ok(()));
+
+        // Without any membership proofs there is no ledger root to form a block against.
+        assert!(version.supports(EnclaveCapabilities::FORM_BLOCK_WITH_REPORT));
+        assert_eq!(
+            enclave.form_block_with_report(&origin_block, &[]),
+            Err(Error::InvalidLocalMembershipProof)
+        );
+    }
+
+    #[test]
+    // Closing all peer sessions succeeds whether or not any are open, and leaves none behind.
+    //
+    // Establishing real peer sessions requires an IAS-verified report, so this exercises the
+    // teardown on session IDs which were never (or are no longer) known to the enclave.
+    fn test_peer_close_all() {
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        let sessions: Vec<PeerSession> = (0u8..4)
+            .map(|i| PeerSession::from(vec![i; 32]))
+            .collect();
+
+        assert_eq!(enclave.peer_close_all(), Ok(()));
+        // Closing again is a no-op.
+        assert_eq!(enclave.peer_close_all(), Ok(()));
+
+        for session in &sessions {
+            assert_eq!(
+                enclave.txs_for_peer(&[], &[], session),
+                Err(Error::Attest(AttestEnclaveError::NotFound))
+            );
+            // Individually closing an already-closed session is still fine.
+            assert_eq!(enclave.peer_close(session), Ok(()));
+        }
+    }
+
+    #[test]
+    // close_sessions reports an outcome for every session given, clients first, and sessions
+    // which are not open do not fail the rest of the batch.
+    //
+    // As with `test_peer_close_all`, sessions can only be established against an IAS-verified
+    // report, so every session here is one that was never open, or was already closed.
+    fn test_close_sessions() {
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        assert_eq!(enclave.close_sessions(&[], &[]), Ok(Vec::new()));
+
+        let clients: Vec<ClientSession> = (0u8..2)
+            .map(|i| ClientSession::from(vec![i; 32]))
+            .collect();
+        let peers: Vec<PeerSession> = (2u8..5)
+            .map(|i| PeerSession::from(vec![i; 32]))
+            .collect();
+        enclave.client_close(clients[0].clone()).unwrap();
+        enclave.peer_close(&peers[1]).unwrap();
+
+        let not_found = Err(Error::Attest(AttestEnclaveError::NotFound));
+        let expected: Vec<(SessionRef, Result<()>)> = clients
+            .iter()
+            .cloned()
+            .map(SessionRef::Client)
+            .chain(peers.iter().cloned().map(SessionRef::Peer))
+            .map(|session| (session, not_found.clone()))
+            .collect();
+        assert_eq!(enclave.close_sessions(&clients, &peers), Ok(expected.clone()));
+
+        // Closing the same sessions again reports the same outcomes.
+        assert_eq!(enclave.close_sessions(&clients, &peers), Ok(expected));
+        assert_eq!(
+            enclave.session_stats(),
+            Ok(SessionStats {
+                client_sessions: 0,
+                peer_sessions: 0,
+            })
+        );
+    }
+
+    #[test]
+    // Once the budget is used up, requests are turned away before they are processed.
+    //
+    // Establishing client sessions requires an IAS-verified report, so the only budget which can
+    // be filled here is an empty one.
+    fn test_client_accept_with_budget() {
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        let request = ClientAuthRequest::from(vec![3u8; 64]);
+
+        assert_eq!(
+            enclave.client_accept_with_budget(request.clone(), 0),
+            Err(Error::Attest(AttestEnclaveError::SessionLimitReached))
+        );
+
+        // With room in the budget, the request is processed, and fails for lack of a report.
+        assert_eq!(
+            enclave.client_accept_with_budget(request, 1),
+            Err(Error::Attest(AttestEnclaveError::NoReportAvailable))
+        );
+        assert_eq!(enclave.client_sessions(), Ok(Vec::new()));
+    }
+
+    #[test]
+    // A peer auth request which fails to be accepted is not remembered, so it can be retried.
+    //
+    // Accepting a request requires an IAS-verified report, so every attempt fails here, and only
+    // replays of accepted requests are rejected as `ReplayedAuthRequest`.
+    fn test_peer_accept_retries_failed_request() {
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        let request = PeerAuthRequest::from(vec![7u8; 64]);
+
+        for _ in 0..3 {
+            assert_eq!(
+                enclave.peer_accept(request.clone()),
+                Err(Error::Attest(AttestEnclaveError::NoReportAvailable))
+            );
+        }
+
+        // A different request is processed as usual.
+        assert_eq!(
+            enclave.peer_accept(PeerAuthRequest::from(vec![8u8; 64])),
+            Err(Error::Attest(AttestEnclaveError::NoReportAvailable))
+        );
+    }
+
+    #[test]
+    // Peers are turned away before their handshake is processed unless the measurement they are
+    // pinned to, our own MRENCLAVE unless `peer_init_pinned` says otherwise, is allowed.
+    //
+    // Completing a handshake requires an IAS-verified report, so allowed peers fail here as well,
+    // but only once their request is processed.
+    fn test_peer_identity_policy() {
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        let body = enclave.report_body().unwrap();
+        let peer_id = ResponderId::from_str("node2.test.mobilecoin.com:8443").unwrap();
+
+        // Allowed peer measurement.
+        enclave
+            .set_peer_identity_policy(vec![Measurement::from(body.mr_enclave())])
+            .unwrap();
+        assert_eq!(
+            enclave.peer_accept(PeerAuthRequest::from(vec![1u8; 64])),
+            Err(Error::Attest(AttestEnclaveError::NoReportAvailable))
+        );
+        assert_eq!(
+            enclave.peer_connect(&peer_id, PeerAuthResponse::from(vec![2u8; 64])),
+            Err(Error::Attest(AttestEnclaveError::NotFound))
+        );
+
+        // Disallowed peer measurements: inbound peers are pinned to our MRENCLAVE, not MRSIGNER.
+        for allowed in vec![vec![], vec![Measurement::from(body.mr_signer())]] {
+            enclave.set_peer_identity_policy(allowed).unwrap();
+            assert_eq!(
+                enclave.peer_accept(PeerAuthRequest::from(vec![3u8; 64])),
+                Err(Error::PeerIdentityNotAllowed)
+            );
+            assert_eq!(
+                enclave.peer_init_pinned(&peer_id, Measurement::from(body.mr_enclave())),
+                Err(Error::PeerIdentityNotAllowed)
+            );
+        }
+    }
+
+    #[test]
+    // A pin is checked against the peer identity policy up front, and is otherwise handed to the
+    // handshake, which verifies the peer's response against it.
+    //
+    // Starting a handshake requires an IAS-verified report, so pins which are allowed fail here
+    // when the request is created, and no connection is left pending.
+    fn test_peer_init_pinned() {
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        let body = enclave.report_body().unwrap();
+        let peer_id = ResponderId::from_str("node2.test.mobilecoin.com:8443").unwrap();
+        let signer_pin = Measurement::from(body.mr_signer());
+        let enclave_pin = Measurement::from(body.mr_enclave());
+
+        // Without a policy, any pin is used.
+        for pin in &[signer_pin, enclave_pin] {
+            assert_eq!(
+                enclave.peer_init_pinned(&peer_id, *pin),
+                Err(Error::Attest(AttestEnclaveError::NoReportAvailable))
+            );
+        }
+
+        // A matching pin is used, a mismatched one is refused.
+        enclave.set_peer_identity_policy(vec![signer_pin]).unwrap();
+        assert_eq!(
+            enclave.peer_init_pinned(&peer_id, signer_pin),
+            Err(Error::Attest(AttestEnclaveError::NoReportAvailable))
+        );
+        assert_eq!(
+            enclave.peer_init_pinned(&peer_id, enclave_pin),
+            Err(Error::PeerIdentityNotAllowed)
+        );
+
+        assert_eq!(
+            enclave.peer_connect(&peer_id, PeerAuthResponse::from(vec![1u8; 64])),
+            Err(Error::Attest(AttestEnclaveError::NotFound))
+        );
+    }
+
+    #[test]
+    // The snapshot returned by `enclave_status` agrees with the individual accessors.
+    //
+    // An IAS report can't be verified here, so the snapshot never holds one.
+    fn test_enclave_status_matches_accessors() {
+        let mut rng = Hc128Rng::from_seed([58u8; 32]);
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+
+        let check = |enclave: &SgxConsensusEnclave| {
+            let status = enclave.enclave_status().unwrap();
+            assert_eq!(status.sessions, enclave.session_stats().unwrap());
+            assert_eq!(
+                enclave.get_ias_report_info(),
+                Err(Error::Attest(AttestEnclaveError::NoReportAvailable))
+            );
+            assert_eq!(status.ias_report_info, None);
+            assert_eq!(status.signing_height, enclave.current_signing_height().unwrap());
+            status
+        };
+        assert_eq!(check(&enclave), EnclaveStatus::default());
+
+        let (parent_block, _input_transactions, well_formed_encrypted_txs_with_proofs) =
+            form_block_candidates(&enclave, &[BASE_FEE], &mut rng);
+        enclave
+            .form_block(&parent_block, &well_formed_encrypted_txs_with_proofs)
+            .unwrap();
+        assert_eq!(check(&enclave).signing_height, parent_block.index + 1);
+    }
+
+    #[test]
+    // Validating a report never touches the cached one. A report signed by IAS can not be made in
+    // tests, so only rejected reports are checked here.
+    fn test_validate_ias_report_leaves_cache_untouched() {
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        let no_report = Err(Error::Attest(AttestEnclaveError::NoReportAvailable));
+        assert_eq!(enclave.get_ias_report(), no_report);
+
+        let unsigned_report = VerificationReport::default();
+        assert!(enclave.validate_ias_report(&unsigned_report).is_err());
+        assert_eq!(enclave.get_ias_report(), no_report);
+
+        // Both checks reject the report for the same reason.
+        assert_eq!(
+            enclave.validate_ias_report(&unsigned_report),
+            enclave.verify_ias_report(unsigned_report)
+        );
+        assert_eq!(enclave.get_ias_report(), no_report);
+    }
+
+    #[test]
+    // A report answering a consumed nonce is rejected as a replay. A report signed by IAS can not
+    // be made in tests, so the nonce is consumed directly, as a successful verification would.
+    fn test_verify_ias_report_rejects_consumed_nonce() {
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        let report = VerificationReport {
+            http_body: IAS_WITH_PIB.into(),
+            ..Default::default()
+        };
+        let nonce = VerificationReportData::try_from(&report)
+            .unwrap()
+            .nonce
+            .unwrap();
+
+        // A report which fails verification does not consume its nonce.
+        let unverified = enclave.verify_ias_report(report.clone());
+        assert!(unverified.is_err());
+        assert_ne!(unverified, Err(Error::NonceAlreadyConsumed));
+        assert_eq!(enclave.verify_ias_report(report.clone()), unverified);
+
+        enclave
+            .consumed_ias_nonces
+            .lock()
+            .unwrap()
+            .push_back(nonce);
+        assert_eq!(
+            enclave.verify_ias_report(report.clone()),
+            Err(Error::NonceAlreadyConsumed)
+        );
+        assert_eq!(
+            enclave.verify_ias_report_at(report, 1_560_982_277),
+            Err(Error::NonceAlreadyConsumed)
+        );
+    }
+
+    #[test]
+    // A report whose nonce can not be read is rejected rather than going untracked.
+    fn test_verify_ias_report_rejects_untracked_nonce() {
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+
+        let unparseable = enclave.verify_ias_report(VerificationReport::default());
+        assert!(unparseable.is_err());
+
+        let nonce_field = "\"nonce\":\"b6cb5af2a11cfe2f9c19b944b32c3aac\",";
+        assert!(IAS_WITH_PIB.contains(nonce_field));
+        let report = VerificationReport {
+            http_body: IAS_WITH_PIB.replace(nonce_field, ""),
+            ..Default::default()
+        };
+        assert_eq!(
+            enclave.verify_ias_report(report.clone()),
+            Err(Error::from(NonceError::Missing))
+        );
+        assert_eq!(
+            enclave.verify_ias_report_at(report, 1_560_982_277),
+            Err(Error::from(NonceError::Missing))
+        );
+    }
+
+    #[test]
+    // Without a cached report there is nothing to count down.
+    fn test_ias_report_blocks_remaining_requires_report() {
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        assert_eq!(
+            enclave.ias_report_blocks_remaining(1_560_982_277, 5, 50),
+            Err(Error::Attest(AttestEnclaveError::NoReportAvailable))
+        );
+    }
+
+    #[test]
+    // The count follows the report's age since IAS issued it. A report signed by IAS can not be
+    // made in tests, so the count is checked on the report body the cached report would have.
+    fn test_report_blocks_remaining() {
+        let report = VerificationReport {
+            http_body: IAS_WITH_PIB.into(),
+            ..Default::default()
+        };
+        let issued_at = 1_560_982_277;
+
+        // Far from the refresh boundary.
+        assert_eq!(report_blocks_remaining(&report, issued_at, 5, 50), Ok(50));
+        assert_eq!(report_blocks_remaining(&report, issued_at + 50, 5, 50), Ok(40));
+
+        // Near and past the boundary.
+        assert_eq!(report_blocks_remaining(&report, issued_at + 245, 5, 50), Ok(1));
+        assert_eq!(report_blocks_remaining(&report, issued_at + 250, 5, 50), Ok(0));
+        assert_eq!(report_blocks_remaining(&report, issued_at + 500, 5, 50), Ok(0));
+
+        // A report whose issue time can not be read has no age to count.
+        assert!(report_blocks_remaining(&VerificationReport::default(), issued_at, 5, 50).is_err());
+    }
+
+    #[test]
+    // The exported report body matches the one the enclave presents for attestation.
+    fn test_report_body_matches_ereport() {
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        let body = enclave.report_body().unwrap();
+        let (report, _nonce) = enclave.new_ereport(TargetInfo::default()).unwrap();
+
+        assert_eq!(body.mr_enclave(), report.body().mr_enclave());
+        assert_eq!(body.mr_signer(), report.body().mr_signer());
+        assert_eq!(body.report_data(), report.body().report_data());
+
+        // Asking again changes nothing.
+        assert_eq!(enclave.report_body(), Ok(body));
+    }
+
+    #[test]
+    // The bundle's parts are the ones the separate getters return, and its identity is the one
+    // attested in its report body.
+    fn test_attestation_bundle_is_consistent() {
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        let bundle = enclave.attestation_bundle().unwrap();
+
+        let body = enclave.report_body().unwrap();
+        assert_eq!(bundle.report_body.report_data(), body.report_data());
+        assert_eq!(bundle.mr_enclave(), enclave.own_measurement().unwrap());
+        assert_eq!(bundle.identity, enclave.get_identity().unwrap());
+        assert!(bundle.is_identity_bound());
+
+        // No report was verified, which the bundle reports rather than failing.
+        assert_eq!(
+            enclave.get_ias_report(),
+            Err(Error::Attest(AttestEnclaveError::NoReportAvailable))
+        );
+        assert_eq!(bundle.ias_report, None);
+
+        // Another enclave's identity does not belong with this report body.
+        let other_enclave = SgxConsensusEnclave::default();
+        let mismatched = AttestationBundle {
+            identity: other_enclave.get_identity().unwrap(),
+            ..bundle
+        };
+        assert!(!mismatched.is_identity_bound());
+    }
+
+    #[test]
+    // Enclaves seeded alike generate the same sequence of quote nonces.
+    fn test_seed_test_rng_makes_quote_nonces_deterministic() {
+        let new_nonce = |enclave: &SgxConsensusEnclave| {
+            let (_report, nonce) = enclave.new_ereport(TargetInfo::default()).unwrap();
+            nonce
+        };
+
+        let (first, _sealed) = init_enclave_with_sealed_key();
+        let (second, _sealed) = init_enclave_with_sealed_key();
+        first.seed_test_rng([64u8; 32]).unwrap();
+        second.seed_test_rng([64u8; 32]).unwrap();
+
+        let first_nonces = [new_nonce(&first), new_nonce(&first)];
+        assert_eq!(first_nonces, [new_nonce(&second), new_nonce(&second)]);
+        assert_ne!(first_nonces[0], first_nonces[1]);
+
+        // A different seed yields different nonces.
+        let (third, _sealed) = init_enclave_with_sealed_key();
+        third.seed_test_rng([65u8; 32]).unwrap();
+        assert_ne!(new_nonce(&third), first_nonces[0]);
+    }
+
+    #[test]
+    // Cancelling pending EREPORTs is idempotent, and attestation can be started again afterwards.
+    //
+    // A quote matching a pending EREPORT can only be produced by a real quoting enclave, so this
+    // does not drive `verify_quote` to completion.
+    fn test_cancel_ereport() {
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+
+        // Cancelling with nothing in flight is fine.
+        assert_eq!(enclave.cancel_ereport(), Ok(()));
+
+        enclave
+            .new_ereport(TargetInfo::default())
+            .expect("Could not create EREPORT");
+        assert_eq!(enclave.cancel_ereport(), Ok(()));
+        assert_eq!(enclave.cancel_ereport(), Ok(()));
+
+        // A new handshake can begin after cancelling the old one.
+        enclave
+            .new_ereport(TargetInfo::default())
+            .expect("Could not create EREPORT after cancel");
+
+        // Cancelling does not conjure up a verified report.
+        assert_eq!(
+            enclave.get_ias_report(),
+            Err(Error::Attest(AttestEnclaveError::NoReportAvailable))
+        );
+    }
+
+    #[test]
+    // Session counts start at zero and are unaffected by closing sessions which do not exist.
+    //
+    // As with `test_peer_close_all`, sessions can only be established against an IAS-verified
+    // report, so the counts are never expected to move above zero here.
+    fn test_session_stats() {
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        let empty = SessionStats {
+            client_sessions: 0,
+            peer_sessions: 0,
+        };
+        assert_eq!(enclave.session_stats(), Ok(empty));
+
+        // Handshakes fail without a cached report, and do not leave sessions behind.
+        assert!(enclave
+            .client_accept(ClientAuthRequest::from(vec![0u8; 64]))
+            .is_err());
+        assert!(enclave
+            .peer_accept(PeerAuthRequest::from(vec![0u8; 64]))
+            .is_err());
+        assert_eq!(enclave.session_stats(), Ok(empty));
+
+        enclave
+            .client_close(ClientSession::from(vec![1u8; 32]))
+            .unwrap();
+        enclave
+            .peer_close(&PeerSession::from(vec![2u8; 32]))
+            .unwrap();
+        enclave.peer_close_all().unwrap();
+        assert_eq!(enclave.session_stats(), Ok(empty));
+    }
+
+    #[test]
+    // No client sessions are listed before any are established, and failed handshakes or closing
+    // unknown sessions do not add any.
+    //
+    // As with `test_session_stats`, sessions can only be established against an IAS-verified
+    // report, so the list of open sessions is never expected to be non-empty here.
+    fn test_client_sessions() {
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        assert_eq!(enclave.client_sessions(), Ok(Vec::new()));
+
+        for i in 0u8..3 {
+            assert!(enclave
+                .client_accept(ClientAuthRequest::from(vec![i; 64]))
+                .is_err());
+            enclave
+                .client_close(ClientSession::from(vec![i; 32]))
+                .unwrap();
         }
+        assert_eq!(enclave.client_sessions(), Ok(Vec::new()));
+        assert_eq!(
+            enclave.session_stats().unwrap().client_sessions,
+            enclave.client_sessions().unwrap().len()
+        );
+    }
 
-        // Create another transaction that spends the zero^th output in block zero.
-        let double_spend = {
-            let tx_out = &block_zero_redacted_tx.outputs[0];
+    #[test]
+    // Only signatures over the given block made by the enclave's own signing key are accepted.
+    fn test_verify_own_block_signature() {
+        let mut rng = Hc128Rng::from_seed([24u8; 32]);
+        let enclave = SgxConsensusEnclave::default();
+        let origin_block = Block::new_origin_block(&[]);
 
-            create_transaction(
-                &mut ledger,
-                tx_out,
-                &sender,
-                &recipient.default_subaddress(),
-                n_blocks + 1,
-                &mut rng,
-            )
+        let own_signature = {
+            let keypair = enclave.ake.get_identity().signing_keypair.lock().unwrap();
+            BlockSignature::from_block_and_keypair(&origin_block, &keypair).unwrap()
         };
-        new_transactions.push(double_spend);
+        assert_eq!(
+            enclave.verify_own_block_signature(&origin_block, &own_signature),
+            Ok(())
+        );
 
-        // Create WellFormedEncryptedTxs + proofs
-        let well_formed_encrypted_txs_with_proofs: Vec<_> = new_transactions
+        // A valid signature by a different key is rejected.
+        let other_keypair = Ed25519Pair::from_random(&mut rng);
+        let other_signature =
+            BlockSignature::from_block_and_keypair(&origin_block, &other_keypair).unwrap();
+        assert!(other_signature.verify(&origin_block).is_ok());
+        assert_eq!(
+            enclave.verify_own_block_signature(&origin_block, &other_signature),
+            Err(Error::Signature)
+        );
+
+        // A different key's signature claiming to be from the enclave is rejected.
+        let forged_signature = BlockSignature::new(
+            other_signature.signature().clone(),
+            *own_signature.signer(),
+        );
+        assert_eq!(
+            enclave.verify_own_block_signature(&origin_block, &forged_signature),
+            Err(Error::Signature)
+        );
+
+        // The enclave's signature over a different block is rejected.
+        let mut other_block = origin_block.clone();
+        other_block.index += 1;
+        assert_eq!(
+            enclave.verify_own_block_signature(&other_block, &own_signature),
+            Err(Error::Signature)
+        );
+    }
+
+    #[test]
+    // A batch check reports one result per block, in order, matching single checks.
+    fn test_verify_own_block_signatures() {
+        let mut rng = Hc128Rng::from_seed([62u8; 32]);
+        let enclave = SgxConsensusEnclave::default();
+        let origin_block = Block::new_origin_block(&[]);
+        let mut other_block = origin_block.clone();
+        other_block.index += 1;
+
+        let (own_signature, own_other_signature) = {
+            let keypair = enclave.ake.get_identity().signing_keypair.lock().unwrap();
+            (
+                BlockSignature::from_block_and_keypair(&origin_block, &keypair).unwrap(),
+                BlockSignature::from_block_and_keypair(&other_block, &keypair).unwrap(),
+            )
+        };
+        let other_keypair = Ed25519Pair::from_random(&mut rng);
+        let other_signature =
+            BlockSignature::from_block_and_keypair(&origin_block, &other_keypair).unwrap();
+
+        let blocks = vec![
+            (origin_block.clone(), own_signature.clone()),
+            (origin_block.clone(), other_signature),
+            (other_block.clone(), own_signature),
+            (other_block, own_other_signature),
+        ];
+        let expected: Vec<bool> = blocks
             .iter()
-            .map(|tx| {
-                let well_formed_tx = WellFormedTx::from(tx.clone());
-                let encrypted_tx = enclave
-                    .encrypt_well_formed_tx(&well_formed_tx, &mut rng)
-                    .unwrap();
+            .map(|(block, signature)| enclave.verify_own_block_signature(block, signature).is_ok())
+            .collect();
+        assert_eq!(expected, vec![true, false, false, true]);
+        assert_eq!(enclave.verify_own_block_signatures(&blocks), Ok(expected));
+    }
 
-                let highest_indices = well_formed_tx.tx.get_membership_proof_highest_indices();
-                let membership_proofs = ledger
-                    .get_tx_out_proof_of_memberships(&highest_indices)
-                    .expect("failed getting proof");
-                (encrypted_tx, membership_proofs)
+    #[test]
+    // A batch discard reports one result per message, in order, matching single discards.
+    // Establishing a client session requires an IAS-verified report, so the batch only mixes
+    // empty and non-empty messages for sessions the enclave does not know about.
+    fn test_client_discard_messages() {
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        assert_eq!(enclave.client_discard_messages(Vec::new()), Ok(Vec::new()));
+
+        let msgs: Vec<EnclaveMessage<ClientSession>> = (0u8..3)
+            .map(|i| EnclaveMessage {
+                aad: vec![i; usize::from(i)],
+                channel_id: ClientSession::from(vec![i; 32]),
+                data: vec![i; 16 * usize::from(i)],
             })
             .collect();
+        let expected: Vec<Result<()>> = msgs
+            .iter()
+            .map(|msg| enclave.client_discard_message(msg.clone()))
+            .collect();
+        assert_eq!(
+            expected,
+            vec![Err(Error::Attest(AttestEnclaveError::NotFound)); msgs.len()]
+        );
+        assert_eq!(enclave.client_discard_messages(msgs), Ok(expected));
+    }
 
-        // Form block
-        let parent_block = ledger.get_block(ledger.num_blocks().unwrap() - 1).unwrap();
+    #[test]
+    // Client messages over the size limit are rejected before decryption is attempted.
+    fn test_client_tx_propose_rejects_oversized_messages() {
+        let limit = 1000;
+        let enclave = SgxConsensusEnclave::default();
+        enclave.set_max_proposal_bytes(limit).unwrap();
+        let message = |size: usize| EnclaveMessage {
+            aad: vec![0u8; 10],
+            channel_id: ClientSession::from(vec![1u8; 32]),
+            data: vec![0u8; size - 10],
+        };
 
-        let form_block_result =
-            enclave.form_block(&parent_block, &well_formed_encrypted_txs_with_proofs);
-        let expected_duplicate_key_image = new_transactions[0].key_images()[0];
+        assert_eq!(
+            enclave.client_tx_propose(message(limit + 1)),
+            Err(Error::ProposalTooLarge {
+                size: limit + 1,
+                limit
+            })
+        );
 
-        // Check
-        let expected = Err(Error::RedactTxs(format!(
-            "Duplicate key image: {:?}",
-            expected_duplicate_key_image
-        )));
+        // Messages at or under the limit get as far as decryption, which fails because the
+        // session is unknown.
+        for size in &[limit, limit - 1] {
+            assert_eq!(
+                enclave.client_tx_propose(message(*size)),
+                Err(Error::Attest(AttestEnclaveError::NotFound))
+            );
+        }
 
-        assert_eq!(form_block_result, expected);
+        // The default limit applies unless configured otherwise.
+        let default_enclave = SgxConsensusEnclave::default();
+        assert_eq!(
+            default_enclave.client_tx_propose(message(limit + 1)),
+            Err(Error::Attest(AttestEnclaveError::NotFound))
+        );
+
+        // Raising the limit admits the message from then on.
+        enclave.set_max_proposal_bytes(limit + 1).unwrap();
+        assert_eq!(
+            enclave.client_tx_propose(message(limit + 1)),
+            Err(Error::Attest(AttestEnclaveError::NotFound))
+        );
     }
 
     #[test]
-    fn form_block_refuses_duplicate_root_elements() {
+    // The signer fingerprint is stable for a given key and changes when the key changes.
+    fn test_get_signer_fingerprint() {
+        let (enclave, sealed) = init_enclave_with_sealed_key();
+        let fingerprint = enclave.get_signer_fingerprint().unwrap();
+        assert_eq!(enclave.get_signer_fingerprint(), Ok(fingerprint));
+        assert_eq!(fingerprint, signer_fingerprint(&enclave.get_signer().unwrap()));
+
+        // A different key has a different fingerprint.
+        let (other_enclave, _other_sealed) = init_enclave_with_sealed_key();
+        let other_fingerprint = other_enclave.get_signer_fingerprint().unwrap();
+        assert_ne!(other_fingerprint, fingerprint);
+
+        // Restoring the first enclave's key also restores its fingerprint.
+        let restarted_enclave = SgxConsensusEnclave::default();
+        let peer_id = ResponderId::from_str("node1.test.mobilecoin.com:8443").unwrap();
+        let client_id = ResponderId::from_str("node1.test.mobilecoin.com:3223").unwrap();
+        restarted_enclave
+            .enclave_init(&peer_id, &client_id, &Some(sealed))
+            .unwrap();
+        assert_eq!(restarted_enclave.get_signer_fingerprint(), Ok(fingerprint));
+    }
+
+    #[test]
+    // Until `enclave_init` succeeds the signing key is a placeholder, so nothing is signed with it.
+    fn test_signing_operations_require_init() {
         let enclave = SgxConsensusEnclave::default();
-        let mut rng = Hc128Rng::from_seed([77u8; 32]);
+        let origin_block = Block::new_origin_block(&[]);
 
-        // Initialize a ledger. `sender` is the owner of all outputs in the initial ledger.
-        let sender = AccountKey::random(&mut rng);
-        let mut ledger = create_ledger();
-        let n_blocks = 3;
-        initialize_ledger(&mut ledger, n_blocks, &sender, &mut rng);
+        assert_eq!(enclave.get_signer(), Err(Error::NotInitialized));
+        assert_eq!(enclave.get_signer_fingerprint(), Err(Error::NotInitialized));
+        assert_eq!(
+            enclave.form_block(&origin_block, &[]),
+            Err(Error::NotInitialized)
+        );
+        assert_eq!(
+            enclave.sign_propose_receipt(TxHash([3u8; 32]), 17),
+            Err(Error::NotInitialized)
+        );
+        assert_eq!(enclave.rotate_signing_key(), Err(Error::NotInitialized));
+
+        // The same calls succeed once the enclave is initialized.
+        let peer_id = ResponderId::from_str("node1.test.mobilecoin.com:8443").unwrap();
+        let client_id = ResponderId::from_str("node1.test.mobilecoin.com:3223").unwrap();
+        enclave.enclave_init(&peer_id, &client_id, &None).unwrap();
+        assert!(enclave.get_signer().is_ok());
+        assert!(enclave.get_signer_fingerprint().is_ok());
+        assert!(enclave
+            .sign_propose_receipt(TxHash([3u8; 32]), 17)
+            .is_ok());
+        assert!(enclave.rotate_signing_key().is_ok());
+        // An empty block has no membership proofs, so it now fails on its contents instead.
+        assert_eq!(
+            enclave.form_block(&origin_block, &[]),
+            Err(Error::InvalidLocalMembershipProof)
+        );
+    }
 
-        let mut ledger2 = create_ledger();
-        initialize_ledger(&mut ledger2, n_blocks + 1, &sender, &mut rng);
+    #[test]
+    // Propose receipts are signed by the block signing key and cover the tx hash and block index.
+    fn test_propose_receipt_verifies_against_signer() {
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        let signer = enclave.get_signer().unwrap();
+        let tx_hash = TxHash([3u8; 32]);
+        let block_index = 17;
+
+        let receipt = enclave.sign_propose_receipt(tx_hash, block_index).unwrap();
+        assert_eq!(receipt.tx_hash, tx_hash);
+        assert_eq!(receipt.block_index, block_index);
+        assert!(receipt.verify(&signer).is_ok());
+
+        // Altering either covered field invalidates the receipt.
+        let mut wrong_block_index = receipt.clone();
+        wrong_block_index.block_index += 1;
+        assert!(wrong_block_index.verify(&signer).is_err());
+
+        let mut wrong_tx_hash = receipt.clone();
+        wrong_tx_hash.tx_hash = TxHash([4u8; 32]);
+        assert!(wrong_tx_hash.verify(&signer).is_err());
+
+        // Another enclave's signer does not verify the receipt.
+        let other_signer = init_enclave_with_sealed_key().0.get_signer().unwrap();
+        assert!(receipt.verify(&other_signer).is_err());
+    }
 
-        // Create a few transactions from `sender` to `recipient`.
-        let num_transactions = 6;
-        let recipient = AccountKey::random(&mut rng);
+    #[test]
+    // A key exported by a previous enclave for this one replaces its block signing key.
+    //
+    // A report signed by IAS can not be produced here, so the key is exported without verifying
+    // the target's attestation bundle.
+    fn test_reseal_signing_key_valid_migration() {
+        let (old_enclave, old_sealed) = init_enclave_with_sealed_key();
+        let (new_enclave, _new_sealed) = init_enclave_with_sealed_key();
+        let replaced_signer = new_enclave.get_signer().unwrap();
+        assert_ne!(new_enclave.get_signer(), old_enclave.get_signer());
+
+        let target_body = new_enclave.report_body().unwrap();
+        let migration_blob = old_enclave
+            .seal_signing_key_for(&old_sealed, &target_body)
+            .unwrap();
+        let resealed = new_enclave
+            .reseal_signing_key(&old_sealed, &migration_blob)
+            .unwrap();
+        assert_eq!(new_enclave.get_signer(), old_enclave.get_signer());
 
-        // The first block contains a single transaction with MIN_RING_SIZE outputs.
-        let block_zero_transactions = ledger.get_transactions_by_block(0).unwrap();
-        let block_zero_redacted_tx = block_zero_transactions.get(0).unwrap();
+        // The replaced key is retired as by rotate_signing_key, and a repeated init call hands
+        // back the migrated key.
+        assert_eq!(new_enclave.is_trusted_signer(&replaced_signer), Ok(true));
+        let peer_id = ResponderId::from_str("node1.test.mobilecoin.com:8443").unwrap();
+        let client_id = ResponderId::from_str("node1.test.mobilecoin.com:3223").unwrap();
+        assert_eq!(
+            new_enclave.enclave_init(&peer_id, &client_id, &None),
+            Ok(resealed.clone())
+        );
 
-        let mut new_transactions = Vec::new();
-        for i in 0..num_transactions {
-            let tx_out = &block_zero_redacted_tx.outputs[i];
+        // The re-sealed key restores the same signing identity on startup.
+        let restarted_enclave = SgxConsensusEnclave::default();
+        restarted_enclave
+            .enclave_init(&peer_id, &client_id, &Some(resealed))
+            .unwrap();
+        assert_eq!(restarted_enclave.get_signer(), old_enclave.get_signer());
+    }
 
-            let tx = create_transaction(
-                &mut ledger,
-                tx_out,
-                &sender,
-                &recipient.default_subaddress(),
-                n_blocks + 1,
-                &mut rng,
-            );
-            new_transactions.push(tx);
+    #[test]
+    // Rotating the signing key swaps the signer in place, and only the new key signs blocks.
+    fn test_rotate_signing_key() {
+        let mut rng = Hc128Rng::from_seed([60u8; 32]);
+        let (enclave, old_sealed) = init_enclave_with_sealed_key();
+        let old_signer = enclave.get_signer().unwrap();
+        let kex_identity = enclave.get_identity().unwrap();
+
+        let (parent_block, _input_transactions, well_formed_encrypted_txs_with_proofs) =
+            form_block_candidates(&enclave, &[BASE_FEE], &mut rng);
+        let grandparent_block = block_below(&parent_block);
+        let (old_block, _redacted_txs, old_signature) = enclave
+            .form_block(&grandparent_block, &well_formed_encrypted_txs_with_proofs)
+            .unwrap();
+        assert_eq!(old_signature.signer(), &old_signer);
+
+        let (new_sealed, new_signer) = enclave.rotate_signing_key().unwrap();
+        assert_ne!(new_sealed, old_sealed);
+        assert_ne!(new_signer, old_signer);
+        assert_eq!(enclave.get_signer(), Ok(new_signer));
+        // Only the signing key changes, the key exchange identity used by sessions does not.
+        assert_eq!(enclave.get_identity(), Ok(kex_identity));
+
+        // Blocks are now signed by the new key, and the old key's signatures are no longer ours.
+        let (_block, _redacted_txs, new_signature) = enclave
+            .form_block(&parent_block, &well_formed_encrypted_txs_with_proofs)
+            .unwrap();
+        assert_eq!(new_signature.signer(), &new_signer);
+        assert_eq!(
+            enclave.verify_own_block_signature(&old_block, &old_signature),
+            Err(Error::Signature)
+        );
+
+        // Repeating the original init hands back the rotated key, not the replaced one.
+        let peer_id = ResponderId::from_str("node1.test.mobilecoin.com:8443").unwrap();
+        let client_id = ResponderId::from_str("node1.test.mobilecoin.com:3223").unwrap();
+        assert_eq!(
+            enclave.enclave_init(&peer_id, &client_id, &None),
+            Ok(new_sealed.clone())
+        );
+
+        // The new sealed key restores the rotated signing identity on startup.
+        let restarted_enclave = SgxConsensusEnclave::default();
+        restarted_enclave
+            .enclave_init(&peer_id, &client_id, &Some(new_sealed))
+            .unwrap();
+        assert_eq!(restarted_enclave.get_signer(), Ok(new_signer));
+    }
+
+    #[test]
+    // Keys replaced by `rotate_signing_key` stay trusted for `RETIRED_SIGNER_HISTORY` rotations,
+    // while unknown keys never are.
+    fn test_is_trusted_signer() {
+        let mut rng = Hc128Rng::from_seed([61u8; 32]);
+        let unknown = Ed25519Pair::from_random(&mut rng).public_key();
+        let enclave = SgxConsensusEnclave::default();
+        assert_eq!(enclave.is_trusted_signer(&unknown), Err(Error::NotInitialized));
+
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        let original_signer = enclave.get_signer().unwrap();
+        assert_eq!(enclave.is_trusted_signer(&original_signer), Ok(true));
+        assert_eq!(enclave.is_trusted_signer(&unknown), Ok(false));
+
+        let (_sealed, current_signer) = enclave.rotate_signing_key().unwrap();
+        assert_eq!(enclave.is_trusted_signer(&current_signer), Ok(true));
+        assert_eq!(enclave.is_trusted_signer(&original_signer), Ok(true));
+        assert_eq!(enclave.is_trusted_signer(&unknown), Ok(false));
+
+        // The original key is forgotten once enough newer keys were retired.
+        for _ in 0..RETIRED_SIGNER_HISTORY {
+            enclave.rotate_signing_key().unwrap();
         }
+        assert_eq!(enclave.is_trusted_signer(&original_signer), Ok(false));
+        assert_eq!(enclave.is_trusted_signer(&current_signer), Ok(true));
+    }
 
-        // Create WellFormedEncryptedTxs + proofs
-        let well_formed_encrypted_txs_with_proofs: Vec<(
-            WellFormedEncryptedTx,
-            Vec<TxOutMembershipProof>,
-        )> = new_transactions
-            .iter()
-            .enumerate()
-            .map(|(tx_idx, tx)| {
-                let well_formed_tx = WellFormedTx::from(tx.clone());
-                let encrypted_tx = enclave
-                    .encrypt_well_formed_tx(&well_formed_tx, &mut rng)
-                    .unwrap();
+    #[test]
+    // Migration blobs which were not exported for this enclave and sealed key must be rejected.
+    // Host builds do not bind sealing to an enclave identity, so a blob for an enclave with
+    // another MRENCLAVE can not be produced here.
+    fn test_reseal_signing_key_rejects_forged_migration() {
+        let mut rng = Hc128Rng::from_seed([1u8; 32]);
+        let (old_enclave, old_sealed) = init_enclave_with_sealed_key();
+        let (new_enclave, _new_sealed) = init_enclave_with_sealed_key();
+        let original_signer = new_enclave.get_signer().unwrap();
+        let target_body = new_enclave.report_body().unwrap();
+
+        // Exported for the target, but presented along with some other sealed key.
+        let migration_blob = old_enclave
+            .seal_signing_key_for(&old_sealed, &target_body)
+            .unwrap();
+        let (_other_enclave, other_sealed) = init_enclave_with_sealed_key();
+        assert_eq!(
+            new_enclave.reseal_signing_key(&other_sealed, &migration_blob),
+            Err(Error::SealedPurposeMismatch)
+        );
 
-                let highest_indices = well_formed_tx.tx.get_membership_proof_highest_indices();
-                let membership_proofs = highest_indices
-                    .iter()
-                    .map(|index| {
-                        // Make one of the proofs have a different root element by creating it from a different
-                        if tx_idx == 0 {
-                            ledger2
-                                .get_tx_out_proof_of_memberships(&[*index])
-                                .expect("failed getting proof")[0]
-                                .clone()
-                        } else {
-                            ledger
-                                .get_tx_out_proof_of_memberships(&[*index])
-                                .expect("failed getting proof")[0]
-                                .clone()
-                        }
-                    })
-                    .collect();
-                (encrypted_tx, membership_proofs)
-            })
-            .collect();
+        // A sealed signing key is not a migration blob, nor is a blob holding a chosen key.
+        assert_eq!(
+            new_enclave.reseal_signing_key(&old_sealed, &old_sealed),
+            Err(Error::SealedPurposeMismatch)
+        );
+        let chosen_key = Ed25519Pair::from_random(&mut rng);
+        let chosen_blob = new_enclave
+            .seal_blob(chosen_key.private_key().as_ref())
+            .unwrap();
+        assert_eq!(
+            new_enclave.reseal_signing_key(&old_sealed, &chosen_blob),
+            Err(Error::SealedPurposeMismatch)
+        );
 
-        // Form block
-        let parent_block = ledger.get_block(ledger.num_blocks().unwrap() - 1).unwrap();
+        // Not a sealed blob at all.
+        assert_eq!(
+            new_enclave.reseal_signing_key(&old_sealed, &[1, 2, 3]),
+            Err(Error::Serialization)
+        );
 
-        let form_block_result =
-            enclave.form_block(&parent_block, &well_formed_encrypted_txs_with_proofs);
+        // An uninitialized enclave has no signing key to replace.
+        assert_eq!(
+            SgxConsensusEnclave::default()
+                .reseal_signing_key(&old_sealed, &migration_blob),
+            Err(Error::NotInitialized)
+        );
 
-        // Check
-        let expected = Err(Error::MalformedTx(
-            TransactionValidationError::InvalidTxOutMembershipProof,
-        ));
-        assert_eq!(form_block_result, expected);
+        // The enclave's signing identity is untouched.
+        assert_eq!(new_enclave.get_signer(), Ok(original_signer));
+    }
+
+    #[test]
+    // Signing keys are only exported to a target whose bundle carries an IAS report. A report
+    // signed by IAS can not be produced here, so forged reports are all that can be checked.
+    fn test_export_signing_key_rejects_unattested_target() {
+        let (old_enclave, old_sealed) = init_enclave_with_sealed_key();
+        let (new_enclave, _new_sealed) = init_enclave_with_sealed_key();
+
+        // A bundle without a report.
+        let bundle = new_enclave.attestation_bundle().unwrap();
+        assert_eq!(bundle.ias_report, None);
+        assert_eq!(
+            old_enclave.export_signing_key(&old_sealed, &bundle),
+            Err(Error::Attest(AttestEnclaveError::NoReportAvailable))
+        );
+
+        // A bundle with a report IAS did not sign.
+        let forged = AttestationBundle {
+            ias_report: Some(VerificationReport {
+                http_body: IAS_WITH_PIB.into(),
+                ..Default::default()
+            }),
+            ..bundle
+        };
+        let result = old_enclave.export_signing_key(&old_sealed, &forged);
+        assert!(result.is_err());
+        assert_ne!(
+            result,
+            Err(Error::Attest(AttestEnclaveError::NoReportAvailable))
+        );
+    }
+
+    #[test]
+    // Blobs sealed with seal_blob unseal to the original plaintext.
+    fn test_seal_blob_round_trip() {
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+
+        let plaintexts: [&[u8]; 3] = [b"counter=42", &[], &[0xff; 1024]];
+        for plaintext in plaintexts.iter() {
+            let sealed = enclave.seal_blob(plaintext).unwrap();
+            assert_ne!(&sealed[..], *plaintext);
+            assert_eq!(enclave.unseal_blob(&sealed), Ok(plaintext.to_vec()));
+        }
+
+        assert_eq!(enclave.unseal_blob(&[1, 2, 3]), Err(Error::Serialization));
+    }
+
+    #[test]
+    // Sealed blobs and sealed signing keys share a key policy, so each is rejected where the
+    // other is expected. Host builds do not bind sealing to an enclave identity, so this is the
+    // separation that can be exercised here.
+    fn test_sealed_blobs_and_signing_keys_are_not_interchangeable() {
+        let (enclave, sealed_key) = init_enclave_with_sealed_key();
+
+        // The signing key can not be read out through unseal_blob.
+        assert_eq!(
+            enclave.unseal_blob(&sealed_key),
+            Err(Error::SealedPurposeMismatch)
+        );
+
+        // A blob holding a key chosen by untrusted can not be installed as the signing key.
+        let mut rng = Hc128Rng::from_seed([43u8; 32]);
+        let chosen_key = Ed25519Pair::from_random(&mut rng);
+        let sealed_blob = enclave
+            .seal_blob(chosen_key.private_key().as_ref())
+            .unwrap();
+
+        let peer_id = ResponderId::from_str("node1.test.mobilecoin.com:8443").unwrap();
+        let client_id = ResponderId::from_str("node1.test.mobilecoin.com:3223").unwrap();
+        let other_enclave = SgxConsensusEnclave::default();
+        assert_eq!(
+            other_enclave.enclave_init(&peer_id, &client_id, &Some(sealed_blob.clone())),
+            Err(Error::SealedPurposeMismatch)
+        );
+        assert_eq!(
+            enclave.reseal_signing_key(&sealed_key, &sealed_blob),
+            Err(Error::SealedPurposeMismatch)
+        );
+
+        // The failed call left the other enclave uninitialized.
+        assert!(other_enclave
+            .enclave_init(&peer_id, &client_id, &None)
+            .is_ok());
+        assert_ne!(other_enclave.get_signer(), Ok(chosen_key.public_key()));
+    }
+
+    #[test]
+    // A chunk size too small for all transactions produces several chunks, each within the
+    // limit, that together hold every transaction in order.
+    fn test_chunk_tx_list_splits_into_multiple_chunks() {
+        let txs: Vec<Vec<u8>> = (0..10u8).map(|i| vec![i; 100]).collect();
+        let max_len = 350;
+
+        let chunks = chunk_tx_list(txs.clone(), max_len).unwrap();
+        assert!(chunks.len() > 1);
+
+        for (i, chunk) in chunks.iter().enumerate() {
+            assert_eq!(chunk.chunk_index as usize, i);
+            assert_eq!(chunk.chunk_total as usize, chunks.len());
+            assert!(mcserial::encode(chunk).len() <= max_len);
+
+            // Each chunk decodes on its own.
+            let decoded = mcserial::decode::<TxList>(&mcserial::encode(chunk)).unwrap();
+            assert_eq!(decoded.txs, chunk.txs);
+        }
+
+        let reassembled: Vec<Vec<u8>> = chunks.into_iter().flat_map(|chunk| chunk.txs).collect();
+        assert_eq!(reassembled, txs);
+    }
+
+    #[test]
+    // A chunk size large enough for everything produces a single chunk.
+    fn test_chunk_tx_list_single_chunk() {
+        let txs: Vec<Vec<u8>> = (0..10u8).map(|i| vec![i; 100]).collect();
+        let max_len = 100_000;
+
+        let chunks = chunk_tx_list(txs.clone(), max_len).unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].chunk_index, 0);
+        assert_eq!(chunks[0].chunk_total, 1);
+        assert_eq!(chunks[0].txs, txs);
+        assert!(mcserial::encode(&chunks[0]).len() <= max_len);
+
+        // No transactions still produce a (single, empty) chunk.
+        let chunks = chunk_tx_list(Vec::new(), max_len).unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert!(chunks[0].txs.is_empty());
+    }
+
+    #[test]
+    // A transaction which can not fit in a chunk by itself is rejected.
+    fn test_chunk_tx_list_rejects_oversized_tx() {
+        let txs = vec![vec![1u8; 10], vec![2u8; 1000]];
+        assert!(chunk_tx_list(txs, 500).is_none());
+        assert!(chunk_tx_list(Vec::new(), TX_LIST_CHUNK_HEADER_LEN - 1).is_none());
+    }
+
+    #[test]
+    // The AAD and channel id sent with every chunk count towards the chunk size, so a message
+    // with a large AAD stays within the limit.
+    fn test_max_tx_list_len_leaves_room_for_aad() {
+        let txs: Vec<Vec<u8>> = (0..10u8).map(|i| vec![i; 100]).collect();
+        let aad_len = 300;
+        let channel_id_len = 32;
+        // Room for exactly two transactions, each encoded with a key and a length byte.
+        let max_chunk_bytes =
+            aad_len + channel_id_len + PEER_MESSAGE_TAG_LEN + TX_LIST_CHUNK_HEADER_LEN + 2 * 102;
+
+        let max_len = max_tx_list_len(max_chunk_bytes, aad_len, channel_id_len).unwrap();
+        let chunks = chunk_tx_list(txs.clone(), max_len).unwrap();
+        assert_eq!(chunks.len(), 5);
+        for chunk in &chunks {
+            let message_len =
+                aad_len + channel_id_len + mcserial::encode(chunk).len() + PEER_MESSAGE_TAG_LEN;
+            assert!(message_len <= max_chunk_bytes);
+        }
+
+        // One byte less leaves room for a single transaction per chunk.
+        let max_len = max_tx_list_len(max_chunk_bytes - 1, aad_len, channel_id_len).unwrap();
+        assert_eq!(chunk_tx_list(txs, max_len).unwrap().len(), 10);
+
+        // An AAD filling the whole chunk leaves no room at all.
+        assert_eq!(max_tx_list_len(aad_len, aad_len, channel_id_len), None);
+        assert_eq!(
+            max_tx_list_len(
+                aad_len + channel_id_len + PEER_MESSAGE_TAG_LEN,
+                aad_len,
+                channel_id_len,
+            ),
+            Some(0)
+        );
+    }
+
+    #[test]
+    // The receiver learns each chunk's position, so chunks decrypted out of order are put back in
+    // the order they were sent, and a missing chunk is noticed.
+    fn test_peer_tx_chunk_reassembly() {
+        let mut rng = Hc128Rng::from_seed([86u8; 32]);
+        let (enclave, _sealed) = init_enclave_with_sealed_key();
+        let (_parent_block, input_transactions, _well_formed_encrypted_txs_with_proofs) =
+            form_block_candidates(&enclave, &[BASE_FEE, BASE_FEE, BASE_FEE], &mut rng);
+
+        // Each chunk has room for the largest transaction, encoded with a key byte and a two byte
+        // length, but not for two.
+        let encoded: Vec<Vec<u8>> = input_transactions.iter().map(mcserial::encode).collect();
+        let max_tx_len = encoded.iter().map(Vec::len).max().unwrap();
+        let max_len = TX_LIST_CHUNK_HEADER_LEN + 1 + 2 + max_tx_len;
+        let payloads: Vec<Vec<u8>> = chunk_tx_list(encoded, max_len)
+            .unwrap()
+            .iter()
+            .map(mcserial::encode)
+            .collect();
+        assert_eq!(payloads.len(), 3);
+
+        let mut chunks = PeerTxChunks::new();
+        for chunk_index in &[2, 0, 1] {
+            let chunk = enclave
+                .peer_tx_chunk(&payloads[*chunk_index], &[])
+                .unwrap();
+            assert_eq!(chunk.chunk_index as usize, *chunk_index);
+            assert_eq!(chunk.chunk_total, 3);
+            chunks.insert(chunk).unwrap();
+        }
+        let tx_hashes: Vec<TxHash> = chunks
+            .into_tx_contexts()
+            .unwrap()
+            .into_iter()
+            .map(|tx_context| tx_context.unwrap().tx_hash)
+            .collect();
+        let expected: Vec<TxHash> = input_transactions.iter().map(Tx::tx_hash).collect();
+        assert_eq!(tx_hashes, expected);
+
+        // Without the middle chunk the batch is incomplete.
+        let mut chunks = PeerTxChunks::new();
+        for chunk_index in &[2, 0] {
+            let chunk = enclave
+                .peer_tx_chunk(&payloads[*chunk_index], &[])
+                .unwrap();
+            chunks.insert(chunk).unwrap();
+        }
+        assert_eq!(chunks.missing(), vec![1]);
+        assert_eq!(chunks.into_tx_contexts(), Err(Error::MissingChunk(1)));
+
+        // A payload from an enclave predating chunking is a batch of its own.
+        let unchunked = mcserial::encode(&TxList {
+            txs: Vec::new(),
+            chunk_index: 0,
+            chunk_total: 0,
+        });
+        let chunk = enclave.peer_tx_chunk(&unchunked, &[]).unwrap();
+        assert_eq!((chunk.chunk_index, chunk.chunk_total), (0, 1));
     }
 }
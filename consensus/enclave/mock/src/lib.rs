@@ -3,30 +3,43 @@
 //! Mock enclave, used for tests
 
 pub use consensus_enclave_api::{
-    ConsensusEnclave, ConsensusEnclaveProxy, Error, LocallyEncryptedTx, Result,
-    SealedBlockSigningKey, TxContext, WellFormedEncryptedTx, WellFormedTxContext,
+    AttestationBundle, BlockLimits, ByPriority, CallTimings, ConsensusEnclave,
+    ConsensusEnclaveProxy, EnclaveCapabilities, EnclaveStatus, EnclaveVersion, Error,
+    ExclusionReason, IasReportInfo, InclusionStatus, LocallyEncryptedTx, PeerTxChunk, PeerTxChunks,
+    ProposeReceipt, Result, SealedBlockSigningKey, SessionRef, SessionStats, TombstoneWindow,
+    TxContext, WellFormedEncryptedTx, WellFormedTxContext,
 };
 
-use attest::{IasNonce, Quote, QuoteNonce, Report, TargetInfo, VerificationReport};
+use consensus_enclave_api::{
+    digest_fee_policy, ias_report_issued_at, signer_fingerprint, WellFormedError, DEFAULT_TOKEN_ID,
+};
+
+use attest::{
+    IasNonce, Measurement, Quote, QuoteNonce, Report, ReportBody, TargetInfo, VerificationReport,
+};
 use attest_enclave_api::{
     ClientAuthRequest, ClientAuthResponse, ClientSession, EnclaveMessage, PeerAuthRequest,
     PeerAuthResponse, PeerSession,
 };
 use common::ResponderId;
-use keys::{Ed25519Pair, Ed25519Public, FromRandom, X25519EphemeralPrivate, X25519Public};
+use keys::{Ed25519Pair, Ed25519Public, FromRandom, Signer, X25519EphemeralPrivate, X25519Public};
 use mcrand::McRng;
 use rand_core::SeedableRng;
 use rand_hc::Hc128Rng;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use transaction::{
+    constants::BASE_FEE,
     ring_signature::KeyImage,
-    tx::{Tx, TxOutMembershipProof},
+    tx::{Tx, TxHash, TxOutMembershipProof},
     Block, BlockSignature, RedactedTx, BLOCK_VERSION,
 };
 
 #[derive(Clone)]
 pub struct ConsensusServiceMockEnclave {
     pub signing_keypair: Arc<Ed25519Pair>,
+
+    /// The lock held by `with_exclusive`, shared by every clone of this mock.
+    exclusive: Arc<Mutex<()>>,
 }
 
 impl Default for ConsensusServiceMockEnclave {
@@ -34,7 +47,10 @@ impl Default for ConsensusServiceMockEnclave {
         let mut csprng = Hc128Rng::seed_from_u64(0);
         let signing_keypair = Arc::new(Ed25519Pair::from_random(&mut csprng));
 
-        Self { signing_keypair }
+        Self {
+            signing_keypair,
+            exclusive: Arc::new(Mutex::new(())),
+        }
     }
 }
 
@@ -44,12 +60,14 @@ impl ConsensusServiceMockEnclave {
         let tx_hash = tx.tx_hash();
         let highest_indices = tx.get_membership_proof_highest_indices();
         let key_images: Vec<KeyImage> = tx.key_images();
+        let tombstone_block = tx.prefix.tombstone_block;
 
         TxContext {
             locally_encrypted_tx,
             tx_hash,
             highest_indices,
             key_images,
+            tombstone_block,
         }
     }
 }
@@ -64,6 +82,93 @@ impl ConsensusEnclave for ConsensusServiceMockEnclave {
         Ok(vec![])
     }
 
+    fn rotate_signing_key(&self) -> Result<(SealedBlockSigningKey, Ed25519Public)> {
+        Ok((vec![], self.signing_keypair.public_key()))
+    }
+
+    fn export_signing_key(
+        &self,
+        _old_sealed: &SealedBlockSigningKey,
+        _target: &AttestationBundle,
+    ) -> Result<Vec<u8>> {
+        Ok(vec![])
+    }
+
+    fn reseal_signing_key(
+        &self,
+        _old_sealed: &SealedBlockSigningKey,
+        _migration_blob: &[u8],
+    ) -> Result<SealedBlockSigningKey> {
+        Ok(vec![])
+    }
+
+    fn seal_blob(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        Ok(plaintext.to_vec())
+    }
+
+    fn unseal_blob(&self, sealed: &[u8]) -> Result<Vec<u8>> {
+        Ok(sealed.to_vec())
+    }
+
+    fn enclave_version(&self) -> Result<EnclaveVersion> {
+        // The mock keeps a single signing key, does not record timings, and does not track the
+        // blocks it forms or enforce the limits it is given, so the methods which would only
+        // pretend to do so are left out.
+        Ok(EnclaveVersion {
+            major: 0,
+            minor: 1,
+            patch: 0,
+            capabilities: EnclaveCapabilities::BATCH_WELL_FORMED
+                | EnclaveCapabilities::MINIMUM_FEE
+                | EnclaveCapabilities::STRUCTURED_WELL_FORMED_ERRORS
+                | EnclaveCapabilities::PEER_TX_PROPOSE_PARTIAL_FAILURE
+                | EnclaveCapabilities::VERSIONED_BLOCK_FORMATION
+                | EnclaveCapabilities::TX_PRIORITY
+                | EnclaveCapabilities::SIGNER_FINGERPRINT
+                | EnclaveCapabilities::PROPOSE_RECEIPT
+                | EnclaveCapabilities::CHUNKED_TXS_FOR_PEER
+                | EnclaveCapabilities::PEER_CLOSE_ALL
+                | EnclaveCapabilities::SESSION_STATS
+                | EnclaveCapabilities::IAS_REPORT_FRESHNESS
+                | EnclaveCapabilities::IAS_REPORT_INFO
+                | EnclaveCapabilities::VERIFY_OWN_BLOCK_SIGNATURE
+                | EnclaveCapabilities::CANCEL_EREPORT
+                | EnclaveCapabilities::CLIENT_SESSIONS
+                | EnclaveCapabilities::FORM_BLOCK_WITH_REPORT
+                | EnclaveCapabilities::CLIENT_ACCEPT_BUDGET
+                | EnclaveCapabilities::REPORT_BODY
+                | EnclaveCapabilities::RING_SIGNATURE_PREVALIDATION
+                | EnclaveCapabilities::SEALED_BLOBS
+                | EnclaveCapabilities::CLOSE_SESSIONS
+                | EnclaveCapabilities::MINIMUM_FEES
+                | EnclaveCapabilities::TX_HASH_VERIFICATION
+                | EnclaveCapabilities::FORM_BLOCK_COST_ESTIMATE
+                | EnclaveCapabilities::PEER_INIT_PINNED
+                | EnclaveCapabilities::BLOCK_VERSION_RANGE
+                | EnclaveCapabilities::ENCLAVE_STATUS
+                | EnclaveCapabilities::BATCH_CLIENT_DISCARD
+                | EnclaveCapabilities::IAS_REPORT_VALIDATION
+                | EnclaveCapabilities::FEE_POLICY_DIGEST
+                | EnclaveCapabilities::REDACT_SINGLE
+                | EnclaveCapabilities::IAS_REPORT_BLOCKS_REMAINING
+                | EnclaveCapabilities::SELF_RESPONDER_IDS
+                | EnclaveCapabilities::BLOCK_TOTAL_FEES
+                | EnclaveCapabilities::INTEGRITY_CHECK
+                | EnclaveCapabilities::SPENT_KEY_IMAGE_CHECK
+                | EnclaveCapabilities::IAS_REPORT_TIME
+                | EnclaveCapabilities::FORM_BLOCK_EXCLUDE
+                | EnclaveCapabilities::PEER_TX_DEDUP
+                | EnclaveCapabilities::TRUSTED_SIGNER_CHECK
+                | EnclaveCapabilities::BATCH_OWN_BLOCK_SIGNATURES
+                | EnclaveCapabilities::TX_FEE_PREVIEW
+                | EnclaveCapabilities::FORM_EMPTY_BLOCK
+                | EnclaveCapabilities::CLIENT_CONNECT_INFO
+                | EnclaveCapabilities::CONTEXT_OF
+                | EnclaveCapabilities::ATTESTATION_BUNDLE
+                | EnclaveCapabilities::PEER_TX_CHUNK_POSITION,
+        })
+    }
+
     fn get_identity(&self) -> Result<X25519Public> {
         let mut csprng = Hc128Rng::seed_from_u64(0);
         let privkey = X25519EphemeralPrivate::from_random(&mut csprng);
@@ -74,6 +179,39 @@ impl ConsensusEnclave for ConsensusServiceMockEnclave {
         Ok(self.signing_keypair.public_key())
     }
 
+    fn get_signer_fingerprint(&self) -> Result<[u8; 32]> {
+        Ok(signer_fingerprint(&self.signing_keypair.public_key()))
+    }
+
+    fn self_peer_id(&self) -> Result<ResponderId> {
+        Ok(ResponderId::default())
+    }
+
+    fn self_client_id(&self) -> Result<ResponderId> {
+        Ok(ResponderId::default())
+    }
+
+    fn client_connect_info(&self) -> Result<(ResponderId, X25519Public)> {
+        Ok((self.self_client_id()?, self.get_identity()?))
+    }
+
+    fn session_stats(&self) -> Result<SessionStats> {
+        Ok(SessionStats::default())
+    }
+
+    fn enclave_status(&self) -> Result<EnclaveStatus> {
+        Ok(EnclaveStatus {
+            sessions: SessionStats::default(),
+            ias_report_info: Some(IasReportInfo::default()),
+            signing_height: 0,
+        })
+    }
+
+    fn last_call_timings(&self) -> Result<CallTimings> {
+        // The mock does not run inside an enclave, so there is nothing to profile.
+        Err(Error::ProfilingUnavailable)
+    }
+
     fn new_ereport(&self, _qe_info: TargetInfo) -> Result<(Report, QuoteNonce)> {
         Ok((Report::default(), QuoteNonce::default()))
     }
@@ -82,14 +220,59 @@ impl ConsensusEnclave for ConsensusServiceMockEnclave {
         Ok(IasNonce::default())
     }
 
+    fn cancel_ereport(&self) -> Result<()> {
+        Ok(())
+    }
+
     fn verify_ias_report(&self, _ias_report: VerificationReport) -> Result<()> {
         Ok(())
     }
 
+    fn verify_ias_report_at(
+        &self,
+        _ias_report: VerificationReport,
+        _now_unix_secs: u64,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn validate_ias_report(&self, _ias_report: &VerificationReport) -> Result<()> {
+        Ok(())
+    }
+
     fn get_ias_report(&self) -> Result<VerificationReport> {
         Ok(VerificationReport::default())
     }
 
+    fn get_ias_report_info(&self) -> Result<IasReportInfo> {
+        Ok(IasReportInfo::default())
+    }
+
+    fn ias_report_blocks_remaining(
+        &self,
+        _now_unix_secs: u64,
+        _secs_per_block: u64,
+        blocks_per_refresh: u64,
+    ) -> Result<u64> {
+        Ok(blocks_per_refresh)
+    }
+
+    fn ias_report_time(&self, report: &VerificationReport) -> Result<u64> {
+        ias_report_issued_at(report)
+    }
+
+    fn report_body(&self) -> Result<ReportBody> {
+        Ok(ReportBody::default())
+    }
+
+    fn attestation_bundle(&self) -> Result<AttestationBundle> {
+        Ok(AttestationBundle {
+            report_body: self.report_body()?,
+            identity: self.get_identity()?,
+            ias_report: Some(self.get_ias_report()?),
+        })
+    }
+
     fn client_accept(
         &self,
         _req: ClientAuthRequest,
@@ -97,18 +280,45 @@ impl ConsensusEnclave for ConsensusServiceMockEnclave {
         Ok((ClientAuthResponse::default(), ClientSession::default()))
     }
 
+    fn client_accept_with_budget(
+        &self,
+        req: ClientAuthRequest,
+        _max_sessions: usize,
+    ) -> Result<(ClientAuthResponse, ClientSession)> {
+        self.client_accept(req)
+    }
+
     fn client_close(&self, _channel_id: ClientSession) -> Result<()> {
         Ok(())
     }
 
+    fn client_sessions(&self) -> Result<Vec<ClientSession>> {
+        Ok(Vec::new())
+    }
+
     fn client_discard_message(&self, _msg: EnclaveMessage<ClientSession>) -> Result<()> {
         Ok(())
     }
 
+    fn client_discard_messages(
+        &self,
+        msgs: Vec<EnclaveMessage<ClientSession>>,
+    ) -> Result<Vec<Result<()>>> {
+        Ok(vec![Ok(()); msgs.len()])
+    }
+
     fn peer_init(&self, _node_id: &ResponderId) -> Result<PeerAuthRequest> {
         Ok(vec![].into())
     }
 
+    fn peer_init_pinned(
+        &self,
+        _node_id: &ResponderId,
+        _expected: Measurement,
+    ) -> Result<PeerAuthRequest> {
+        Ok(vec![].into())
+    }
+
     fn peer_accept(&self, _req: PeerAuthRequest) -> Result<(PeerAuthResponse, PeerSession)> {
         Ok((PeerAuthResponse::default(), PeerSession::default()))
     }
@@ -117,18 +327,81 @@ impl ConsensusEnclave for ConsensusServiceMockEnclave {
         Ok(vec![].into())
     }
 
+    fn set_peer_identity_policy(&self, _allowed: Vec<Measurement>) -> Result<()> {
+        Ok(())
+    }
+
     fn peer_close(&self, _msg: &PeerSession) -> Result<()> {
         Ok(())
     }
 
+    fn peer_close_all(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn close_sessions(
+        &self,
+        clients: &[ClientSession],
+        peers: &[PeerSession],
+    ) -> Result<Vec<(SessionRef, Result<()>)>> {
+        Ok(clients
+            .iter()
+            .cloned()
+            .map(SessionRef::Client)
+            .chain(peers.iter().cloned().map(SessionRef::Peer))
+            .map(|session| (session, Ok(())))
+            .collect())
+    }
+
     fn client_tx_propose(&self, _msg: EnclaveMessage<ClientSession>) -> Result<TxContext> {
         Ok(TxContext::default())
     }
 
-    fn peer_tx_propose(&self, _msg: EnclaveMessage<PeerSession>) -> Result<Vec<TxContext>> {
+    fn client_tx_propose_with_receipt(
+        &self,
+        msg: EnclaveMessage<ClientSession>,
+        block_index: u64,
+    ) -> Result<(TxContext, ProposeReceipt)> {
+        let tx_context = self.client_tx_propose(msg)?;
+        let signature = self
+            .signing_keypair
+            .try_sign(&ProposeReceipt::message(&tx_context.tx_hash, block_index))?;
+        let receipt = ProposeReceipt {
+            tx_hash: tx_context.tx_hash,
+            block_index,
+            signature,
+        };
+        Ok((tx_context, receipt))
+    }
+
+    fn peer_tx_propose(
+        &self,
+        _msg: EnclaveMessage<PeerSession>,
+    ) -> Result<Vec<Result<TxContext>>> {
         Ok(Vec::default())
     }
 
+    fn peer_tx_propose_dedup(
+        &self,
+        msg: EnclaveMessage<PeerSession>,
+        _known_hashes: &[TxHash],
+    ) -> Result<Vec<Result<TxContext>>> {
+        self.peer_tx_propose(msg)
+    }
+
+    fn peer_tx_propose_chunk(
+        &self,
+        msg: EnclaveMessage<PeerSession>,
+        known_hashes: &[TxHash],
+    ) -> Result<PeerTxChunk> {
+        // The mock sends every batch in a single message.
+        Ok(PeerTxChunk {
+            chunk_index: 0,
+            chunk_total: 1,
+            tx_contexts: self.peer_tx_propose_dedup(msg, known_hashes)?,
+        })
+    }
+
     fn tx_is_well_formed(
         &self,
         locally_encrypted_tx: LocallyEncryptedTx,
@@ -142,6 +415,77 @@ impl ConsensusEnclave for ConsensusServiceMockEnclave {
         Ok((well_formed_encrypted_tx, well_formed_tx_context))
     }
 
+    fn txs_are_well_formed(
+        &self,
+        txs: Vec<(LocallyEncryptedTx, u64, Vec<TxOutMembershipProof>)>,
+    ) -> Result<Vec<Result<(WellFormedEncryptedTx, WellFormedTxContext)>>> {
+        Ok(txs
+            .into_iter()
+            .map(|(locally_encrypted_tx, block_index, proofs)| {
+                self.tx_is_well_formed(locally_encrypted_tx, block_index, proofs)
+            })
+            .collect())
+    }
+
+    fn tx_is_well_formed_unspent(
+        &self,
+        locally_encrypted_tx: LocallyEncryptedTx,
+        block_index: u64,
+        proofs: Vec<TxOutMembershipProof>,
+        spent_key_images: &[KeyImage],
+    ) -> Result<(WellFormedEncryptedTx, WellFormedTxContext)> {
+        let tx: Tx = mcserial::decode(&locally_encrypted_tx.0)?;
+        if let Some(key_image) = tx
+            .key_images()
+            .into_iter()
+            .find(|key_image| spent_key_images.contains(key_image))
+        {
+            return Err(WellFormedError::SpentKeyImage(key_image).into());
+        }
+        self.tx_is_well_formed(locally_encrypted_tx, block_index, proofs)
+    }
+
+    fn tx_ring_signature_is_valid(&self, locally_encrypted_tx: &LocallyEncryptedTx) -> Result<()> {
+        let _tx: Tx = mcserial::decode(&locally_encrypted_tx.0)?;
+        Ok(())
+    }
+
+    fn integrity_check(&self, locally_encrypted_tx: &LocallyEncryptedTx) -> Result<()> {
+        // These bytes are normally an enclave-encrypted Tx, but here, it is just serialized.
+        mcserial::decode::<Tx>(&locally_encrypted_tx.0).or(Err(Error::Integrity))?;
+        Ok(())
+    }
+
+    fn verify_tx_hash(
+        &self,
+        locally_encrypted_tx: &LocallyEncryptedTx,
+        expected: &TxHash,
+    ) -> Result<()> {
+        let tx: Tx = mcserial::decode(&locally_encrypted_tx.0)?;
+        if tx.tx_hash() != *expected {
+            return Err(Error::TxHashMismatch);
+        }
+        Ok(())
+    }
+
+    fn tx_fee_preview(&self, locally_encrypted_tx: &LocallyEncryptedTx) -> Result<(u64, u64)> {
+        let tx: Tx = mcserial::decode(&locally_encrypted_tx.0)?;
+        Ok((DEFAULT_TOKEN_ID, tx.prefix.fee))
+    }
+
+    fn get_minimum_fee(&self, _block_index: u64) -> Result<u64> {
+        // The fee policy does not currently depend on the block index.
+        Ok(BASE_FEE)
+    }
+
+    fn get_minimum_fees(&self) -> Result<Vec<(u64, u64)>> {
+        Ok(vec![(DEFAULT_TOKEN_ID, BASE_FEE)])
+    }
+
+    fn fee_policy_digest(&self) -> Result<[u8; 32]> {
+        Ok(digest_fee_policy(&self.get_minimum_fees()?))
+    }
+
     fn txs_for_peer(
         &self,
         _encrypted_txs: &[WellFormedEncryptedTx],
@@ -151,17 +495,41 @@ impl ConsensusEnclave for ConsensusServiceMockEnclave {
         Ok(EnclaveMessage::default())
     }
 
+    fn txs_for_peer_chunked(
+        &self,
+        _encrypted_txs: &[WellFormedEncryptedTx],
+        _aad: &[u8],
+        _peer: &PeerSession,
+        _max_chunk_bytes: usize,
+    ) -> Result<Vec<EnclaveMessage<PeerSession>>> {
+        Ok(vec![EnclaveMessage::default()])
+    }
+
     fn form_block(
         &self,
         parent_block: &Block,
         encrypted_txs_with_proofs: &[(WellFormedEncryptedTx, Vec<TxOutMembershipProof>)],
     ) -> Result<(Block, Vec<RedactedTx>, BlockSignature)> {
+        self.form_block_versioned(parent_block, encrypted_txs_with_proofs, BLOCK_VERSION)
+    }
+
+    fn form_block_versioned(
+        &self,
+        parent_block: &Block,
+        encrypted_txs_with_proofs: &[(WellFormedEncryptedTx, Vec<TxOutMembershipProof>)],
+        block_version: u32,
+    ) -> Result<(Block, Vec<RedactedTx>, BlockSignature)> {
+        // Only the current block format is supported.
+        if block_version != BLOCK_VERSION {
+            return Err(Error::UnsupportedBlockVersion(block_version));
+        }
+
         let transactions_with_proofs: Vec<(Tx, Vec<TxOutMembershipProof>)> =
             encrypted_txs_with_proofs
                 .iter()
                 .map(|(encrypted_tx, proofs)| {
                     // These bytes are normally an enclave-encrypted Tx, but here, it is just serialized.
-                    let ciphertext = &encrypted_tx.0;
+                    let ciphertext = encrypted_tx.as_ref();
                     let tx = mcserial::decode::<Tx>(ciphertext).unwrap();
                     (tx, proofs.clone())
                 })
@@ -197,7 +565,7 @@ impl ConsensusEnclave for ConsensusServiceMockEnclave {
             .collect();
 
         let block = Block::new(
-            BLOCK_VERSION,
+            block_version,
             &parent_block.id,
             parent_block.index + 1,
             &root_elements[0],
@@ -208,7 +576,300 @@ impl ConsensusEnclave for ConsensusServiceMockEnclave {
 
         Ok((block, redacted_transactions, signature))
     }
+
+    fn block_version_range(&self) -> Result<(u32, u32)> {
+        Ok((BLOCK_VERSION, BLOCK_VERSION))
+    }
+
+    fn form_block_with_report(
+        &self,
+        parent_block: &Block,
+        encrypted_txs_with_proofs: &[(WellFormedEncryptedTx, Vec<TxOutMembershipProof>)],
+    ) -> Result<(Block, Vec<RedactedTx>, BlockSignature, Vec<(TxHash, InclusionStatus)>)> {
+        // The mock never leaves transactions out of a block.
+        let report = encrypted_txs_with_proofs
+            .iter()
+            .map(|(encrypted_tx, _proofs)| {
+                let tx = mcserial::decode::<Tx>(encrypted_tx.as_ref()).unwrap();
+                (tx.tx_hash(), InclusionStatus::Included)
+            })
+            .collect();
+        let (block, redacted_transactions, signature) =
+            self.form_block(parent_block, encrypted_txs_with_proofs)?;
+        Ok((block, redacted_transactions, signature, report))
+    }
+
+    fn form_block_excluding(
+        &self,
+        parent_block: &Block,
+        encrypted_txs_with_proofs: &[(WellFormedEncryptedTx, Vec<TxOutMembershipProof>)],
+        exclude: &[TxHash],
+    ) -> Result<(Block, Vec<RedactedTx>, BlockSignature)> {
+        let remaining: Vec<_> = encrypted_txs_with_proofs
+            .iter()
+            .filter(|(encrypted_tx, _proofs)| {
+                let tx = mcserial::decode::<Tx>(encrypted_tx.as_ref()).unwrap();
+                !exclude.contains(&tx.tx_hash())
+            })
+            .cloned()
+            .collect();
+        self.form_block(parent_block, &remaining)
+    }
+
+    fn form_empty_block(
+        &self,
+        parent_block: &Block,
+        root_proof: &TxOutMembershipProof,
+    ) -> Result<(Block, Vec<RedactedTx>, BlockSignature)> {
+        let root_element = root_proof
+            .elements
+            .last() // The last element contains the root hash.
+            .ok_or(Error::InvalidLocalMembershipProof)?;
+
+        // The mock mints no fee outputs, so a single transaction without outputs stands in for
+        // one, which is enough for the ledger to accept the block.
+        let redacted_transactions = vec![RedactedTx::new(Vec::new(), Vec::new())];
+        let block = Block::new(
+            BLOCK_VERSION,
+            &parent_block.id,
+            parent_block.index + 1,
+            root_element,
+            &redacted_transactions,
+        );
+        let signature = BlockSignature::from_block_and_keypair(&block, &self.signing_keypair)?;
+        Ok((block, redacted_transactions, signature))
+    }
+
+    fn estimate_form_block_cost(
+        &self,
+        txs: &[(WellFormedEncryptedTx, Vec<TxOutMembershipProof>)],
+    ) -> Result<usize> {
+        // The mock holds each tx once, unencrypted.
+        Ok(txs.iter().map(|(tx, _proofs)| tx.encrypted_len()).sum())
+    }
+
+    fn redact_single(&self, encrypted_tx: &WellFormedEncryptedTx) -> Result<RedactedTx> {
+        // These bytes are normally an enclave-encrypted Tx, but here, it is just serialized.
+        Ok(mcserial::decode::<Tx>(encrypted_tx.as_ref())?.redact())
+    }
+
+    fn context_of(&self, encrypted_tx: &WellFormedEncryptedTx) -> Result<WellFormedTxContext> {
+        // These bytes are normally an enclave-encrypted Tx, but here, it is just serialized.
+        let tx = mcserial::decode::<Tx>(encrypted_tx.as_ref())?;
+        Ok(WellFormedTxContext::from(&tx))
+    }
+
+    fn block_total_fees(&self, _block: &Block, _redacted: &[RedactedTx]) -> Result<u64> {
+        // The mock does not mint a fee output into the blocks it forms.
+        Ok(0)
+    }
+
+    fn set_block_limits(&self, _limits: BlockLimits) -> Result<()> {
+        Ok(())
+    }
+
+    fn set_max_proposal_bytes(&self, _max_proposal_bytes: usize) -> Result<()> {
+        Ok(())
+    }
+
+    fn set_tombstone_window(&self, _window: TombstoneWindow) -> Result<()> {
+        Ok(())
+    }
+
+    fn current_signing_height(&self) -> Result<u64> {
+        Ok(0)
+    }
+
+    fn included_in_block(&self, _tx_hash: &TxHash) -> Result<Option<u64>> {
+        Ok(None)
+    }
+
+    fn recent_key_images(&self, _since_block: u64) -> Result<Vec<KeyImage>> {
+        Ok(Vec::new())
+    }
+
+    fn verify_own_block_signature(&self, block: &Block, signature: &BlockSignature) -> Result<()> {
+        if signature.signer() != &self.signing_keypair.public_key() {
+            return Err(Error::Signature);
+        }
+        Ok(signature.verify(block)?)
+    }
+
+    fn verify_own_block_signatures(&self, blocks: &[(Block, BlockSignature)]) -> Result<Vec<bool>> {
+        Ok(blocks
+            .iter()
+            .map(|(block, signature)| self.verify_own_block_signature(block, signature).is_ok())
+            .collect())
+    }
+
+    fn is_trusted_signer(&self, key: &Ed25519Public) -> Result<bool> {
+        // The mock never rotates its signing key.
+        Ok(key == &self.signing_keypair.public_key())
+    }
 }
 
-// Get the marker trait as well
-impl ConsensusEnclaveProxy for ConsensusServiceMockEnclave {}
+// Get the "handle" trait as well
+impl ConsensusEnclaveProxy for ConsensusServiceMockEnclave {
+    fn with_exclusive<R>(&self, f: impl FnOnce() -> R) -> Result<R> {
+        let _guard = self.exclusive.lock()?;
+        Ok(f())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ledger_db::Ledger;
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+    };
+    use transaction::account_keys::AccountKey;
+    use transaction_test_utils::{create_ledger, create_transaction, initialize_ledger};
+
+    /// Creates a valid tx spending the last output of a fresh ledger, the block it should be
+    /// formed on top of, and the membership proofs needed to do so.
+    fn tx_and_parent_block(rng: &mut Hc128Rng) -> (Tx, Block, Vec<TxOutMembershipProof>) {
+        let sender = AccountKey::random(rng);
+        let recipient = AccountKey::random(rng);
+
+        let mut ledger = create_ledger();
+        let n_blocks = 3;
+        initialize_ledger(&mut ledger, n_blocks, &sender, rng);
+
+        // Only the TxOut in the last block is unspent.
+        let mut transactions = ledger.get_transactions_by_block(n_blocks - 1).unwrap();
+        let tx_stored = transactions.pop().unwrap();
+        let tx_out = tx_stored.outputs[0].clone();
+
+        let tx = create_transaction(
+            &mut ledger,
+            &tx_out,
+            &sender,
+            &recipient.default_subaddress(),
+            n_blocks + 1,
+            rng,
+        );
+        let proofs = ledger
+            .get_tx_out_proof_of_memberships(&tx.get_membership_proof_highest_indices())
+            .unwrap();
+        let parent_block = ledger.get_block(n_blocks - 1).unwrap();
+
+        (tx, parent_block, proofs)
+    }
+
+    #[test]
+    // A tx found well-formed forms a block containing it, signed by the mock's key.
+    fn test_tx_is_well_formed_then_form_block() {
+        let mut rng = Hc128Rng::seed_from_u64(1);
+        let enclave = ConsensusServiceMockEnclave::default();
+        let (tx, parent_block, proofs) = tx_and_parent_block(&mut rng);
+
+        let tx_context = ConsensusServiceMockEnclave::tx_to_tx_context(&tx);
+        let (well_formed_encrypted_tx, well_formed_tx_context) = enclave
+            .tx_is_well_formed(
+                tx_context.locally_encrypted_tx,
+                parent_block.index,
+                proofs.clone(),
+            )
+            .unwrap();
+        assert_eq!(well_formed_tx_context.tx_hash(), &tx.tx_hash());
+        assert_eq!(well_formed_tx_context.fee(), tx.prefix.fee);
+
+        let (block, redacted_txs, signature) = enclave
+            .form_block(&parent_block, &[(well_formed_encrypted_tx, proofs)])
+            .unwrap();
+        assert_eq!(block.parent_id, parent_block.id);
+        assert_eq!(block.index, parent_block.index + 1);
+        assert_eq!(redacted_txs, vec![tx.redact()]);
+        assert_eq!(signature.signer(), &enclave.get_signer().unwrap());
+        assert!(signature.verify(&block).is_ok());
+    }
+
+    #[test]
+    // Mocks use the same deterministic key, so they form and sign identical blocks.
+    fn test_form_block_is_deterministic() {
+        let mut rng = Hc128Rng::seed_from_u64(2);
+        let (tx, parent_block, proofs) = tx_and_parent_block(&mut rng);
+        let txs = [(WellFormedEncryptedTx(mcserial::encode(&tx)), proofs)];
+
+        let first = ConsensusServiceMockEnclave::default();
+        let second = ConsensusServiceMockEnclave::default();
+        assert_eq!(first.get_signer(), second.get_signer());
+        assert_eq!(
+            first.form_block(&parent_block, &txs),
+            second.form_block(&parent_block, &txs)
+        );
+    }
+
+    #[test]
+    // Only the capabilities the mock really implements are advertised.
+    fn test_enclave_version_lists_implemented_capabilities() {
+        let enclave = ConsensusServiceMockEnclave::default();
+        let version = enclave.enclave_version().unwrap();
+
+        assert!(!version.supports(EnclaveCapabilities::CALL_TIMINGS));
+        assert_eq!(enclave.last_call_timings(), Err(Error::ProfilingUnavailable));
+
+        // The signing key never changes.
+        assert!(!version.supports(EnclaveCapabilities::SIGNING_KEY_ROTATION));
+        assert!(!version.supports(EnclaveCapabilities::SIGNING_KEY_MIGRATION));
+        let signer = enclave.get_signer().unwrap();
+        enclave.rotate_signing_key().unwrap();
+        assert_eq!(enclave.get_signer(), Ok(signer));
+
+        // Limits are accepted but not enforced, and formed blocks are not tracked.
+        assert!(!version.supports(EnclaveCapabilities::BLOCK_LIMITS));
+        assert!(!version.supports(EnclaveCapabilities::MAX_PROPOSAL_BYTES));
+        assert!(!version.supports(EnclaveCapabilities::TOMBSTONE_WINDOW));
+        assert!(!version.supports(EnclaveCapabilities::PEER_IDENTITY_POLICY));
+        assert!(!version.supports(EnclaveCapabilities::INCLUSION_LOOKUP));
+        assert!(!version.supports(EnclaveCapabilities::RECENT_KEY_IMAGES));
+        assert!(!version.supports(EnclaveCapabilities::SIGNING_HEIGHT));
+
+        // Methods the mock does implement are still advertised.
+        assert!(version.supports(EnclaveCapabilities::BATCH_WELL_FORMED));
+        assert!(version.supports(EnclaveCapabilities::FORM_EMPTY_BLOCK));
+        assert!(version.supports(EnclaveCapabilities::TRUSTED_SIGNER_CHECK));
+        assert!(enclave.is_trusted_signer(&signer).unwrap());
+    }
+
+    #[test]
+    // Sections run through `with_exclusive` on clones of one mock, forming blocks on some threads
+    // and rotating the signing key on others, never overlap.
+    fn test_with_exclusive_serializes_sections() {
+        let mut rng = Hc128Rng::seed_from_u64(3);
+        let (tx, parent_block, proofs) = tx_and_parent_block(&mut rng);
+        let txs = vec![(WellFormedEncryptedTx(mcserial::encode(&tx)), proofs)];
+        let enclave = ConsensusServiceMockEnclave::default();
+        let active = Arc::new(AtomicUsize::new(0));
+
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let enclave = enclave.clone();
+                let parent_block = parent_block.clone();
+                let txs = txs.clone();
+                let active = active.clone();
+                thread::spawn(move || {
+                    for _ in 0..20 {
+                        enclave
+                            .with_exclusive(|| {
+                                assert_eq!(active.fetch_add(1, Ordering::SeqCst), 0);
+                                if i % 2 == 0 {
+                                    enclave.form_block(&parent_block, &txs).unwrap();
+                                } else {
+                                    enclave.rotate_signing_key().unwrap();
+                                }
+                                thread::yield_now();
+                                active.fetch_sub(1, Ordering::SeqCst);
+                            })
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+}
@@ -3,12 +3,16 @@
 //! The Consensus Service SGX Enclave Proxy
 
 pub use consensus_enclave_api::{
-    ConsensusEnclave, ConsensusEnclaveProxy, EnclaveCall, Error, LocallyEncryptedTx, Result,
-    TxContext, WellFormedEncryptedTx, WellFormedTxContext,
+    AttestationBundle, BlockLimits, ByPriority, CallTimings, ConsensusEnclave,
+    ConsensusEnclaveProxy, EnclaveCall, EnclaveCapabilities, EnclaveStatus, EnclaveVersion, Error,
+    ExclusionReason, IasReportInfo, InclusionStatus, LocallyEncryptedTx, PeerTxChunk, PeerTxChunks,
+    ProposeReceipt, Result, SessionRef, SessionStats, TombstoneWindow, TxContext,
+    WellFormedEncryptedTx, WellFormedTxContext,
 };
 
 use attest::{
-    IasNonce, Quote, QuoteNonce, Report, SgxError, TargetInfo, VerificationReport, DEBUG_ENCLAVE,
+    IasNonce, Measurement, Quote, QuoteNonce, Report, ReportBody, SgxError, TargetInfo,
+    VerificationReport, DEBUG_ENCLAVE,
 };
 use attest_enclave_api::{
     ClientAuthRequest, ClientAuthResponse, ClientSession, EnclaveMessage, PeerAuthRequest,
@@ -19,8 +23,16 @@ use enclave_boundary::untrusted::make_variable_length_ecall;
 use keys::{Ed25519Public, X25519Public};
 use sgx_types::{sgx_enclave_id_t, sgx_status_t, *};
 use sgx_urts::SgxEnclave;
-use std::{path, result::Result as StdResult, sync::Arc};
-use transaction::{tx::TxOutMembershipProof, Block, BlockSignature, RedactedTx};
+use std::{
+    path,
+    result::Result as StdResult,
+    sync::{Arc, Mutex},
+};
+use transaction::{
+    ring_signature::KeyImage,
+    tx::{TxHash, TxOutMembershipProof},
+    Block, BlockSignature, RedactedTx,
+};
 
 #[allow(unused_imports)]
 use sgx_slog;
@@ -33,6 +45,9 @@ pub struct ConsensusServiceSgxEnclave {
     /// Hold a reference counter to the enclave to prevent destruction,
     /// this object is a handle to an enclave rather than having its lifetime tied to the actual enclave.
     enclave: Arc<SgxEnclave>,
+
+    /// The lock held by `with_exclusive`, shared by every clone of this handle.
+    exclusive: Arc<Mutex<()>>,
 }
 
 impl ConsensusServiceSgxEnclave {
@@ -65,6 +80,7 @@ impl ConsensusServiceSgxEnclave {
 
         let sgx_enclave = ConsensusServiceSgxEnclave {
             enclave: Arc::new(enclave),
+            exclusive: Arc::new(Mutex::new(())),
         };
 
         let sealed_key = sgx_enclave
@@ -103,6 +119,56 @@ impl ConsensusEnclave for ConsensusServiceSgxEnclave {
         mcserial::deserialize(&outbuf[..])?
     }
 
+    fn export_signing_key(
+        &self,
+        old_sealed: &SealedBlockSigningKey,
+        target: &AttestationBundle,
+    ) -> Result<Vec<u8>> {
+        let inbuf = mcserial::serialize(&EnclaveCall::ExportSigningKey(
+            old_sealed.clone(),
+            target.clone(),
+        ))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn reseal_signing_key(
+        &self,
+        old_sealed: &SealedBlockSigningKey,
+        migration_blob: &[u8],
+    ) -> Result<SealedBlockSigningKey> {
+        let inbuf = mcserial::serialize(&EnclaveCall::ResealSigningKey(
+            old_sealed.clone(),
+            migration_blob.to_vec(),
+        ))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn rotate_signing_key(&self) -> Result<(SealedBlockSigningKey, Ed25519Public)> {
+        let inbuf = mcserial::serialize(&EnclaveCall::RotateSigningKey)?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn seal_blob(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let inbuf = mcserial::serialize(&EnclaveCall::SealBlob(plaintext.to_vec()))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn unseal_blob(&self, sealed: &[u8]) -> Result<Vec<u8>> {
+        let inbuf = mcserial::serialize(&EnclaveCall::UnsealBlob(sealed.to_vec()))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn enclave_version(&self) -> Result<EnclaveVersion> {
+        let inbuf = mcserial::serialize(&EnclaveCall::EnclaveVersion)?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
     fn get_identity(&self) -> Result<X25519Public> {
         let inbuf = mcserial::serialize(&EnclaveCall::GetIdentity)?;
         let outbuf = self.enclave_call(&inbuf)?;
@@ -115,6 +181,48 @@ impl ConsensusEnclave for ConsensusServiceSgxEnclave {
         mcserial::deserialize(&outbuf[..])?
     }
 
+    fn get_signer_fingerprint(&self) -> Result<[u8; 32]> {
+        let inbuf = mcserial::serialize(&EnclaveCall::GetSignerFingerprint)?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn self_peer_id(&self) -> Result<ResponderId> {
+        let inbuf = mcserial::serialize(&EnclaveCall::SelfPeerId)?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn self_client_id(&self) -> Result<ResponderId> {
+        let inbuf = mcserial::serialize(&EnclaveCall::SelfClientId)?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn client_connect_info(&self) -> Result<(ResponderId, X25519Public)> {
+        let inbuf = mcserial::serialize(&EnclaveCall::ClientConnectInfo)?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn session_stats(&self) -> Result<SessionStats> {
+        let inbuf = mcserial::serialize(&EnclaveCall::SessionStats)?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn enclave_status(&self) -> Result<EnclaveStatus> {
+        let inbuf = mcserial::serialize(&EnclaveCall::EnclaveStatus)?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn last_call_timings(&self) -> Result<CallTimings> {
+        let inbuf = mcserial::serialize(&EnclaveCall::LastCallTimings)?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
     fn new_ereport(&self, qe_info: TargetInfo) -> Result<(Report, QuoteNonce)> {
         let inbuf = mcserial::serialize(&EnclaveCall::NewEreport(qe_info))?;
         let outbuf = self.enclave_call(&inbuf)?;
@@ -127,42 +235,138 @@ impl ConsensusEnclave for ConsensusServiceSgxEnclave {
         mcserial::deserialize(&outbuf[..])?
     }
 
+    fn cancel_ereport(&self) -> Result<()> {
+        let inbuf = mcserial::serialize(&EnclaveCall::CancelEreport)?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
     fn verify_ias_report(&self, ias_report: VerificationReport) -> Result<()> {
         let inbuf = mcserial::serialize(&EnclaveCall::VerifyReport(ias_report))?;
         let outbuf = self.enclave_call(&inbuf)?;
         mcserial::deserialize(&outbuf[..])?
     }
 
+    fn verify_ias_report_at(
+        &self,
+        ias_report: VerificationReport,
+        now_unix_secs: u64,
+    ) -> Result<()> {
+        let inbuf = mcserial::serialize(&EnclaveCall::VerifyReportAt(ias_report, now_unix_secs))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn validate_ias_report(&self, ias_report: &VerificationReport) -> Result<()> {
+        let inbuf = mcserial::serialize(&EnclaveCall::ValidateReport(ias_report.clone()))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
     fn get_ias_report(&self) -> Result<VerificationReport> {
         let inbuf = mcserial::serialize(&EnclaveCall::GetReport)?;
         let outbuf = self.enclave_call(&inbuf)?;
         mcserial::deserialize(&outbuf[..])?
     }
 
+    fn get_ias_report_info(&self) -> Result<IasReportInfo> {
+        let inbuf = mcserial::serialize(&EnclaveCall::GetReportInfo)?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn ias_report_blocks_remaining(
+        &self,
+        now_unix_secs: u64,
+        secs_per_block: u64,
+        blocks_per_refresh: u64,
+    ) -> Result<u64> {
+        let inbuf = mcserial::serialize(&EnclaveCall::IasReportBlocksRemaining(
+            now_unix_secs,
+            secs_per_block,
+            blocks_per_refresh,
+        ))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn ias_report_time(&self, report: &VerificationReport) -> Result<u64> {
+        let inbuf = mcserial::serialize(&EnclaveCall::IasReportTime(report.clone()))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn report_body(&self) -> Result<ReportBody> {
+        let inbuf = mcserial::serialize(&EnclaveCall::GetReportBody)?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn attestation_bundle(&self) -> Result<AttestationBundle> {
+        let inbuf = mcserial::serialize(&EnclaveCall::AttestationBundle)?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
     fn client_accept(&self, req: ClientAuthRequest) -> Result<(ClientAuthResponse, ClientSession)> {
         let inbuf = mcserial::serialize(&EnclaveCall::ClientAccept(req))?;
         let outbuf = self.enclave_call(&inbuf)?;
         mcserial::deserialize(&outbuf[..])?
     }
 
+    fn client_accept_with_budget(
+        &self,
+        req: ClientAuthRequest,
+        max_sessions: usize,
+    ) -> Result<(ClientAuthResponse, ClientSession)> {
+        let inbuf = mcserial::serialize(&EnclaveCall::ClientAcceptWithBudget(req, max_sessions))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
     fn client_close(&self, channel_id: ClientSession) -> Result<()> {
         let inbuf = mcserial::serialize(&EnclaveCall::ClientClose(channel_id))?;
         let outbuf = self.enclave_call(&inbuf)?;
         mcserial::deserialize(&outbuf[..])?
     }
 
+    fn client_sessions(&self) -> Result<Vec<ClientSession>> {
+        let inbuf = mcserial::serialize(&EnclaveCall::ClientSessions)?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
     fn client_discard_message(&self, msg: EnclaveMessage<ClientSession>) -> Result<()> {
         let inbuf = mcserial::serialize(&EnclaveCall::ClientDiscardMessage(msg))?;
         let outbuf = self.enclave_call(&inbuf)?;
         mcserial::deserialize(&outbuf[..])?
     }
 
+    fn client_discard_messages(
+        &self,
+        msgs: Vec<EnclaveMessage<ClientSession>>,
+    ) -> Result<Vec<Result<()>>> {
+        let inbuf = mcserial::serialize(&EnclaveCall::ClientDiscardMessages(msgs))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
     fn peer_init(&self, peer_id: &ResponderId) -> Result<PeerAuthRequest> {
         let inbuf = mcserial::serialize(&EnclaveCall::PeerInit(peer_id.clone()))?;
         let outbuf = self.enclave_call(&inbuf)?;
         mcserial::deserialize(&outbuf[..])?
     }
 
+    fn peer_init_pinned(
+        &self,
+        peer_id: &ResponderId,
+        expected: Measurement,
+    ) -> Result<PeerAuthRequest> {
+        let inbuf = mcserial::serialize(&EnclaveCall::PeerInitPinned(peer_id.clone(), expected))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
     fn peer_accept(&self, req: PeerAuthRequest) -> Result<(PeerAuthResponse, PeerSession)> {
         let inbuf = mcserial::serialize(&EnclaveCall::PeerAccept(req))?;
         let outbuf = self.enclave_call(&inbuf)?;
@@ -175,24 +379,83 @@ impl ConsensusEnclave for ConsensusServiceSgxEnclave {
         mcserial::deserialize(&outbuf[..])?
     }
 
+    fn set_peer_identity_policy(&self, allowed: Vec<Measurement>) -> Result<()> {
+        let inbuf = mcserial::serialize(&EnclaveCall::SetPeerIdentityPolicy(allowed))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
     fn peer_close(&self, session_id: &PeerSession) -> Result<()> {
         let inbuf = mcserial::serialize(&EnclaveCall::PeerClose(session_id.clone()))?;
         let outbuf = self.enclave_call(&inbuf)?;
         mcserial::deserialize(&outbuf[..])?
     }
 
+    fn peer_close_all(&self) -> Result<()> {
+        let inbuf = mcserial::serialize(&EnclaveCall::PeerCloseAll)?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn close_sessions(
+        &self,
+        clients: &[ClientSession],
+        peers: &[PeerSession],
+    ) -> Result<Vec<(SessionRef, Result<()>)>> {
+        let inbuf =
+            mcserial::serialize(&EnclaveCall::CloseSessions(clients.to_vec(), peers.to_vec()))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
     fn client_tx_propose(&self, msg: EnclaveMessage<ClientSession>) -> Result<TxContext> {
         let inbuf = mcserial::serialize(&EnclaveCall::ClientTxPropose(msg))?;
         let outbuf = self.enclave_call(&inbuf)?;
         mcserial::deserialize(&outbuf[..])?
     }
 
-    fn peer_tx_propose(&self, msg: EnclaveMessage<PeerSession>) -> Result<Vec<TxContext>> {
+    fn client_tx_propose_with_receipt(
+        &self,
+        msg: EnclaveMessage<ClientSession>,
+        block_index: u64,
+    ) -> Result<(TxContext, ProposeReceipt)> {
+        let inbuf =
+            mcserial::serialize(&EnclaveCall::ClientTxProposeWithReceipt(msg, block_index))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn peer_tx_propose(
+        &self,
+        msg: EnclaveMessage<PeerSession>,
+    ) -> Result<Vec<Result<TxContext>>> {
         let inbuf = mcserial::serialize(&EnclaveCall::PeerTxPropose(msg))?;
         let outbuf = self.enclave_call(&inbuf)?;
         mcserial::deserialize(&outbuf[..])?
     }
 
+    fn peer_tx_propose_dedup(
+        &self,
+        msg: EnclaveMessage<PeerSession>,
+        known_hashes: &[TxHash],
+    ) -> Result<Vec<Result<TxContext>>> {
+        let inbuf =
+            mcserial::serialize(&EnclaveCall::PeerTxProposeDedup(msg, known_hashes.to_vec()))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn peer_tx_propose_chunk(
+        &self,
+        msg: EnclaveMessage<PeerSession>,
+        known_hashes: &[TxHash],
+    ) -> Result<PeerTxChunk> {
+        let inbuf =
+            mcserial::serialize(&EnclaveCall::PeerTxProposeChunk(msg, known_hashes.to_vec()))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
     fn tx_is_well_formed(
         &self,
         locally_encrypted_tx: LocallyEncryptedTx,
@@ -208,6 +471,84 @@ impl ConsensusEnclave for ConsensusServiceSgxEnclave {
         mcserial::deserialize(&outbuf[..])?
     }
 
+    fn txs_are_well_formed(
+        &self,
+        txs: Vec<(LocallyEncryptedTx, u64, Vec<TxOutMembershipProof>)>,
+    ) -> Result<Vec<Result<(WellFormedEncryptedTx, WellFormedTxContext)>>> {
+        let inbuf = mcserial::serialize(&EnclaveCall::TxsAreWellFormed(txs))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn tx_is_well_formed_unspent(
+        &self,
+        locally_encrypted_tx: LocallyEncryptedTx,
+        block_index: u64,
+        proofs: Vec<TxOutMembershipProof>,
+        spent_key_images: &[KeyImage],
+    ) -> Result<(WellFormedEncryptedTx, WellFormedTxContext)> {
+        let inbuf = mcserial::serialize(&EnclaveCall::TxIsWellFormedUnspent(
+            locally_encrypted_tx,
+            block_index,
+            proofs,
+            spent_key_images.to_vec(),
+        ))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn tx_ring_signature_is_valid(&self, locally_encrypted_tx: &LocallyEncryptedTx) -> Result<()> {
+        let inbuf = mcserial::serialize(&EnclaveCall::TxRingSignatureIsValid(
+            locally_encrypted_tx.clone(),
+        ))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn integrity_check(&self, locally_encrypted_tx: &LocallyEncryptedTx) -> Result<()> {
+        let inbuf =
+            mcserial::serialize(&EnclaveCall::IntegrityCheck(locally_encrypted_tx.clone()))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn verify_tx_hash(
+        &self,
+        locally_encrypted_tx: &LocallyEncryptedTx,
+        expected: &TxHash,
+    ) -> Result<()> {
+        let inbuf = mcserial::serialize(&EnclaveCall::VerifyTxHash(
+            locally_encrypted_tx.clone(),
+            *expected,
+        ))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn tx_fee_preview(&self, locally_encrypted_tx: &LocallyEncryptedTx) -> Result<(u64, u64)> {
+        let inbuf = mcserial::serialize(&EnclaveCall::TxFeePreview(locally_encrypted_tx.clone()))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn get_minimum_fee(&self, block_index: u64) -> Result<u64> {
+        let inbuf = mcserial::serialize(&EnclaveCall::GetMinimumFee(block_index))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn get_minimum_fees(&self) -> Result<Vec<(u64, u64)>> {
+        let inbuf = mcserial::serialize(&EnclaveCall::GetMinimumFees)?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn fee_policy_digest(&self) -> Result<[u8; 32]> {
+        let inbuf = mcserial::serialize(&EnclaveCall::FeePolicyDigest)?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
     fn txs_for_peer(
         &self,
         encrypted_txs: &[WellFormedEncryptedTx],
@@ -223,6 +564,23 @@ impl ConsensusEnclave for ConsensusServiceSgxEnclave {
         mcserial::deserialize(&outbuf[..])?
     }
 
+    fn txs_for_peer_chunked(
+        &self,
+        encrypted_txs: &[WellFormedEncryptedTx],
+        aad: &[u8],
+        peer: &PeerSession,
+        max_chunk_bytes: usize,
+    ) -> Result<Vec<EnclaveMessage<PeerSession>>> {
+        let inbuf = mcserial::serialize(&EnclaveCall::TxsForPeerChunked(
+            encrypted_txs.to_vec(),
+            aad.to_vec(),
+            peer.clone(),
+            max_chunk_bytes,
+        ))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
     fn form_block(
         &self,
         parent_block: &Block,
@@ -235,6 +593,153 @@ impl ConsensusEnclave for ConsensusServiceSgxEnclave {
         let outbuf = self.enclave_call(&inbuf)?;
         mcserial::deserialize(&outbuf[..])?
     }
+
+    fn form_block_versioned(
+        &self,
+        parent_block: &Block,
+        txs_with_proofs: &[(WellFormedEncryptedTx, Vec<TxOutMembershipProof>)],
+        block_version: u32,
+    ) -> Result<(Block, Vec<RedactedTx>, BlockSignature)> {
+        let inbuf = mcserial::serialize(&EnclaveCall::FormBlockVersioned(
+            parent_block.clone(),
+            txs_with_proofs.to_vec(),
+            block_version,
+        ))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn block_version_range(&self) -> Result<(u32, u32)> {
+        let inbuf = mcserial::serialize(&EnclaveCall::BlockVersionRange)?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn form_block_with_report(
+        &self,
+        parent_block: &Block,
+        txs_with_proofs: &[(WellFormedEncryptedTx, Vec<TxOutMembershipProof>)],
+    ) -> Result<(Block, Vec<RedactedTx>, BlockSignature, Vec<(TxHash, InclusionStatus)>)> {
+        let inbuf = mcserial::serialize(&EnclaveCall::FormBlockWithReport(
+            parent_block.clone(),
+            txs_with_proofs.to_vec(),
+        ))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn form_block_excluding(
+        &self,
+        parent_block: &Block,
+        txs_with_proofs: &[(WellFormedEncryptedTx, Vec<TxOutMembershipProof>)],
+        exclude: &[TxHash],
+    ) -> Result<(Block, Vec<RedactedTx>, BlockSignature)> {
+        let inbuf = mcserial::serialize(&EnclaveCall::FormBlockExcluding(
+            parent_block.clone(),
+            txs_with_proofs.to_vec(),
+            exclude.to_vec(),
+        ))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn form_empty_block(
+        &self,
+        parent_block: &Block,
+        root_proof: &TxOutMembershipProof,
+    ) -> Result<(Block, Vec<RedactedTx>, BlockSignature)> {
+        let inbuf = mcserial::serialize(&EnclaveCall::FormEmptyBlock(
+            parent_block.clone(),
+            root_proof.clone(),
+        ))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn estimate_form_block_cost(
+        &self,
+        txs: &[(WellFormedEncryptedTx, Vec<TxOutMembershipProof>)],
+    ) -> Result<usize> {
+        let inbuf = mcserial::serialize(&EnclaveCall::EstimateFormBlockCost(txs.to_vec()))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn redact_single(&self, encrypted_tx: &WellFormedEncryptedTx) -> Result<RedactedTx> {
+        let inbuf = mcserial::serialize(&EnclaveCall::RedactSingle(encrypted_tx.clone()))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn context_of(&self, encrypted_tx: &WellFormedEncryptedTx) -> Result<WellFormedTxContext> {
+        let inbuf = mcserial::serialize(&EnclaveCall::ContextOf(encrypted_tx.clone()))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn block_total_fees(&self, block: &Block, redacted: &[RedactedTx]) -> Result<u64> {
+        let inbuf =
+            mcserial::serialize(&EnclaveCall::BlockTotalFees(block.clone(), redacted.to_vec()))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn set_block_limits(&self, limits: BlockLimits) -> Result<()> {
+        let inbuf = mcserial::serialize(&EnclaveCall::SetBlockLimits(limits))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn set_max_proposal_bytes(&self, max_proposal_bytes: usize) -> Result<()> {
+        let inbuf = mcserial::serialize(&EnclaveCall::SetMaxProposalBytes(max_proposal_bytes))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn set_tombstone_window(&self, window: TombstoneWindow) -> Result<()> {
+        let inbuf = mcserial::serialize(&EnclaveCall::SetTombstoneWindow(window))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn current_signing_height(&self) -> Result<u64> {
+        let inbuf = mcserial::serialize(&EnclaveCall::CurrentSigningHeight)?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn included_in_block(&self, tx_hash: &TxHash) -> Result<Option<u64>> {
+        let inbuf = mcserial::serialize(&EnclaveCall::IncludedInBlock(*tx_hash))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn recent_key_images(&self, since_block: u64) -> Result<Vec<KeyImage>> {
+        let inbuf = mcserial::serialize(&EnclaveCall::RecentKeyImages(since_block))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn verify_own_block_signature(&self, block: &Block, signature: &BlockSignature) -> Result<()> {
+        let inbuf = mcserial::serialize(&EnclaveCall::VerifyOwnBlockSignature(
+            block.clone(),
+            signature.clone(),
+        ))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn verify_own_block_signatures(&self, blocks: &[(Block, BlockSignature)]) -> Result<Vec<bool>> {
+        let inbuf = mcserial::serialize(&EnclaveCall::VerifyOwnBlockSignatures(blocks.to_vec()))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
+
+    fn is_trusted_signer(&self, key: &Ed25519Public) -> Result<bool> {
+        let inbuf = mcserial::serialize(&EnclaveCall::IsTrustedSigner(*key))?;
+        let outbuf = self.enclave_call(&inbuf)?;
+        mcserial::deserialize(&outbuf[..])?
+    }
 }
 
 extern "C" {
@@ -285,5 +790,10 @@ extern "C" {
     ) -> sgx_status_t;
 }
 
-// Get the "handle" marker trait as well
-impl ConsensusEnclaveProxy for ConsensusServiceSgxEnclave {}
+// Get the "handle" trait as well
+impl ConsensusEnclaveProxy for ConsensusServiceSgxEnclave {
+    fn with_exclusive<R>(&self, f: impl FnOnce() -> R) -> Result<R> {
+        let _guard = self.exclusive.lock()?;
+        Ok(f())
+    }
+}
@@ -37,10 +37,34 @@ pub fn ecall_dispatcher(inbuf: &[u8]) -> Result<Vec<u8>, sgx_status_t> {
             serialize(&ENCLAVE.enclave_init(&peer_self_id, &client_self_id, &sealed_key))
                 .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
         }
+        EnclaveCall::EnclaveVersion => {
+            serialize(&ENCLAVE.enclave_version()).or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
+        EnclaveCall::ExportSigningKey(old_sealed, target) => {
+            serialize(&ENCLAVE.export_signing_key(&old_sealed, &target))
+                .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
+        EnclaveCall::ResealSigningKey(old_sealed, migration_blob) => {
+            serialize(&ENCLAVE.reseal_signing_key(&old_sealed, &migration_blob))
+                .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
+        EnclaveCall::RotateSigningKey => {
+            serialize(&ENCLAVE.rotate_signing_key()).or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
+        EnclaveCall::SealBlob(plaintext) => {
+            serialize(&ENCLAVE.seal_blob(&plaintext)).or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
+        EnclaveCall::UnsealBlob(sealed) => {
+            serialize(&ENCLAVE.unseal_blob(&sealed)).or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
         // Node-to-Node Attestation
         EnclaveCall::PeerInit(node_id) => {
             serialize(&ENCLAVE.peer_init(&node_id)).or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
         }
+        EnclaveCall::PeerInitPinned(node_id, expected) => {
+            serialize(&ENCLAVE.peer_init_pinned(&node_id, expected))
+                .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
         EnclaveCall::PeerAccept(auth_msg) => {
             serialize(&ENCLAVE.peer_accept(auth_msg)).or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
         }
@@ -48,15 +72,37 @@ pub fn ecall_dispatcher(inbuf: &[u8]) -> Result<Vec<u8>, sgx_status_t> {
             serialize(&ENCLAVE.peer_connect(&node_id, auth_msg))
                 .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
         }
+        EnclaveCall::SetPeerIdentityPolicy(allowed) => {
+            serialize(&ENCLAVE.set_peer_identity_policy(allowed))
+                .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
         EnclaveCall::PeerClose(session_id) => serialize(&ENCLAVE.peer_close(&session_id))
             .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?,
+        EnclaveCall::PeerCloseAll => {
+            serialize(&ENCLAVE.peer_close_all()).or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
+        EnclaveCall::CloseSessions(clients, peers) => {
+            serialize(&ENCLAVE.close_sessions(&clients, &peers))
+                .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
         // Node-to-Client Attestation
         EnclaveCall::ClientAccept(auth_msg) => serialize(&ENCLAVE.client_accept(auth_msg))
             .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?,
+        EnclaveCall::ClientAcceptWithBudget(auth_msg, max_sessions) => {
+            serialize(&ENCLAVE.client_accept_with_budget(auth_msg, max_sessions))
+                .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
         EnclaveCall::ClientClose(channel_id) => serialize(&ENCLAVE.client_close(channel_id))
             .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?,
+        EnclaveCall::ClientSessions => {
+            serialize(&ENCLAVE.client_sessions()).or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
         EnclaveCall::ClientDiscardMessage(msg) => serialize(&ENCLAVE.client_discard_message(msg))
             .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?,
+        EnclaveCall::ClientDiscardMessages(msgs) => {
+            serialize(&ENCLAVE.client_discard_messages(msgs))
+                .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
         // Report Caching
         EnclaveCall::GetIdentity => {
             serialize(&ENCLAVE.get_identity()).or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
@@ -64,37 +110,206 @@ pub fn ecall_dispatcher(inbuf: &[u8]) -> Result<Vec<u8>, sgx_status_t> {
         EnclaveCall::GetSigner => {
             serialize(&ENCLAVE.get_signer()).or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
         }
+        EnclaveCall::GetSignerFingerprint => serialize(&ENCLAVE.get_signer_fingerprint())
+            .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?,
+        EnclaveCall::SelfPeerId => {
+            serialize(&ENCLAVE.self_peer_id()).or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
+        EnclaveCall::SelfClientId => {
+            serialize(&ENCLAVE.self_client_id()).or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
+        EnclaveCall::ClientConnectInfo => serialize(&ENCLAVE.client_connect_info())
+            .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?,
+        EnclaveCall::SessionStats => {
+            serialize(&ENCLAVE.session_stats()).or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
+        EnclaveCall::EnclaveStatus => {
+            serialize(&ENCLAVE.enclave_status()).or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
+        EnclaveCall::LastCallTimings => {
+            serialize(&ENCLAVE.last_call_timings()).or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
         EnclaveCall::NewEreport(qe_info) => {
             serialize(&ENCLAVE.new_ereport(qe_info)).or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
         }
+        EnclaveCall::CancelEreport => {
+            serialize(&ENCLAVE.cancel_ereport()).or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
         EnclaveCall::VerifyQuote(quote, qe_report) => {
             serialize(&ENCLAVE.verify_quote(quote, qe_report))
                 .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
         }
         EnclaveCall::VerifyReport(ias_report) => serialize(&ENCLAVE.verify_ias_report(ias_report))
             .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?,
+        EnclaveCall::VerifyReportAt(ias_report, now_unix_secs) => {
+            serialize(&ENCLAVE.verify_ias_report_at(ias_report, now_unix_secs))
+                .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
+        EnclaveCall::ValidateReport(ias_report) => {
+            serialize(&ENCLAVE.validate_ias_report(&ias_report))
+                .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
         EnclaveCall::GetReport => {
             serialize(&ENCLAVE.get_ias_report()).or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
         }
+        EnclaveCall::GetReportInfo => serialize(&ENCLAVE.get_ias_report_info())
+            .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?,
+        EnclaveCall::IasReportBlocksRemaining(now, secs_per_block, blocks_per_refresh) => {
+            serialize(&ENCLAVE.ias_report_blocks_remaining(now, secs_per_block, blocks_per_refresh))
+                .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
+        EnclaveCall::IasReportTime(report) => serialize(&ENCLAVE.ias_report_time(&report))
+            .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?,
+        EnclaveCall::GetReportBody => {
+            serialize(&ENCLAVE.report_body()).or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
+        EnclaveCall::AttestationBundle => serialize(&ENCLAVE.attestation_bundle())
+            .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?,
         // Transactions
         EnclaveCall::ClientTxPropose(msg) => serialize(&ENCLAVE.client_tx_propose(msg))
             .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?,
+        EnclaveCall::ClientTxProposeWithReceipt(msg, block_index) => {
+            serialize(&ENCLAVE.client_tx_propose_with_receipt(msg, block_index))
+                .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
         EnclaveCall::PeerTxPropose(msg) => {
             serialize(&ENCLAVE.peer_tx_propose(msg)).or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
         }
+        EnclaveCall::PeerTxProposeDedup(msg, known_hashes) => {
+            serialize(&ENCLAVE.peer_tx_propose_dedup(msg, &known_hashes))
+                .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
+        EnclaveCall::PeerTxProposeChunk(msg, known_hashes) => {
+            serialize(&ENCLAVE.peer_tx_propose_chunk(msg, &known_hashes))
+                .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
         EnclaveCall::TxIsWellFormed(locally_encrypted_tx, block_index, proofs) => {
             serialize(&ENCLAVE.tx_is_well_formed(locally_encrypted_tx, block_index, proofs))
                 .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
         }
+        EnclaveCall::TxsAreWellFormed(txs) => serialize(&ENCLAVE.txs_are_well_formed(txs))
+            .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?,
+        EnclaveCall::TxIsWellFormedUnspent(
+            locally_encrypted_tx,
+            block_index,
+            proofs,
+            spent_key_images,
+        ) => serialize(&ENCLAVE.tx_is_well_formed_unspent(
+            locally_encrypted_tx,
+            block_index,
+            proofs,
+            &spent_key_images,
+        ))
+        .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?,
+        EnclaveCall::TxRingSignatureIsValid(locally_encrypted_tx) => {
+            serialize(&ENCLAVE.tx_ring_signature_is_valid(&locally_encrypted_tx))
+                .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
+        EnclaveCall::IntegrityCheck(locally_encrypted_tx) => {
+            serialize(&ENCLAVE.integrity_check(&locally_encrypted_tx))
+                .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
+        EnclaveCall::VerifyTxHash(locally_encrypted_tx, expected) => {
+            serialize(&ENCLAVE.verify_tx_hash(&locally_encrypted_tx, &expected))
+                .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
+        EnclaveCall::TxFeePreview(locally_encrypted_tx) => {
+            serialize(&ENCLAVE.tx_fee_preview(&locally_encrypted_tx))
+                .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
+        EnclaveCall::GetMinimumFee(block_index) => {
+            serialize(&ENCLAVE.get_minimum_fee(block_index))
+                .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
+        EnclaveCall::GetMinimumFees => {
+            serialize(&ENCLAVE.get_minimum_fees()).or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
+        EnclaveCall::FeePolicyDigest => {
+            serialize(&ENCLAVE.fee_policy_digest()).or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
         EnclaveCall::TxsForPeer(txs, aad, peer) => {
             serialize(&ENCLAVE.txs_for_peer(&txs, &aad, &peer))
                 .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
         }
+        EnclaveCall::TxsForPeerChunked(txs, aad, peer, max_chunk_bytes) => {
+            serialize(&ENCLAVE.txs_for_peer_chunked(&txs, &aad, &peer, max_chunk_bytes))
+                .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
 
         EnclaveCall::FormBlock(parent_block, encrypted_txs_with_proofs) => {
             serialize(&ENCLAVE.form_block(&parent_block, &encrypted_txs_with_proofs))
                 .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
         }
+        EnclaveCall::FormBlockVersioned(parent_block, encrypted_txs_with_proofs, block_version) => {
+            serialize(&ENCLAVE.form_block_versioned(
+                &parent_block,
+                &encrypted_txs_with_proofs,
+                block_version,
+            ))
+            .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
+        EnclaveCall::BlockVersionRange => serialize(&ENCLAVE.block_version_range())
+            .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?,
+        EnclaveCall::FormBlockWithReport(parent_block, encrypted_txs_with_proofs) => {
+            serialize(&ENCLAVE.form_block_with_report(&parent_block, &encrypted_txs_with_proofs))
+                .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
+        EnclaveCall::FormBlockExcluding(parent_block, encrypted_txs_with_proofs, exclude) => {
+            serialize(&ENCLAVE.form_block_excluding(
+                &parent_block,
+                &encrypted_txs_with_proofs,
+                &exclude,
+            ))
+            .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
+        EnclaveCall::FormEmptyBlock(parent_block, root_proof) => {
+            serialize(&ENCLAVE.form_empty_block(&parent_block, &root_proof))
+                .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
+        EnclaveCall::EstimateFormBlockCost(txs) => {
+            serialize(&ENCLAVE.estimate_form_block_cost(&txs))
+                .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
+        EnclaveCall::RedactSingle(encrypted_tx) => {
+            serialize(&ENCLAVE.redact_single(&encrypted_tx))
+                .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
+        EnclaveCall::ContextOf(encrypted_tx) => {
+            serialize(&ENCLAVE.context_of(&encrypted_tx))
+                .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
+        EnclaveCall::BlockTotalFees(block, redacted) => {
+            serialize(&ENCLAVE.block_total_fees(&block, &redacted))
+                .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
+        EnclaveCall::SetBlockLimits(limits) => serialize(&ENCLAVE.set_block_limits(limits))
+            .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?,
+        EnclaveCall::SetMaxProposalBytes(max_proposal_bytes) => {
+            serialize(&ENCLAVE.set_max_proposal_bytes(max_proposal_bytes))
+                .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
+        EnclaveCall::SetTombstoneWindow(window) => {
+            serialize(&ENCLAVE.set_tombstone_window(window))
+                .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
+        EnclaveCall::CurrentSigningHeight => serialize(&ENCLAVE.current_signing_height())
+            .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?,
+        EnclaveCall::IncludedInBlock(tx_hash) => serialize(&ENCLAVE.included_in_block(&tx_hash))
+            .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?,
+        EnclaveCall::RecentKeyImages(since_block) => {
+            serialize(&ENCLAVE.recent_key_images(since_block))
+                .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
+        EnclaveCall::VerifyOwnBlockSignature(block, signature) => {
+            serialize(&ENCLAVE.verify_own_block_signature(&block, &signature))
+                .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
+        EnclaveCall::VerifyOwnBlockSignatures(blocks) => {
+            serialize(&ENCLAVE.verify_own_block_signatures(&blocks))
+                .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        }
+        EnclaveCall::IsTrustedSigner(key) => serialize(&ENCLAVE.is_trusted_signer(&key))
+            .or(Err(sgx_status_t::SGX_ERROR_UNEXPECTED))?,
     };
 
     Ok(outdata)
@@ -60,6 +60,10 @@ impl From<EnclaveError> for ConsensusGrpcError {
     fn from(src: EnclaveError) -> Self {
         match src {
             EnclaveError::MalformedTx(err) => Self::from(err),
+            EnclaveError::WellFormedCheck(err) => Self::from(TransactionValidationError::from(err)),
+            EnclaveError::TombstoneExpired { .. } => {
+                Self::from(TransactionValidationError::TombstoneBlockExceeded)
+            }
             _ => Self::Enclave(src),
         }
     }
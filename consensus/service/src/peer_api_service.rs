@@ -27,7 +27,7 @@ use mobilecoin_api::{
     consensus_peer_grpc::ConsensusPeerApi,
     empty::Empty,
 };
-use peers::TxProposeAAD;
+use peers::PeerAad;
 use std::{
     convert::{TryFrom, TryInto},
     sync::Arc,
@@ -103,12 +103,24 @@ impl<E: ConsensusEnclaveProxy, L: Ledger> PeerApiService<E, L> {
 
         // We fail silently here since the only effect of not having
         // origin_node/relayed_by node IDs is less efficient broadcasting.
-        let (origin_node, relayed_by) = mcserial::deserialize::<TxProposeAAD>(&aad)
+        let (origin_node, relayed_by) = PeerAad::from_bytes(&aad)
             .map(|aad| (Some(aad.origin_node), Some(aad.relayed_by)))
             .unwrap_or((None, None));
 
         // Feed to manager
-        for tx_context in tx_contexts {
+        for maybe_tx_context in tx_contexts {
+            // A single bad tx in the peer's message should not prevent us from handling the rest.
+            let tx_context = match maybe_tx_context {
+                Ok(tx_context) => tx_context,
+                Err(err) => {
+                    log::info!(
+                        logger,
+                        "Dropping tx that failed to decode in peer_tx_propose: {err}",
+                        err = format!("{:?}", err)
+                    );
+                    continue;
+                }
+            };
             let tx_hash = tx_context.tx_hash;
 
             match self.tx_manager.insert_proposed_tx(tx_context) {
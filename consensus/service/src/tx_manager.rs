@@ -47,10 +47,17 @@ pub enum TxManagerError {
 
 impl From<ConsensusEnclaveError> for TxManagerError {
     fn from(err: ConsensusEnclaveError) -> Self {
-        if let ConsensusEnclaveError::MalformedTx(transaction_validation_error) = err {
-            Self::TransactionValidation(transaction_validation_error)
-        } else {
-            Self::Enclave(err)
+        match err {
+            ConsensusEnclaveError::MalformedTx(transaction_validation_error) => {
+                Self::TransactionValidation(transaction_validation_error)
+            }
+            ConsensusEnclaveError::WellFormedCheck(well_formed_error) => {
+                Self::TransactionValidation(well_formed_error.into())
+            }
+            ConsensusEnclaveError::TombstoneExpired { .. } => {
+                Self::TransactionValidation(TransactionValidationError::TombstoneBlockExceeded)
+            }
+            err => Self::Enclave(err),
         }
     }
 }
@@ -58,21 +58,28 @@ impl<L: Ledger> TxManagerUntrustedInterfaces for DefaultTxManagerUntrustedInterf
             return Err(TransactionValidationError::ContainsSpentKeyImage);
         }
 
-        let membership_proofs = self
-            .ledger
-            .get_tx_out_proof_of_memberships(highest_indices)
-            .map_err(|e| TransactionValidationError::Ledger(e.to_string()))?;
-
-        // Note: It is possible that the proofs above are obtained for a different block index as a
-        // new block could be written between getting the proofs and the call to num_blocks().
-        // However, this has no effect on validation as the block index is only used for tombstone
-        // checking.
-        let current_block_index = self
-            .ledger
-            .num_blocks()
-            .map_err(|e| TransactionValidationError::Ledger(e.to_string()))?;
-
-        Ok((current_block_index, membership_proofs))
+        // The enclave rejects proofs made against a ledger older than the block index it is
+        // given, so both must come from the same ledger state. Read the block index on both sides
+        // of getting the proofs, and get them again if a new block was written in between.
+        loop {
+            let current_block_index = self
+                .ledger
+                .num_blocks()
+                .map_err(|e| TransactionValidationError::Ledger(e.to_string()))?;
+
+            let membership_proofs = self
+                .ledger
+                .get_tx_out_proof_of_memberships(highest_indices)
+                .map_err(|e| TransactionValidationError::Ledger(e.to_string()))?;
+
+            let num_blocks = self
+                .ledger
+                .num_blocks()
+                .map_err(|e| TransactionValidationError::Ledger(e.to_string()))?;
+            if num_blocks == current_block_index {
+                return Ok((current_block_index, membership_proofs));
+            }
+        }
     }
 
     /// Checks if a transaction is valid (see definition at top of this file).
@@ -149,16 +156,94 @@ impl<L: Ledger> TxManagerUntrustedInterfaces for DefaultTxManagerUntrustedInterf
 pub mod well_formed_tests {
     use super::*;
     use common::logger::{bench_with_logger, test_with_logger, Logger};
-    use ledger_db::LedgerDB;
+    use ledger_db::{Error as LedgerError, LedgerDB};
     use rand::SeedableRng;
     use rand_hc::Hc128Rng;
+    use std::sync::{Arc, Mutex};
     use test::Bencher;
     use transaction::{
-        account_keys::AccountKey, constants::MAX_TOMBSTONE_BLOCKS, ring_signature::KeyImage,
-        tx::Tx, validation::TransactionValidationError,
+        account_keys::AccountKey,
+        constants::MAX_TOMBSTONE_BLOCKS,
+        ring_signature::KeyImage,
+        tx::{Tx, TxOut},
+        validation::TransactionValidationError,
+        Block, BlockSignature, RedactedTx, BLOCK_VERSION,
     };
     use transaction_test_utils::{create_ledger, create_transaction, initialize_ledger};
 
+    /// A ledger which writes `pending` right after handing out membership proofs for the first
+    /// time, as the node does when consensus externalizes a block during a well-formed check.
+    #[derive(Clone)]
+    struct AppendingLedger {
+        ledger: LedgerDB,
+        pending: Arc<Mutex<Option<(Block, Vec<RedactedTx>)>>>,
+    }
+
+    impl Ledger for AppendingLedger {
+        fn append_block(
+            &mut self,
+            block: &Block,
+            transactions: &[RedactedTx],
+            signature: Option<&BlockSignature>,
+        ) -> Result<(), LedgerError> {
+            self.ledger.append_block(block, transactions, signature)
+        }
+
+        fn num_blocks(&self) -> Result<u64, LedgerError> {
+            self.ledger.num_blocks()
+        }
+
+        fn get_block(&self, block_number: u64) -> Result<Block, LedgerError> {
+            self.ledger.get_block(block_number)
+        }
+
+        fn get_block_signature(&self, block_number: u64) -> Result<BlockSignature, LedgerError> {
+            self.ledger.get_block_signature(block_number)
+        }
+
+        fn num_txos(&self) -> Result<u64, LedgerError> {
+            self.ledger.num_txos()
+        }
+
+        fn get_tx_out_index_by_hash(&self, tx_out_hash: &[u8; 32]) -> Result<u64, LedgerError> {
+            self.ledger.get_tx_out_index_by_hash(tx_out_hash)
+        }
+
+        fn get_tx_out_by_index(&self, index: u64) -> Result<TxOut, LedgerError> {
+            self.ledger.get_tx_out_by_index(index)
+        }
+
+        fn get_tx_out_proof_of_memberships(
+            &self,
+            indexes: &[u64],
+        ) -> Result<Vec<TxOutMembershipProof>, LedgerError> {
+            let proofs = self.ledger.get_tx_out_proof_of_memberships(indexes)?;
+            if let Some((block, transactions)) = self.pending.lock().unwrap().take() {
+                self.ledger.clone().append_block(&block, &transactions, None)?;
+            }
+            Ok(proofs)
+        }
+
+        fn num_txs(&self) -> Result<u64, LedgerError> {
+            self.ledger.num_txs()
+        }
+
+        fn get_transactions_by_block(
+            &self,
+            block_number: u64,
+        ) -> Result<Vec<RedactedTx>, LedgerError> {
+            self.ledger.get_transactions_by_block(block_number)
+        }
+
+        fn check_key_image(&self, key_image: &KeyImage) -> Result<Option<u64>, LedgerError> {
+            self.ledger.check_key_image(key_image)
+        }
+
+        fn get_key_images_by_block(&self, block_number: u64) -> Result<Vec<KeyImage>, LedgerError> {
+            self.ledger.get_key_images_by_block(block_number)
+        }
+    }
+
     fn is_well_formed(tx: &Tx, ledger: &LedgerDB) -> TransactionValidationResult<()> {
         let mut rng = Hc128Rng::from_seed([77u8; 32]);
 
@@ -315,6 +400,55 @@ pub mod well_formed_tests {
         );
     }
 
+    #[test_with_logger]
+    /// `well_formed_check` should return proofs made against the ledger at the block index it
+    /// returns, even if a block is written while the proofs are being read. Proofs one block
+    /// behind would otherwise be rejected as stale by the enclave.
+    fn well_formed_check_returns_block_index_of_proofs(_logger: Logger) {
+        let mut rng = Hc128Rng::from_seed([80u8; 32]);
+
+        let sender = AccountKey::random(&mut rng);
+        let recipient = AccountKey::random(&mut rng);
+
+        let mut ledger = create_ledger();
+        let n_blocks = 3;
+        let blocks = initialize_ledger(&mut ledger, n_blocks, &sender, &mut rng);
+
+        // The block written mid-check spends the output of the last block.
+        let mut transactions = ledger.get_transactions_by_block(n_blocks - 1).unwrap();
+        let tx_stored = transactions.pop().unwrap();
+        let tx_out = tx_stored.outputs[0].clone();
+        let tx = create_transaction(
+            &mut ledger,
+            &tx_out,
+            &sender,
+            &recipient.default_subaddress(),
+            n_blocks + 1,
+            &mut rng,
+        );
+        let redacted_transactions = vec![tx.redact()];
+        let block = Block::new(
+            BLOCK_VERSION,
+            &blocks.last().unwrap().id,
+            n_blocks,
+            &Default::default(),
+            &redacted_transactions,
+        );
+
+        let untrusted = DefaultTxManagerUntrustedInterfaces::new(AppendingLedger {
+            ledger: ledger.clone(),
+            pending: Arc::new(Mutex::new(Some((block, redacted_transactions)))),
+        });
+        let (cur_block_index, membership_proofs) = untrusted.well_formed_check(&[0], &[]).unwrap();
+
+        assert_eq!(ledger.num_blocks().unwrap(), n_blocks + 1);
+        assert_eq!(cur_block_index, n_blocks + 1);
+        assert_eq!(
+            membership_proofs,
+            ledger.get_tx_out_proof_of_memberships(&[0]).unwrap()
+        );
+    }
+
     #[test_with_logger]
     /// `is_well_formed` should reject a transaction with a tombstone block that has been exceeded.
     fn is_well_formed_rejects_past_tombstone_block(_logger: Logger) {
@@ -6,8 +6,9 @@ extern crate alloc;
 use aes_gcm::Aes256Gcm;
 use alloc::{string::ToString, vec::Vec};
 use attest::{
-    IasNonce, Nonce, NonceError, Quote, QuoteNonce, Report, ReportData, TargetInfo,
-    VerificationReport, VerificationReportData, VerifyError, DEBUG_ENCLAVE, IAS_VERSION,
+    IasNonce, Measurement, Nonce, NonceError, Quote, QuoteNonce, Report, ReportBody, ReportData,
+    TargetInfo, VerificationReport, VerificationReportData, VerifyError, DEBUG_ENCLAVE,
+    IAS_VERSION,
 };
 use attest_ake::{
     AuthPending, AuthRequestInput, AuthRequestOutput, AuthResponse, NodeInitiate, Ready, Start,
@@ -23,6 +24,10 @@ use core::convert::TryFrom;
 use digest::Digest;
 use keys::{FromRandom, X25519Private, X25519Public, X25519};
 use mcrand::McRng;
+#[cfg(feature = "test_utils")]
+use rand_core::SeedableRng;
+#[cfg(feature = "test_utils")]
+use rand_hc::Hc128Rng;
 use sgx_compat::sync::Mutex;
 use sha2::{Sha256, Sha512};
 
@@ -38,6 +43,9 @@ const MAX_PEER_SESSIONS: usize = 64;
 /// Max number of client sessions.
 const MAX_CLIENT_SESSIONS: usize = 10000;
 
+/// Max number of accepted inbound peer auth requests remembered in order to detect replays.
+const MAX_SEEN_PEER_AUTH_REQUESTS: usize = 256;
+
 /// Any additional "identities" (e.g. key material) for a given enclave that needs to become
 /// a part of the report. We provide some simple identities, and a trait to allow extensions
 mod identity;
@@ -68,8 +76,10 @@ pub struct AkeEnclaveState<EI: EnclaveIdentity> {
     /// The cached IAS report, if any.
     current_ias_report: Mutex<Option<VerificationReport>>,
 
-    /// A map of responder-ID to incomplete, outbound, AKE state.
-    initiator_auth_pending: Mutex<LruCache<ResponderId, AuthPending<X25519, Aes256Gcm, Sha512>>>,
+    /// A map of responder-ID to incomplete, outbound, AKE state, along with the measurement the
+    /// responder is expected to present.
+    initiator_auth_pending:
+        Mutex<LruCache<ResponderId, (Measurement, AuthPending<X25519, Aes256Gcm, Sha512>)>>,
 
     /// A map of channel ID outbound connection state.
     peer_outbound: Mutex<LruCache<PeerSession, Ready<Aes256Gcm>>>,
@@ -77,8 +87,15 @@ pub struct AkeEnclaveState<EI: EnclaveIdentity> {
     /// A map of channel ID to inbound connection state.
     peer_inbound: Mutex<LruCache<PeerSession, Ready<Aes256Gcm>>>,
 
+    /// The SHA-256 digests of the most recently accepted inbound peer auth requests.
+    seen_peer_auth_requests: Mutex<LruCache<[u8; 32], ()>>,
+
     /// A map of channel ID to connection state
     clients: Mutex<LruCache<ClientSession, Ready<Aes256Gcm>>>,
+
+    /// A seeded RNG which, once set by `seed_test_rng`, replaces `McRng` for quote nonces.
+    #[cfg(feature = "test_utils")]
+    test_rng: Mutex<Option<Hc128Rng>>,
 }
 
 impl<EI: EnclaveIdentity + Default> Default for AkeEnclaveState<EI> {
@@ -101,7 +118,10 @@ impl<EI: EnclaveIdentity> AkeEnclaveState<EI> {
             initiator_auth_pending: Mutex::new(LruCache::new(MAX_AUTH_PENDING_REQUESTS)),
             peer_outbound: Mutex::new(LruCache::new(MAX_PEER_SESSIONS)),
             peer_inbound: Mutex::new(LruCache::new(MAX_PEER_SESSIONS)),
+            seen_peer_auth_requests: Mutex::new(LruCache::new(MAX_SEEN_PEER_AUTH_REQUESTS)),
             clients: Mutex::new(LruCache::new(MAX_CLIENT_SESSIONS)),
+            #[cfg(feature = "test_utils")]
+            test_rng: Mutex::new(None),
         }
     }
 
@@ -147,7 +167,16 @@ impl<EI: EnclaveIdentity> AkeEnclaveState<EI> {
         &self,
         req: ClientAuthRequest,
     ) -> Result<(ClientAuthResponse, ClientSession)> {
-        self.accept(self.get_client_self_id()?, &self.clients, req)
+        self.accept(self.get_client_self_id()?, &self.clients, req, None)
+    }
+
+    /// Accept a client connection, unless `max_sessions` client sessions are already established
+    pub fn client_accept_with_budget(
+        &self,
+        req: ClientAuthRequest,
+        max_sessions: usize,
+    ) -> Result<(ClientAuthResponse, ClientSession)> {
+        self.accept(self.get_client_self_id()?, &self.clients, req, Some(max_sessions))
     }
 
     /// Close a client session
@@ -156,8 +185,52 @@ impl<EI: EnclaveIdentity> AkeEnclaveState<EI> {
         Ok(())
     }
 
+    /// Get the number of established client sessions
+    pub fn client_session_count(&self) -> Result<usize> {
+        Ok(self.clients.lock()?.len())
+    }
+
+    /// The IDs of all established client sessions, from most to least recently used.
+    pub fn client_sessions(&self) -> Result<Vec<ClientSession>> {
+        Ok(self
+            .clients
+            .lock()?
+            .iter()
+            .map(|(session, _)| session.clone())
+            .collect())
+    }
+
     /// Begin a peer connection
     pub fn peer_init(&self, peer_id: &ResponderId) -> Result<PeerAuthRequest> {
+        self.initiate(peer_id, None)
+    }
+
+    /// Begin a peer connection, expecting the peer to present `expected_measurement` rather
+    /// than our own MRENCLAVE
+    pub fn peer_init_pinned(
+        &self,
+        peer_id: &ResponderId,
+        expected_measurement: Measurement,
+    ) -> Result<PeerAuthRequest> {
+        self.initiate(peer_id, Some(expected_measurement))
+    }
+
+    /// Get the measurement the peer we are connecting to is expected to present
+    pub fn pending_peer_measurement(&self, peer_id: &ResponderId) -> Result<Measurement> {
+        self.initiator_auth_pending
+            .lock()?
+            .peek(peer_id)
+            .map(|(expected_measurement, _initiator)| *expected_measurement)
+            .ok_or(Error::NotFound)
+    }
+
+    /// Begin a peer connection, expecting the peer to present `expected_measurement`, or our own
+    /// MRENCLAVE if none is given
+    fn initiate(
+        &self,
+        peer_id: &ResponderId,
+        expected_measurement: Option<Measurement>,
+    ) -> Result<PeerAuthRequest> {
         let local_identity = self.kex_identity.clone();
         let ias_report = self.get_ias_report()?;
 
@@ -165,9 +238,11 @@ impl<EI: EnclaveIdentity> AkeEnclaveState<EI> {
         let mut csprng = McRng::default();
         // TODO: Cache expected values rather than creating a new report each time.
         let report_body = Report::new(None, None)?.body();
+        let expected_measurement =
+            expected_measurement.unwrap_or_else(|| report_body.mr_enclave().into());
         let initiator = Start::new(
             peer_id.to_string(),
-            report_body.mr_enclave(),
+            expected_measurement,
             report_body.product_id(),
             report_body.security_version(),
             DEBUG_ENCLAVE,
@@ -183,7 +258,7 @@ impl<EI: EnclaveIdentity> AkeEnclaveState<EI> {
         // Store the current state
         self.initiator_auth_pending
             .lock()?
-            .put(peer_id.clone(), initiator);
+            .put(peer_id.clone(), (expected_measurement, initiator));
 
         // Return the output.
         let msg_vec: Vec<u8> = msg.into();
@@ -192,7 +267,20 @@ impl<EI: EnclaveIdentity> AkeEnclaveState<EI> {
 
     /// Accept a peer connection
     pub fn peer_accept(&self, req: PeerAuthRequest) -> Result<(PeerAuthResponse, PeerSession)> {
-        self.accept(self.get_peer_self_id()?, &self.peer_inbound, req)
+        let peer_self_id = self.get_peer_self_id()?;
+
+        // Every genuine request carries a fresh ephemeral key, so a request which has been
+        // accepted before can only be a replay. One which failed may be retried, and the seen
+        // requests stay locked until it is accepted so a replay can not race it.
+        let mut digest = [0u8; 32];
+        digest.copy_from_slice(Sha256::digest(req.as_ref()).as_slice());
+        let mut seen = self.seen_peer_auth_requests.lock()?;
+        if seen.contains(&digest) {
+            return Err(Error::ReplayedAuthRequest);
+        }
+        let accepted = self.accept(peer_self_id, &self.peer_inbound, req, None)?;
+        seen.put(digest, ());
+        Ok(accepted)
     }
 
     /// Connect to a peer that our accepted our PeerAuthRequest
@@ -202,7 +290,7 @@ impl<EI: EnclaveIdentity> AkeEnclaveState<EI> {
         msg: PeerAuthResponse,
     ) -> Result<PeerSession> {
         // Find our state machine
-        let initiator = self
+        let (_expected_measurement, initiator) = self
             .initiator_auth_pending
             .lock()?
             .pop(peer_id)
@@ -229,6 +317,74 @@ impl<EI: EnclaveIdentity> AkeEnclaveState<EI> {
         Ok(())
     }
 
+    /// Close all peer connections, inbound and outbound.
+    ///
+    /// Both maps are always cleared: failing to lock one of them does not prevent the other from
+    /// being emptied. If either lock fails, the first error is returned after both were attempted.
+    pub fn peer_close_all(&self) -> Result<()> {
+        let inbound = self.peer_inbound.lock().map(|mut map| map.clear());
+        let outbound = self.peer_outbound.lock().map(|mut map| map.clear());
+        inbound?;
+        outbound?;
+        Ok(())
+    }
+
+    /// Close the given client and peer sessions, holding every session lock for the whole
+    /// teardown so no other call observes it half done.
+    ///
+    /// Returns one result per client session and one per peer session, in the order given:
+    /// `Error::NotFound` for sessions which were not open.
+    pub fn close_sessions(
+        &self,
+        clients: &[ClientSession],
+        peers: &[PeerSession],
+    ) -> Result<(Vec<Result<()>>, Vec<Result<()>>)> {
+        let mut client_map = self.clients.lock()?;
+        let mut inbound = self.peer_inbound.lock()?;
+        let mut outbound = self.peer_outbound.lock()?;
+
+        let client_results = clients
+            .iter()
+            .map(|session| client_map.pop(session).map(|_| ()).ok_or(Error::NotFound))
+            .collect();
+        let peer_results = peers
+            .iter()
+            .map(|session| {
+                // A session is in at most one of the maps, but pop it from both regardless.
+                let was_inbound = inbound.pop(session).is_some();
+                let was_outbound = outbound.pop(session).is_some();
+                if was_inbound || was_outbound {
+                    Ok(())
+                } else {
+                    Err(Error::NotFound)
+                }
+            })
+            .collect();
+
+        Ok((client_results, peer_results))
+    }
+
+    /// Get the number of established client sessions, the number of established peer sessions,
+    /// and the cached IAS report, if any, all at the same point in time
+    pub fn status(&self) -> Result<(usize, usize, Option<VerificationReport>)> {
+        let clients = self.clients.lock()?;
+        let inbound = self.peer_inbound.lock()?;
+        let outbound = self.peer_outbound.lock()?;
+        let ias_report = self.current_ias_report.lock()?;
+        Ok((
+            clients.len(),
+            inbound.len() + outbound.len(),
+            ias_report.clone(),
+        ))
+    }
+
+    /// Get the number of established peer sessions, inbound and outbound
+    pub fn peer_session_count(&self) -> Result<usize> {
+        let inbound = self.peer_inbound.lock()?.len();
+        let outbound = self.peer_outbound.lock()?.len();
+        Ok(inbound + outbound)
+    }
+
     /// Check if a peer is known, return Error::NotFound if not
     pub fn is_peer_known(&self, session_id: &PeerSession) -> Result<bool> {
         match self.get_peer_map_by_session(session_id) {
@@ -315,8 +471,7 @@ impl<EI: EnclaveIdentity> AkeEnclaveState<EI> {
         let mut quote_pending = self.quote_pending.lock()?;
 
         let quote_nonce = loop {
-            let mut csprng = McRng::default();
-            let quote_nonce = QuoteNonce::new(&mut csprng)?;
+            let quote_nonce = self.new_quote_nonce()?;
             if quote_pending.contains(&quote_nonce) {
                 continue;
             } else {
@@ -324,7 +479,40 @@ impl<EI: EnclaveIdentity> AkeEnclaveState<EI> {
             }
         };
 
-        // Copy the public key into the report data.
+        // Actually get the EREPORT
+        let report = Report::new(Some(&qe_info), Some(&self.report_data()))?;
+        quote_pending.put(quote_nonce, report);
+
+        Ok((report, quote_nonce))
+    }
+
+    /// Make all quote nonces generated from here on deterministic, derived from `seed`.
+    #[cfg(feature = "test_utils")]
+    pub fn seed_test_rng(&self, seed: [u8; 32]) -> Result<()> {
+        *self.test_rng.lock()? = Some(Hc128Rng::from_seed(seed));
+        Ok(())
+    }
+
+    /// Generate a fresh quote nonce, from the seeded test RNG if there is one.
+    fn new_quote_nonce(&self) -> Result<QuoteNonce> {
+        #[cfg(feature = "test_utils")]
+        {
+            if let Some(rng) = self.test_rng.lock()?.as_mut() {
+                return Ok(QuoteNonce::new(rng)?);
+            }
+        }
+        Ok(QuoteNonce::new(&mut McRng::default())?)
+    }
+
+    /// Get the body of a report for this enclave, carrying the same report data as the
+    /// EREPORTs created by `new_ereport`.
+    pub fn report_body(&self) -> Result<ReportBody> {
+        Ok(Report::new(None, Some(&self.report_data()))?.body())
+    }
+
+    /// The report data attested to by this enclave: the public key used for key exchange,
+    /// followed by the custom identity's report bytes.
+    fn report_data(&self) -> ReportData {
         let mut report_data = ReportData::default();
         let report_data_bytes: &mut [u8] = report_data.as_mut();
         let identity = self.get_kex_identity();
@@ -332,12 +520,17 @@ impl<EI: EnclaveIdentity> AkeEnclaveState<EI> {
         let custom_identity_bytes = self.custom_identity.get_bytes_for_report();
         report_data_bytes[..identity_bytes.len()].copy_from_slice(identity_bytes);
         report_data_bytes[identity_bytes.len()..].copy_from_slice(custom_identity_bytes.as_ref());
+        report_data
+    }
 
-        // Actually get the EREPORT
-        let report = Report::new(Some(&qe_info), Some(&report_data))?;
-        quote_pending.put(quote_nonce, report);
-
-        Ok((report, quote_nonce))
+    /// Forget every pending EREPORT and quote, so that any in-flight attestation has to start
+    /// over with `new_ereport`. The cached IAS report, if any, is kept.
+    pub fn cancel_ereport(&self) -> Result<()> {
+        let quotes = self.quote_pending.lock().map(|mut map| map.clear());
+        let ias = self.ias_pending.lock().map(|mut map| map.clear());
+        quotes?;
+        ias?;
+        Ok(())
     }
 
     /// Verify a quote
@@ -402,11 +595,51 @@ impl<EI: EnclaveIdentity> AkeEnclaveState<EI> {
 
     /// Verify an ias report
     pub fn verify_ias_report(&self, ias_report: VerificationReport) -> Result<()> {
+        self.verify_and_cache_ias_report(ias_report, None)
+    }
+
+    /// Verify an ias report, additionally rejecting it if it was issued more than `max_age_secs`
+    /// before `now_unix_secs`.
+    pub fn verify_ias_report_at(
+        &self,
+        ias_report: VerificationReport,
+        now_unix_secs: u64,
+        max_age_secs: u64,
+    ) -> Result<()> {
+        self.verify_and_cache_ias_report(ias_report, Some((now_unix_secs, max_age_secs)))
+    }
+
+    /// Verify an ias report's signature and contents, without caching it or consuming any
+    /// pending quote. Unlike `verify_ias_report`, the report need not answer one of our own
+    /// quotes, so this can be used to vet a report presented by someone else.
+    pub fn validate_ias_report(&self, ias_report: &VerificationReport) -> Result<()> {
+        ias_report.verify_signature(None)?;
+        let ias_data = VerificationReportData::try_from(ias_report)?;
+        ias_data.verify_data(IAS_VERSION, None, None)?;
+        Ok(())
+    }
+
+    //
+    // Details
+    //
+
+    /// Helper: Verify an ias report, optionally checking its age, and cache it.
+    fn verify_and_cache_ias_report(
+        &self,
+        ias_report: VerificationReport,
+        freshness: Option<(u64, u64)>,
+    ) -> Result<()> {
         // Check report signature first
         ias_report.verify_signature(None)?;
 
-        // Parse the data, extract the nonce
+        // Parse the data, and reject stale reports before consuming the cached quote, so a
+        // fresher report for the same quote can still be provided.
         let ias_data = VerificationReportData::try_from(&ias_report)?;
+        if let Some((now_unix_secs, max_age_secs)) = freshness {
+            ias_data.verify_freshness(now_unix_secs, max_age_secs)?;
+        }
+
+        // Extract the nonce
         let nonce = ias_data
             .nonce
             .as_ref()
@@ -432,10 +665,6 @@ impl<EI: EnclaveIdentity> AkeEnclaveState<EI> {
         Ok(())
     }
 
-    //
-    // Details
-    //
-
     /// Helper: Find peer connection among either the inbound set or outbound set
     fn get_peer_map_by_session(
         &self,
@@ -460,6 +689,7 @@ impl<EI: EnclaveIdentity> AkeEnclaveState<EI> {
         self_id: ResponderId,
         session_db: &Mutex<LruCache<S, Ready<Aes256Gcm>>>,
         req: S::Request,
+        max_sessions: Option<usize>,
     ) -> Result<(S::Response, S)>
     where
         Start: Transition<
@@ -475,6 +705,12 @@ impl<EI: EnclaveIdentity> AkeEnclaveState<EI> {
             >>::Error,
         >,
     {
+        // Turn away requests which could not be admitted before doing any work on them.
+        let at_limit = |len: usize| max_sessions.map_or(false, |max| len >= max);
+        if at_limit(session_db.lock()?.len()) {
+            return Err(Error::SessionLimitReached);
+        }
+
         let local_identity = self.kex_identity.clone();
         let ias_report = self.get_ias_report()?;
 
@@ -502,8 +738,15 @@ impl<EI: EnclaveIdentity> AkeEnclaveState<EI> {
 
         let (responder, auth_response) = responder.try_next(&mut csprng, auth_request)?;
         let session_id = S::from(responder.binding());
-        // This session is established as far as we are concerned.
-        session_db.lock()?.put(session_id.clone(), responder);
+        {
+            // Other sessions may have been established since the check above.
+            let mut sessions = session_db.lock()?;
+            if at_limit(sessions.len()) {
+                return Err(Error::SessionLimitReached);
+            }
+            // This session is established as far as we are concerned.
+            sessions.put(session_id.clone(), responder);
+        }
 
         let auth_response: Vec<u8> = auth_response.into();
         Ok((S::Response::from(auth_response), session_id))
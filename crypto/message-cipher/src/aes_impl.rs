@@ -177,4 +177,19 @@ mod tests {
             }
         });
     }
+
+    #[test]
+    fn verify_detects_corruption() {
+        run_with_several_seeds(|mut rng| {
+            let mut cipher = AesMessageCipher::new(&mut rng);
+
+            let ciphertext = cipher.encrypt_bytes(&mut rng, b"foobar".to_vec());
+            assert_eq!(cipher.verify_bytes(&ciphertext), Ok(()));
+
+            let mut corrupted = ciphertext.clone();
+            corrupted[0] ^= 1;
+            assert_eq!(cipher.verify_bytes(&corrupted), Err(CipherError::MacFailure));
+            assert_eq!(cipher.verify_bytes(&ciphertext[..4]), Err(CipherError::TooShort));
+        });
+    }
 }
@@ -28,6 +28,11 @@ pub trait MessageCipher {
     // Decrypt bytes produced by encrypt_bytes
     // An allocation is required when using this API, but we reuse the allocation made by caller.
     fn decrypt_bytes(&mut self, ciphertext: Vec<u8>) -> Result<Vec<u8>, CipherError>;
+    // Check that bytes produced by encrypt_bytes are intact, without handing out the plaintext.
+    // The AEAD only checks its tag while decrypting, so the plaintext is produced and dropped.
+    fn verify_bytes(&mut self, ciphertext: &[u8]) -> Result<(), CipherError> {
+        self.decrypt_bytes(ciphertext.to_vec()).map(|_plaintext| ())
+    }
 
     ////
     // Helpers that incorporate prost serialization
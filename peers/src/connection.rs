@@ -3,7 +3,7 @@
 //! Peer-to-Peer Networking with SGX.
 
 use crate::{
-    consensus_msg::{ConsensusMsg, TxProposeAAD},
+    consensus_msg::{ConsensusMsg, PeerAad},
     error::{PeerAttestationError, Result},
     traits::ConsensusConnection,
 };
@@ -249,10 +249,8 @@ impl<Enclave: ConsensusEnclaveProxy> ConsensusConnection for PeerConnection<Encl
             self.attest()?;
         }
 
-        let aad = mcserial::serialize(&TxProposeAAD {
-            origin_node: origin_node.clone(),
-            relayed_by: self.local_node_id().responder_id,
-        })?;
+        let aad = PeerAad::new(origin_node.clone(), self.local_node_id().responder_id)
+            .to_bytes()?;
 
         let request = self.enclave.txs_for_peer(
             &[encrypted_tx.clone()],
@@ -277,7 +275,12 @@ impl<Enclave: ConsensusEnclaveProxy> ConsensusConnection for PeerConnection<Encl
         ));
 
         let response = self.attested_call(|this| this.consensus_api_client.fetch_txs(&request))?;
-        let tx_contexts = self.enclave.peer_tx_propose(response.into())?;
+        // We asked for specific txs, so any entry that fails to decode fails the whole fetch.
+        let tx_contexts = self
+            .enclave
+            .peer_tx_propose(response.into())?
+            .into_iter()
+            .collect::<StdResult<Vec<TxContext>, _>>()?;
 
         Ok(tx_contexts)
     }
@@ -14,7 +14,7 @@ mod traits;
 
 pub use crate::{
     connection::PeerConnection,
-    consensus_msg::{ConsensusMsg, ConsensusMsgError, TxProposeAAD, VerifiedConsensusMsg},
+    consensus_msg::{ConsensusMsg, ConsensusMsgError, PeerAad, VerifiedConsensusMsg},
     error::{Error, Result},
     threaded_broadcaster::ThreadedBroadcaster,
     threaded_broadcaster_retry::{
@@ -20,7 +20,7 @@ cfg_if! {
         pub use sgx_sync as sync;
         pub use sgx_debug::eprintln;
 
-        pub use sgx_service::{report, verify_report, calc_sealed_data_size, seal_data, get_sealed_payload_sizes, unseal_data};
+        pub use sgx_service::{report, verify_report, calc_sealed_data_size, seal_data, seal_data_to_signer, get_sealed_payload_sizes, unseal_data};
     }
     else {
         extern crate std;
@@ -32,6 +32,6 @@ cfg_if! {
         pub use std::eprintln;
 
         mod mock_service;
-        pub use mock_service::{report, verify_report, calc_sealed_data_size, seal_data, get_sealed_payload_sizes, unseal_data};
+        pub use mock_service::{report, verify_report, calc_sealed_data_size, seal_data, seal_data_to_signer, get_sealed_payload_sizes, unseal_data};
     }
 }
@@ -69,6 +69,16 @@ pub fn seal_data(
     Ok(())
 }
 
+/// Seal data for another enclave from the same signer. The mock has no enclave identities, so
+/// this is the same as `seal_data`
+pub fn seal_data_to_signer(
+    plaintext: &[u8],
+    additional_mac_txt: &[u8],
+    out_buffer: &mut [u8],
+) -> Result<(), sgx_status_t> {
+    seal_data(plaintext, additional_mac_txt, out_buffer)
+}
+
 /// Extract sealed payload sizes from a sealed blob
 pub fn get_sealed_payload_sizes(sealed_data: &[u8]) -> Result<(u32, u32), sgx_status_t> {
     if sealed_data.len() < PREFIX_LEN as usize {
@@ -10,7 +10,7 @@ use sgx_types::{
     sgx_attributes_t, sgx_calc_sealed_data_size, sgx_create_report, sgx_get_add_mac_txt_len,
     sgx_get_encrypt_txt_len, sgx_report_data_t, sgx_report_t, sgx_seal_data_ex, sgx_sealed_data_t,
     sgx_status_t, sgx_target_info_t, sgx_unseal_data, sgx_verify_report, SGX_KEYPOLICY_MRENCLAVE,
-    TSEAL_DEFAULT_FLAGSMASK, TSEAL_DEFAULT_MISCMASK,
+    SGX_KEYPOLICY_MRSIGNER, TSEAL_DEFAULT_FLAGSMASK, TSEAL_DEFAULT_MISCMASK,
 };
 
 ////
@@ -49,8 +49,10 @@ pub fn verify_report(report: &sgx_report_t) -> Result<(), sgx_status_t> {
 // Sealing
 ////
 
-// To keep things simple, we only allow MRENCLAVE identity and default flags
+// To keep things simple, we only allow MRENCLAVE identity and default flags, except for data
+// handed over to a later enclave from the same signer, which is sealed to MRSIGNER identity
 const KEY_POLICY: u16 = SGX_KEYPOLICY_MRENCLAVE;
+const SIGNER_KEY_POLICY: u16 = SGX_KEYPOLICY_MRSIGNER;
 const ATTRIBUTE_MASK: sgx_attributes_t = sgx_attributes_t {
     flags: TSEAL_DEFAULT_FLAGSMASK,
     xfrm: 0,
@@ -91,11 +93,32 @@ pub fn seal_data(
     plaintext: &[u8],
     additional_mac_txt: &[u8],
     out_buffer: &mut [u8],
+) -> Result<(), sgx_status_t> {
+    seal_data_with_policy(KEY_POLICY, plaintext, additional_mac_txt, out_buffer)
+}
+
+/// Given a plaintext, seal it to MRSIGNER identity, so that any enclave from the same signer,
+/// with the same or a greater ISVSVN, can unseal it
+/// The entire outbuffer will be used
+/// It is an error if out_buffer.len() does not equal calc_sealed_data_size(plaintext.len())
+pub fn seal_data_to_signer(
+    plaintext: &[u8],
+    additional_mac_txt: &[u8],
+    out_buffer: &mut [u8],
+) -> Result<(), sgx_status_t> {
+    seal_data_with_policy(SIGNER_KEY_POLICY, plaintext, additional_mac_txt, out_buffer)
+}
+
+fn seal_data_with_policy(
+    key_policy: u16,
+    plaintext: &[u8],
+    additional_mac_txt: &[u8],
+    out_buffer: &mut [u8],
 ) -> Result<(), sgx_status_t> {
     match unsafe {
         #[allow(clippy::cast_ptr_alignment)]
         sgx_seal_data_ex(
-            KEY_POLICY,
+            key_policy,
             ATTRIBUTE_MASK,
             MISC_MASK,
             usize_for_sgx(additional_mac_txt.len())?,
@@ -41,7 +41,7 @@ pub mod proptest_fixtures;
 pub use block::*;
 pub use commitment::*;
 pub use compressed_commitment::*;
-pub use redacted_tx::RedactedTx;
+pub use redacted_tx::{redacted_output_keys, RedactedTx};
 
 /// Get the shared secret for a transaction output.
 ///
@@ -7,6 +7,7 @@ use crate::{
 };
 use alloc::vec::Vec;
 use digestible::Digestible;
+use keys::CompressedRistrettoPublic;
 use serde::{Deserialize, Serialize};
 
 /// A transaction that has had sensitive fields like inputs removed. It may be stored in plain text.
@@ -37,3 +38,56 @@ impl RedactedTx {
         TxHash::from(result)
     }
 }
+
+/// The public keys of every output of `redacted`, in order, e.g. for indexing the outputs of a
+/// newly formed block.
+pub fn redacted_output_keys(redacted: &[RedactedTx]) -> Vec<CompressedRistrettoPublic> {
+    redacted
+        .iter()
+        .flat_map(|redacted_tx| redacted_tx.outputs.iter())
+        .map(|tx_out| tx_out.public_key)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account_keys::AccountKey;
+    use keys::{FromRandom, RistrettoPrivate};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    // Output keys are listed for every output of every tx, in block order.
+    fn test_redacted_output_keys() {
+        let mut rng: StdRng = SeedableRng::from_seed([56u8; 32]);
+        let recipient = AccountKey::random(&mut rng);
+
+        let redacted: Vec<RedactedTx> = [3, 0, 2]
+            .iter()
+            .map(|num_outputs| {
+                let outputs = (0..*num_outputs)
+                    .map(|_i| {
+                        TxOut::new(
+                            45,
+                            &recipient.default_subaddress(),
+                            &RistrettoPrivate::from_random(&mut rng),
+                            Default::default(),
+                            &mut rng,
+                        )
+                        .unwrap()
+                    })
+                    .collect();
+                RedactedTx::new(outputs, Vec::new())
+            })
+            .collect();
+
+        let keys = redacted_output_keys(&redacted);
+        assert_eq!(keys.len(), 5);
+        assert_eq!(keys[0], redacted[0].outputs[0].public_key);
+        assert_eq!(keys[2], redacted[0].outputs[2].public_key);
+        assert_eq!(keys[3], redacted[2].outputs[0].public_key);
+        assert_eq!(keys[4], redacted[2].outputs[1].public_key);
+
+        assert!(redacted_output_keys(&[]).is_empty());
+    }
+}